@@ -0,0 +1,142 @@
+/* C API of the chip8vm library. Generated with cbindgen, do not edit. */
+
+#ifndef CHIP8VM_H
+#define CHIP8VM_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The status codes returned by the C API.
+ */
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  /**
+   * A required pointer argument was null.
+   */
+  CHIP8_STATUS_NULL_POINTER = 1,
+  /**
+   * An argument is out of range (e.g. a key index above 0xF, an empty
+   * ROM).
+   */
+  CHIP8_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The ROM does not fit in memory.
+   */
+  CHIP8_STATUS_ROM_TOO_LARGE = 3,
+  /**
+   * The output buffer is too small ; the required size is reported when
+   * the function allows it.
+   */
+  CHIP8_STATUS_BUFFER_TOO_SMALL = 4,
+  /**
+   * The snapshot is corrupted or comes from an incompatible version.
+   */
+  CHIP8_STATUS_INVALID_SNAPSHOT = 5,
+  /**
+   * The loaded program is done.
+   */
+  CHIP8_STATUS_FINISHED = 6,
+  /**
+   * An internal error occurred. The handle should be destroyed.
+   */
+  CHIP8_STATUS_PANIC = 7,
+} Chip8Status;
+
+/**
+ * CHIP 8 virtual machine.
+ */
+typedef struct Chip8 Chip8;
+
+/**
+ * The registers of the virtual machine, as seen from C.
+ */
+typedef struct Chip8Registers {
+  uint8_t v[16];
+  uint16_t i;
+  uint16_t pc;
+  uint8_t sp;
+  uint16_t stack[16];
+  uint8_t delay_timer;
+  uint8_t sound_timer;
+} Chip8Registers;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a new virtual machine. The handle must be released with
+ * 'chip8_destroy'. Return null if the allocation failed.
+ */
+Chip8 *chip8_create(void);
+
+/**
+ * Destroy a virtual machine created by 'chip8_create'. Null is ignored.
+ */
+void chip8_destroy(Chip8 *vm);
+
+/**
+ * Reset the virtual machine and load the ROM of 'len' bytes at 'data'.
+ */
+Chip8Status chip8_load(Chip8 *vm, const uint8_t *data, size_t len);
+
+/**
+ * Emulate the given number of CPU cycles then update the timers once, i.e.
+ * run a 60 Hz frame (use 'cycles' = clock / 60, e.g. 10 for 600 Hz).
+ * Return 'Finished' when the program is done.
+ */
+Chip8Status chip8_run_frame(Chip8 *vm, uint32_t cycles);
+
+/**
+ * Press ('pressed' != 0) or release the key of the virtual keypad at the
+ * given index (0x0 to 0xF).
+ */
+Chip8Status chip8_set_key(Chip8 *vm, uint8_t key, int32_t pressed);
+
+/**
+ * Copy the framebuffer to 'out', one byte per pixel (0 = off) row by row,
+ * and store its dimensions in 'width' and 'height'.
+ * If 'out' is null or too small, only the dimensions are stored and
+ * 'BufferTooSmall' is returned.
+ */
+Chip8Status chip8_framebuffer(const Chip8 *vm,
+                              uint8_t *out,
+                              size_t len,
+                              uint32_t *width,
+                              uint32_t *height);
+
+/**
+ * Store the registers of the virtual machine in 'registers'.
+ * Only the 16 oldest entries of a deeper stack are stored.
+ */
+Chip8Status chip8_get_registers(const Chip8 *vm, Chip8Registers *registers);
+
+/**
+ * Overwrite the registers of the virtual machine with 'registers'.
+ * The index, program counter and stack pointer must stay within bounds,
+ * the stack within the maximum depth of the virtual machine.
+ */
+Chip8Status chip8_set_registers(Chip8 *vm, const Chip8Registers *registers);
+
+/**
+ * Serialize the whole state of the virtual machine to 'out'.
+ * The size of the snapshot is always stored in 'written' ; if 'out' is
+ * null or too small 'BufferTooSmall' is returned and nothing is copied.
+ */
+Chip8Status chip8_snapshot(const Chip8 *vm, uint8_t *out, size_t len, size_t *written);
+
+/**
+ * Restore a snapshot taken by 'chip8_snapshot'. On failure the virtual
+ * machine is left untouched.
+ */
+Chip8Status chip8_restore(Chip8 *vm, const uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CHIP8VM_H */
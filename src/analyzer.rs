@@ -0,0 +1,420 @@
+/// Static analysis of the ROMs, to guess the platform they are written for
+/// when they are not in the ROM database.
+/// The analyzer follows the code reachable from the start address with the
+/// instruction decoder, without executing it, and counts the instructions of
+/// each family.
+use std::fmt;
+
+use crate::platform::Platform;
+use crate::vm::{HIRES_PROGRAM_START, PROGRAM_START};
+
+/// The instruction families, grouping the opcodes by the platform which
+/// introduced them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Family {
+    /// The instructions of the original CHIP 8 interpreter.
+    Chip8,
+    /// 0NNN : calls to machine code routines of the host computer.
+    MachineCall,
+    /// SUPER-CHIP scrolling : 00CN, 00FB and 00FC.
+    SchipScroll,
+    /// SUPER-CHIP high resolution : 00FE, 00FF and the 16x16 sprites DXY0.
+    SchipHires,
+    /// The other SUPER-CHIP instructions : 00FD, FX30, FX75 and FX85.
+    Schip,
+    /// XO-CHIP long loads : F000 NNNN.
+    XoChipLongLoad,
+    /// The other XO-CHIP instructions : 00DN, 5XY2, 5XY3, FN01, F002 and
+    /// FX3A.
+    XoChip,
+}
+
+/// All the families, in the order of the reports.
+pub const FAMILIES: [Family; 7] = [
+    Family::Chip8,
+    Family::MachineCall,
+    Family::SchipScroll,
+    Family::SchipHires,
+    Family::Schip,
+    Family::XoChipLongLoad,
+    Family::XoChip,
+];
+
+impl Family {
+    /// Return the platform introducing the family.
+    pub fn platform(self) -> Platform {
+        match self {
+            Family::Chip8 | Family::MachineCall => Platform::Chip8,
+            Family::SchipScroll | Family::SchipHires | Family::Schip => Platform::SuperChip,
+            Family::XoChipLongLoad | Family::XoChip => Platform::XoChip,
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Family::Chip8 => "CHIP-8",
+            Family::MachineCall => "machine code calls (0NNN)",
+            Family::SchipScroll => "SUPER-CHIP scrolling",
+            Family::SchipHires => "SUPER-CHIP high resolution",
+            Family::Schip => "SUPER-CHIP others",
+            Family::XoChipLongLoad => "XO-CHIP long loads (F000 NNNN)",
+            Family::XoChip => "XO-CHIP others",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How an instruction changes the program counter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Flow {
+    /// Continue with the next instruction.
+    Next,
+    /// Continue with the next instruction or skip it.
+    Skip,
+    /// Jump to the given address.
+    Jump(usize),
+    /// Call the subroutine at the given address, then continue with the next
+    /// instruction.
+    Call(usize),
+    /// Jump to an address only known at runtime (BNNN).
+    ComputedJump,
+    /// Return from a subroutine.
+    Return,
+    /// Stop the interpreter (00FD).
+    Exit,
+}
+
+/// A decoded instruction.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub family: Family,
+    pub flow: Flow,
+    /// The length in bytes : 4 for the XO-CHIP long loads, 2 otherwise.
+    pub length: usize,
+}
+
+/// Decode the instruction starting with the given opcode.
+/// Return None if it is not a valid instruction on any platform, e.g. data.
+pub fn decode(op: u16) -> Option<Instruction> {
+    let nnn = (op & 0x0FFF) as usize;
+    let instruction = |family, flow| {
+        Some(Instruction {
+            family,
+            flow,
+            length: 2,
+        })
+    };
+    use self::Family::*;
+    match (
+        (op & 0xF000) >> 12,
+        (op & 0x0F00) >> 8,
+        (op & 0x00F0) >> 4,
+        op & 0x000F,
+    ) {
+        (0x0, 0x0, 0xE, 0x0) => instruction(Chip8, Flow::Next),
+        (0x0, 0x0, 0xE, 0xE) => instruction(Chip8, Flow::Return),
+        (0x0, 0x0, 0xC, _) | (0x0, 0x0, 0xF, 0xB) | (0x0, 0x0, 0xF, 0xC) => {
+            instruction(SchipScroll, Flow::Next)
+        }
+        (0x0, 0x0, 0xF, 0xD) => instruction(Schip, Flow::Exit),
+        (0x0, 0x0, 0xF, 0xE) | (0x0, 0x0, 0xF, 0xF) => instruction(SchipHires, Flow::Next),
+        (0x0, 0x0, 0xD, _) => instruction(XoChip, Flow::Next),
+        // 0000 is far more likely to be padding than a machine code call
+        (0x0, _, _, _) if nnn != 0 => instruction(MachineCall, Flow::Next),
+        (0x1, _, _, _) => instruction(Chip8, Flow::Jump(nnn)),
+        (0x2, _, _, _) => instruction(Chip8, Flow::Call(nnn)),
+        (0x3, _, _, _) | (0x4, _, _, _) | (0x5, _, _, 0x0) | (0x9, _, _, 0x0) => {
+            instruction(Chip8, Flow::Skip)
+        }
+        (0x5, _, _, 0x2) | (0x5, _, _, 0x3) => instruction(XoChip, Flow::Next),
+        (0x6, _, _, _) | (0x7, _, _, _) | (0xA, _, _, _) | (0xC, _, _, _) => {
+            instruction(Chip8, Flow::Next)
+        }
+        (0x8, _, _, 0x0..=0x7) | (0x8, _, _, 0xE) => instruction(Chip8, Flow::Next),
+        (0xB, _, _, _) => instruction(Chip8, Flow::ComputedJump),
+        (0xD, _, _, 0x0) => instruction(SchipHires, Flow::Next),
+        (0xD, _, _, _) => instruction(Chip8, Flow::Next),
+        (0xE, _, 0x9, 0xE) | (0xE, _, 0xA, 0x1) => instruction(Chip8, Flow::Skip),
+        (0xF, 0x0, 0x0, 0x0) => Some(Instruction {
+            family: XoChipLongLoad,
+            flow: Flow::Next,
+            length: 4,
+        }),
+        (0xF, _, 0x0, 0x1) | (0xF, 0x0, 0x0, 0x2) | (0xF, _, 0x3, 0xA) => {
+            instruction(XoChip, Flow::Next)
+        }
+        (0xF, _, 0x3, 0x0) | (0xF, _, 0x7, 0x5) | (0xF, _, 0x8, 0x5) => {
+            instruction(Schip, Flow::Next)
+        }
+        (0xF, _, 0x0, 0x7)
+        | (0xF, _, 0x0, 0xA)
+        | (0xF, _, 0x1, 0x5)
+        | (0xF, _, 0x1, 0x8)
+        | (0xF, _, 0x1, 0xE)
+        | (0xF, _, 0x2, 0x9)
+        | (0xF, _, 0x3, 0x3)
+        | (0xF, _, 0x5, 0x5)
+        | (0xF, _, 0x6, 0x5) => instruction(Chip8, Flow::Next),
+        _ => None,
+    }
+}
+
+/// How sure the analyzer is of its recommendation.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    fn lower(self) -> Confidence {
+        match self {
+            Confidence::High => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The result of the analysis of a ROM.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// The number of reachable instructions of each family, in the order of
+    /// 'FAMILIES'.
+    pub counts: [usize; 7],
+    /// The addresses of the invalid opcodes reached, i.e. data or code of an
+    /// unknown extension.
+    pub invalid: Vec<usize>,
+    /// The number of computed jumps (BNNN), whose targets are not followed.
+    pub computed_jumps: usize,
+    /// The jumps and calls leaving the ROM.
+    pub outside: usize,
+    /// The number of 8XY6 and 8XYE shifts with X different from Y.
+    pub shifts_with_vy: usize,
+    /// Does the ROM start with the 0x1260 entry trick of the two-page hi-res
+    /// interpreter ?
+    pub hires_trick: bool,
+    /// The recommended platform.
+    pub platform: Platform,
+    pub confidence: Confidence,
+    /// The reasons of the recommendation and the caveats.
+    pub notes: Vec<String>,
+}
+
+impl Analysis {
+    /// Return the number of reachable instructions of the given family.
+    pub fn count(&self, family: Family) -> usize {
+        FAMILIES
+            .iter()
+            .position(|&f| f == family)
+            .map_or(0, |index| self.counts[index])
+    }
+
+    /// Return the number of reachable instructions.
+    pub fn instructions(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "reachable instructions : {}", self.instructions())?;
+        for (family, &count) in FAMILIES.iter().zip(self.counts.iter()) {
+            if count > 0 {
+                writeln!(f, "  {} : {}", family, count)?;
+            }
+        }
+        write!(
+            f,
+            "recommended platform : {} ({} confidence)",
+            self.platform, self.confidence
+        )?;
+        for note in self.notes.iter() {
+            write!(f, "\n  - {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Analyze the given ROM, loaded at the given address.
+pub fn analyze(rom: &[u8], load_address: usize) -> Analysis {
+    let mut analysis = Analysis {
+        counts: [0; 7],
+        invalid: Vec::new(),
+        computed_jumps: 0,
+        outside: 0,
+        shifts_with_vy: 0,
+        hires_trick: load_address == PROGRAM_START && rom.starts_with(&[0x12, 0x60]),
+        platform: Platform::Chip8,
+        confidence: Confidence::Low,
+        notes: Vec::new(),
+    };
+
+    // the opcode at the given address, if it is in the ROM
+    let fetch = |address: usize, length: usize| -> Option<u32> {
+        let offset = address.checked_sub(load_address)?;
+        let bytes = rom.get(offset..offset + length)?;
+        Some(bytes.iter().fold(0, |op, &byte| op << 8 | byte as u32))
+    };
+
+    let mut visited = vec![false; rom.len()];
+    // the patch of the hi-res interpreter, jumped to by the entry trick, is
+    // machine code : follow the program after it instead
+    let mut pending = vec![if analysis.hires_trick {
+        HIRES_PROGRAM_START
+    } else {
+        load_address
+    }];
+    while let Some(address) = pending.pop() {
+        let op = match fetch(address, 2) {
+            Some(op) => op as u16,
+            None => {
+                analysis.outside += 1;
+                continue;
+            }
+        };
+        if visited[address - load_address] {
+            continue;
+        }
+        visited[address - load_address] = true;
+
+        let instruction = match decode(op) {
+            Some(instruction) if fetch(address, instruction.length).is_some() => instruction,
+            _ => {
+                analysis.invalid.push(address);
+                continue;
+            }
+        };
+        let index = FAMILIES
+            .iter()
+            .position(|&family| family == instruction.family)
+            .unwrap();
+        analysis.counts[index] += 1;
+        if op & 0xF00E == 0x8006 && (op & 0x0F00) >> 8 != (op & 0x00F0) >> 4 {
+            analysis.shifts_with_vy += 1;
+        }
+
+        let next = address + instruction.length;
+        match instruction.flow {
+            Flow::Next => pending.push(next),
+            Flow::Skip => {
+                // XO-CHIP skips the whole long load
+                let skipped = match fetch(next, 2) {
+                    Some(0xF000) => 4,
+                    _ => 2,
+                };
+                pending.push(next + skipped);
+                pending.push(next);
+            }
+            Flow::Jump(target) => pending.push(target),
+            Flow::Call(target) => {
+                pending.push(next);
+                pending.push(target);
+            }
+            Flow::ComputedJump => analysis.computed_jumps += 1,
+            Flow::Return | Flow::Exit => {}
+        }
+    }
+    analysis.invalid.sort();
+
+    recommend(&mut analysis);
+    analysis
+}
+
+/// Fill in the recommended platform of the analysis, with its confidence and
+/// notes.
+fn recommend(analysis: &mut Analysis) {
+    let count = |families: &[Family]| -> usize {
+        families.iter().map(|&family| analysis.count(family)).sum()
+    };
+    let xochip = count(&[Family::XoChipLongLoad, Family::XoChip]);
+    let schip = count(&[Family::SchipScroll, Family::SchipHires, Family::Schip]);
+    let machine_calls = count(&[Family::MachineCall]);
+    let certainty = |count: usize| {
+        if count > 1 {
+            Confidence::High
+        } else {
+            Confidence::Medium
+        }
+    };
+
+    let mut notes = Vec::new();
+    // the XO-CHIP platform only implements the SUPER-CHIP subset of
+    // XO-CHIP, as for the XO-CHIP ROMs of the database
+    let (platform, mut confidence) = if xochip > 0 {
+        notes.push(format!(
+            "{} XO-CHIP instructions, which the virtual machine does not \
+             implement : the ROM will not run correctly",
+            xochip
+        ));
+        (Platform::XoChip, Confidence::Low)
+    } else if schip > 0 {
+        notes.push(format!("{} SUPER-CHIP instructions", schip));
+        (Platform::SuperChip, certainty(schip))
+    } else if analysis.hires_trick {
+        notes.push(
+            "the 0x1260 entry trick of the two-page hi-res interpreter (64x64 \
+             display)"
+                .to_owned(),
+        );
+        (Platform::Chip8Hires, Confidence::High)
+    } else if machine_calls > 0 {
+        notes.push(format!(
+            "{} calls to machine code routines : the ROM was written for \
+             the COSMAC VIP and its routines are not emulated",
+            machine_calls
+        ));
+        (Platform::Chip8, certainty(machine_calls))
+    } else if analysis.shifts_with_vy > 0 {
+        notes.push(format!(
+            "{} shifts of VY into VX, as on the COSMAC VIP",
+            analysis.shifts_with_vy
+        ));
+        (Platform::Chip8, Confidence::Medium)
+    } else {
+        notes.push(
+            "only CHIP-8 instructions : the quirks cannot be told apart \
+             statically"
+                .to_owned(),
+        );
+        (Platform::Chip8, Confidence::Low)
+    };
+    if !analysis.invalid.is_empty() {
+        notes.push(format!(
+            "{} invalid opcodes reached (first at {:0>3X}) : data or an \
+             unknown extension",
+            analysis.invalid.len(),
+            analysis.invalid[0]
+        ));
+        confidence = confidence.lower();
+    }
+    if analysis.computed_jumps > 0 {
+        notes.push(format!(
+            "{} computed jumps (BNNN) not followed : their jump quirk matters",
+            analysis.computed_jumps
+        ));
+    }
+    if analysis.outside > 0 {
+        notes.push(format!(
+            "{} jumps or calls leaving the ROM not followed",
+            analysis.outside
+        ));
+    }
+
+    analysis.platform = platform;
+    analysis.confidence = confidence;
+    analysis.notes = notes;
+}
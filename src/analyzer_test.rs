@@ -0,0 +1,69 @@
+use super::analyzer::{analyze, decode, Confidence, Family, Flow};
+use super::platform::Platform;
+
+#[test]
+fn decode_instructions() {
+    let instruction = decode(0x1234).unwrap();
+    assert_eq!(instruction.family, Family::Chip8);
+    assert_eq!(instruction.flow, Flow::Jump(0x234));
+    assert_eq!(decode(0x00FF).unwrap().family, Family::SchipHires);
+    assert_eq!(decode(0x00C4).unwrap().family, Family::SchipScroll);
+    assert_eq!(decode(0x00FD).unwrap().flow, Flow::Exit);
+    assert_eq!(decode(0xF000).unwrap().length, 4);
+    assert_eq!(decode(0x0123).unwrap().family, Family::MachineCall);
+    assert_eq!(decode(0xB200).unwrap().flow, Flow::ComputedJump);
+    assert!(decode(0x0000).is_none());
+    assert!(decode(0x8008).is_none());
+    assert!(decode(0xE000).is_none());
+}
+
+#[test]
+fn analyze_chip8() {
+    // CLS ; CALL 208 ; JP 204 (halt) ; data ; RET
+    let rom = [0x00, 0xE0, 0x22, 0x08, 0x12, 0x04, 0xFF, 0xFF, 0x00, 0xEE];
+    let analysis = analyze(&rom, 0x200);
+    assert_eq!(analysis.instructions(), 4);
+    assert_eq!(analysis.count(Family::Chip8), 4);
+    assert!(analysis.invalid.is_empty());
+    assert_eq!(analysis.platform, Platform::Chip8);
+    assert_eq!(analysis.confidence, Confidence::Low);
+
+    // SHR V1, V2 ; SYS 123 ; halt
+    let rom = [0x81, 0x26, 0x01, 0x23, 0x12, 0x04];
+    let analysis = analyze(&rom, 0x200);
+    assert_eq!(analysis.shifts_with_vy, 1);
+    assert_eq!(analysis.count(Family::MachineCall), 1);
+    assert_eq!(analysis.platform, Platform::Chip8);
+    assert_eq!(analysis.confidence, Confidence::Medium);
+    // hi-res entry trick : the patch is skipped, the program starts at 2C0
+    let mut rom = vec![0x12, 0x60];
+    rom.resize(0xC0, 0xFF);
+    rom.extend_from_slice(&[0x02, 0x30, 0x12, 0xC2]);
+    let analysis = analyze(&rom, 0x200);
+    assert!(analysis.hires_trick);
+    assert_eq!(analysis.instructions(), 2);
+    assert!(analysis.invalid.is_empty());
+    assert_eq!(analysis.platform, Platform::Chip8Hires);
+}
+
+#[test]
+fn analyze_extensions() {
+    // HIGH ; SCR ; DRW V0, V1, 0 ; EXIT
+    let rom = [0x00, 0xFF, 0x00, 0xFB, 0xD0, 0x10, 0x00, 0xFD];
+    let analysis = analyze(&rom, 0x200);
+    assert_eq!(analysis.count(Family::SchipHires), 2);
+    assert_eq!(analysis.count(Family::SchipScroll), 1);
+    assert_eq!(analysis.platform, Platform::SuperChip);
+    assert_eq!(analysis.confidence, Confidence::High);
+
+    // SE V0, 0 ; LD I, long 0300 (skipped as a whole) ; JP 20A (invalid)
+    let rom = [
+        0x30, 0x00, 0xF0, 0x00, 0x03, 0x00, 0x12, 0x0A, 0x12, 0x08, 0xFF, 0xFF,
+    ];
+    let analysis = analyze(&rom, 0x200);
+    assert_eq!(analysis.count(Family::XoChipLongLoad), 1);
+    assert_eq!(analysis.invalid, vec![0x20A]);
+    assert_eq!(analysis.platform, Platform::XoChip);
+    assert_eq!(analysis.confidence, Confidence::Low);
+    assert!(analysis.notes[0].starts_with("1 XO-CHIP instructions"));
+}
@@ -1,14 +1,30 @@
 use std::cmp;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
 
-use time::{Duration, Instant};
+use time::{Duration, Instant, OffsetDateTime};
 
 use crate::input;
 use chip8vm;
-use chip8vm::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8vm::cosmac::Vip;
+use chip8vm::diagnostics::StrictMode;
+use chip8vm::display::Display;
+use chip8vm::font::Font;
 use chip8vm::keypad::Keystate;
+use chip8vm::loader::{self, Program};
+use chip8vm::machine::Machine;
+use chip8vm::megachip::Sound;
+use chip8vm::palette::Palette;
+use chip8vm::persistence::PersistenceMode;
+use chip8vm::platform::Platform;
+use chip8vm::png;
+use chip8vm::quirks::Quirks;
+use chip8vm::record::{RecordFormat, Recorder};
+use chip8vm::symbols::SymbolTable;
+use chip8vm::syscall;
 use chip8vm::vm::{Chip8, CPU_CLOCK, TIMERS_CLOCK};
 
 /// Structure facilitating the configuration of a 'Chip8Application'.
@@ -16,7 +32,7 @@ use chip8vm::vm::{Chip8, CPU_CLOCK, TIMERS_CLOCK};
 /// to allow chaining them inside the Chip8Application::new function call.
 pub struct Chip8Config {
     /// The title of the emulator window.
-    pub window_title: &'static str,
+    pub window_title: String,
     /// The desired width for the emulator window.
     /// NB : this is just a hint, the application may resize to reach a proper
     /// aspect ratio.
@@ -27,37 +43,137 @@ pub struct Chip8Config {
     pub window_height: u16,
     /// The keyboard configuration. QWERTY by default.
     pub keypad_binding: input::KeyboardBinding,
+    /// The game controller configuration. D-pad on 2/4/6/8 by default.
+    pub controller_binding: input::ControllerBinding,
     /// The virtual machine's desired CPU clock in Hz (cycles per second).
     pub vm_cpu_clock: u32,
+    /// The address where the ROM is loaded and started. The entry point of
+    /// the platform by default.
+    pub load_address: Option<usize>,
+    /// The platform the ROM is written for. Modern CHIP 8 by default.
+    pub platform: Platform,
+    /// The font set. The font of the platform by default.
+    pub font: Option<Font>,
+    /// The implementation options of the virtual machine.
+    pub quirks: Quirks,
+    /// The palette used to render the display. Classic theme by default.
+    pub palette: Palette,
+    /// The phosphor persistence model used for rendering. Off by default.
+    pub persistence: PersistenceMode,
+    /// The size of a CHIP 8 pixel in the screenshots and recordings, in
+    /// pixels.
+    pub screenshot_scale: u32,
+    /// If set, record the emulation from the start to this file ("-" for the
+    /// standard output).
+    pub record_filepath: Option<PathBuf>,
+    /// The recording format. If not set, guessed from the file extension.
+    pub record_format: Option<RecordFormat>,
+    /// Should the programs be able to call the debugging routines of the host
+    /// with 0001, 0002 and 0003 ? Off by default.
+    pub debug_syscalls: bool,
+    /// If set, run the virtual machine in the strict mode with these options,
+    /// reporting the bugs of the program when the emulation ends.
+    pub strict: Option<StrictMode>,
+    /// If set, the maximum depth of the stack, None for an unlimited stack.
+    /// The depth of the platform by default.
+    pub stack_depth: Option<Option<usize>>,
+    /// Should the stack be kept in memory, as on the COSMAC VIP ? Off by
+    /// default.
+    pub stack_in_memory: bool,
+    /// The symbols of the program, naming the subroutines in the backtraces
+    /// logged with the errors.
+    pub symbols: Option<SymbolTable>,
+    /// Should FX0A wait for the release of the key, as on the COSMAC VIP ?
+    /// On by default.
+    pub key_wait_release: bool,
+    /// If set, run the ROM on the emulated COSMAC VIP with these ROM images
+    /// instead of the CHIP 8 virtual machine.
+    pub vip: Option<VipImages>,
+}
+
+/// The ROM images run by the emulated COSMAC VIP.
+#[derive(Clone)]
+pub struct VipImages {
+    /// The CHIP 8 interpreter, loaded at 0x0000.
+    pub interpreter: Vec<u8>,
+    /// The monitor ROM, which boots the VIP if given.
+    pub monitor: Option<Vec<u8>>,
 }
 
 /// Macro to avoid boilerplate setter code.
 macro_rules! config_set_param {
-    ($setter_name: ident, $param_name: ident, $param_type: ty) => (
-        pub fn $setter_name(mut self, $param_name: $param_type)
-            -> Chip8Config {
-            self.$param_name = $param_name; self
+    ($setter_name: ident, $param_name: ident, $param_type: ty) => {
+        pub fn $setter_name(mut self, $param_name: $param_type) -> Chip8Config {
+            self.$param_name = $param_name;
+            self
         }
-    )
+    };
 }
 
 impl Chip8Config {
     /// Create and return the default set of options.
     pub fn new() -> Chip8Config {
         Chip8Config {
-            window_title: "",
+            window_title: String::new(),
             window_width: 64,
             window_height: 32,
             keypad_binding: input::KeyboardBinding::QWERTY,
+            controller_binding: input::ControllerBinding::Directional,
             vm_cpu_clock: CPU_CLOCK,
+            load_address: None,
+            platform: Platform::Modern,
+            font: None,
+            quirks: Quirks::default(),
+            palette: Palette::default(),
+            persistence: PersistenceMode::Off,
+            screenshot_scale: 8,
+            record_filepath: None,
+            record_format: None,
+            debug_syscalls: false,
+            strict: None,
+            stack_depth: None,
+            stack_in_memory: false,
+            symbols: None,
+            key_wait_release: true,
+            vip: None,
         }
     }
 
-    config_set_param!(w_title, window_title, &'static str);
+    config_set_param!(w_title, window_title, String);
     config_set_param!(w_width, window_width, u16);
     config_set_param!(w_height, window_height, u16);
     config_set_param!(key_binds, keypad_binding, input::KeyboardBinding);
+    config_set_param!(pad_binds, controller_binding, input::ControllerBinding);
     config_set_param!(vm_cpu_clock, vm_cpu_clock, u32);
+    config_set_param!(load_address, load_address, Option<usize>);
+    config_set_param!(font, font, Option<Font>);
+    config_set_param!(quirks, quirks, Quirks);
+
+    /// Set the platform, along with the quirks to its preset.
+    pub fn platform(mut self, platform: Platform) -> Chip8Config {
+        self.platform = platform;
+        self.quirks = platform.quirks();
+        self
+    }
+
+    /// Return the address where the ROM is loaded and started.
+    pub fn entry_point(&self) -> usize {
+        self.load_address
+            .unwrap_or_else(|| self.platform.memory_map().entry_point)
+    }
+
+    config_set_param!(palette, palette, Palette);
+    config_set_param!(persistence, persistence, PersistenceMode);
+    config_set_param!(screenshot_scale, screenshot_scale, u32);
+    config_set_param!(record_filepath, record_filepath, Option<PathBuf>);
+    config_set_param!(record_format, record_format, Option<RecordFormat>);
+    config_set_param!(debug_syscalls, debug_syscalls, bool);
+    config_set_param!(strict, strict, Option<StrictMode>);
+    config_set_param!(stack_depth, stack_depth, Option<Option<usize>>);
+    config_set_param!(stack_in_memory, stack_in_memory, bool);
+    config_set_param!(symbols, symbols, Option<SymbolTable>);
+    config_set_param!(key_wait_release, key_wait_release, bool);
+    config_set_param!(vip, vip, Option<VipImages>);
 }
 
 /// A command for the Chip8 virtual machine.
@@ -68,6 +184,12 @@ pub enum Chip8VMCommand {
     UpdateRunStatus(bool),
     /// Communicate an update in the status of the key at the given index.
     UpdateKeyStatus(usize, Keystate),
+    /// Communicate an update in the status of the key at the given index of
+    /// the second keypad (CHIP-8X), ignored by the other platforms.
+    UpdateSecondKeyStatus(usize, Keystate),
+    /// Enable or disable the emission of a 'Chip8UICommand::CaptureFrame'
+    /// at every 60 Hz frame.
+    UpdateFrameCaptureStatus(bool),
     /// Reset the virtual machine to its default state.
     Reset,
     /// Shutdown the virtual machine.
@@ -80,11 +202,17 @@ pub enum Chip8UICommand {
     /// Signal whether the emulator should emit a sound or not (true whenever
     /// the VM's sound timer is not zero).
     UpdateBeepingStatus(bool),
+    /// Start playing the given digitised sound (MEGA-CHIP), replacing the
+    /// one playing, or stop it.
+    UpdateSound(Option<Sound>),
     /// A drawing command for the UI, communicating the information needed to
     /// do so. As of now, the 'Display' structure is pretty much that so we can
     /// affort to pass a copy of it.
     /// Should be called only when needed (display flagged dirty).
     UpdateDisplay(Display),
+    /// A snapshot of the display taken at the end of a 60 Hz frame, emitted
+    /// at every frame while the frame capture is enabled (e.g. for recording).
+    CaptureFrame(Display),
     /// Signal that the emulation is finished, emitted either after a
     /// 'Chip8VMCommand::Quit' signal was received or when the virtual machine
     /// finished the execution of its loaded program.
@@ -129,21 +257,30 @@ impl<'a> Chip8Emulator<'a> {
         }
     }
 
-    /// Run the emulator application after loading the given ROM.
+    /// Run the emulator application after loading the given ROM, on the
+    /// COSMAC VIP if the 'Chip8Config' gives its ROM images and on the CHIP 8
+    /// virtual machine otherwise.
     /// Return true if all went well, false otherwise.
     /// TODO : more flexible run function (maybe a LoadRomCommand ?)
-    pub fn run_rom(&mut self, rom_filepath: &Path) -> bool {
+    pub fn run_rom(&mut self, rom: &[u8]) -> bool {
         // VM creation and ROM loading
-        let mut vm = Chip8::new();
-        info!("loading the ROM file \"{}\"...", rom_filepath.display());
-        let oerror = vm.load(rom_filepath);
-        if oerror.is_none() {
-            info!("successfully loaded the ROM file.");
+        if self.config.vip.is_some() {
+            match load_vip(&self.config, rom) {
+                Some(vip) => self.run(vip),
+                None => false,
+            }
         } else {
-            error!("loading error : {}", oerror.unwrap());
-            return false;
+            match load_vm(&self.config, rom) {
+                Some(vm) => self.run(vm),
+                None => false,
+            }
         }
+    }
 
+    /// Run the emulator application with the given machine, its program
+    /// already loaded.
+    /// Return true if all went well, false otherwise.
+    pub fn run<M: Machine + Send + 'static>(&mut self, mut vm: M) -> bool {
         // Communication channels
         let (tx_ui, rx_ui) = channel::<Chip8UICommand>();
         let (tx_vm, rx_vm) = channel::<Chip8VMCommand>();
@@ -162,10 +299,121 @@ impl<'a> Chip8Emulator<'a> {
     }
 }
 
-/// Emulation loop simulating the CHIP 8 virtual machine and communicating back
-/// to the emulator's backend implementation by feeding Chip8UI
-pub fn exec_vm(
-    vm: &mut Chip8,
+/// The maximum size of the ROM files, containers included.
+const ROM_FILE_SIZE_MAX: u64 = 1 << 20;
+
+/// Read the ROM file at the given path, or the standard input for "-", and
+/// extract the program from its container.
+/// Stop reading past the maximum file size, the size of the program being
+/// checked when loading.
+/// Return None, after logging why, if the ROM could not be read.
+pub fn read_rom(rom_filepath: &Path) -> Option<Program> {
+    let mut rom = Vec::new();
+    let limit = ROM_FILE_SIZE_MAX + 1;
+    let result = if rom_filepath == Path::new("-") {
+        info!("reading the ROM from the standard input...");
+        io::stdin().take(limit).read_to_end(&mut rom)
+    } else {
+        info!("reading the ROM file \"{}\"...", rom_filepath.display());
+        fs::File::open(rom_filepath).and_then(|file| file.take(limit).read_to_end(&mut rom))
+    };
+    if let Err(why) = result {
+        error!("cannot read the ROM : {}", why);
+        return None;
+    }
+    match loader::open(rom, Some(rom_filepath)) {
+        Ok(program) => Some(program),
+        Err(why) => {
+            error!("cannot load the ROM : {}", why);
+            None
+        }
+    }
+}
+
+/// Create a new virtual machine configured as asked and load the given ROM
+/// into it.
+/// Return None, after logging why, if the ROM could not be loaded.
+pub fn load_vm(config: &Chip8Config, rom: &[u8]) -> Option<Chip8> {
+    let mut vm = Chip8::new();
+    if let Err(why) = vm.set_platform(config.platform) {
+        error!("invalid platform : {}", why);
+        return None;
+    }
+    if let Some(ref font) = config.font {
+        if let Err(why) = vm.set_font(font.clone()) {
+            error!("invalid font : {}", why);
+            return None;
+        }
+    }
+    if let Some(address) = config.load_address {
+        vm.set_load_address(address);
+    }
+    vm.set_quirks(config.quirks);
+    if let Some(depth) = config.stack_depth {
+        vm.set_stack_depth(depth);
+    }
+    vm.set_stack_in_memory(config.stack_in_memory);
+    vm.set_symbols(config.symbols.clone());
+    vm.set_key_wait_release(config.key_wait_release);
+    vm.set_strict_mode(config.strict);
+    if config.debug_syscalls {
+        syscall::install_debug_syscalls(&mut vm);
+    }
+    match vm.load_bytes(rom) {
+        Ok(()) => {
+            info!("successfully loaded the ROM.");
+            Some(vm)
+        }
+        Err(why) => {
+            error!("loading error : {}", why);
+            None
+        }
+    }
+}
+
+/// Create a new COSMAC VIP with the ROM images from the 'Chip8Config' and
+/// load the given ROM into it. The platform only selects the display height
+/// of the interpreter.
+/// Return None, after logging why, if the ROM could not be loaded.
+pub fn load_vip(config: &Chip8Config, rom: &[u8]) -> Option<Vip> {
+    let images = config.vip.as_ref()?;
+    let mut vip = Vip::new();
+    if let Some(ref monitor) = images.monitor {
+        if let Err(why) = vip.load_monitor(monitor) {
+            error!("invalid VIP monitor : {}", why);
+            return None;
+        }
+    }
+    if let Err(why) = vip.load_interpreter(&images.interpreter) {
+        error!("invalid VIP interpreter : {}", why);
+        return None;
+    }
+    match config.platform {
+        Platform::Chip8 => {}
+        Platform::Chip8Hires => vip.set_display_height(64).unwrap(),
+        platform => warn!(
+            "the VIP runs its interpreter, not the {} platform",
+            platform
+        ),
+    }
+    match vip.load_bytes(rom) {
+        Ok(()) => {
+            info!("successfully loaded the ROM on the VIP.");
+            Some(vip)
+        }
+        Err(why) => {
+            error!("loading error : {}", why);
+            None
+        }
+    }
+}
+
+/// Emulation loop simulating the given machine and communicating back to the
+/// emulator's backend implementation by feeding Chip8UI
+/// The machine runs a frame of 'cpu_clock / TIMERS_CLOCK' cycles at every
+/// 60 Hz tick.
+pub fn exec_vm<M: Machine>(
+    vm: &mut M,
     cpu_clock: u32,
     tx: Sender<Chip8UICommand>,
     rx: Receiver<Chip8VMCommand>,
@@ -180,18 +428,16 @@ pub fn exec_vm(
 
     // time handling is in nanoseconds
     let mut t = Instant::now();
-    let mut last_t_cpu = Instant::now();
     let mut last_t_timers = t;
     let timers_step = Duration::nanoseconds(10i64.pow(9) / (TIMERS_CLOCK as i64));
-    let cpu_step = Duration::nanoseconds(10i64.pow(9) / (cpu_clock as i64));
+    let cycles_per_frame = cmp::max(1, cpu_clock / TIMERS_CLOCK);
 
     // VM state
     let mut running = true;
     let mut beeping = false;
-    let mut waiting_for_key = false;
-    // avoid triggering multiple 'wait for key' instructions at once
-    // especially with a high CPU clock
-    let mut wait_for_key_last_pressed = 0xFF;
+    // the serial number of the digitised sound playing, if any
+    let mut sound_serial = None;
+    let mut capturing = false;
 
     'vm: loop {
         // Command from the UI
@@ -199,25 +445,17 @@ pub fn exec_vm(
             // non-blocking receiving function
             Ok(vm_command) => match vm_command {
                 UpdateRunStatus(run) => running = run,
-                UpdateKeyStatus(index, state) => match state {
-                    Keystate::Pressed => {
-                        if waiting_for_key && (index != wait_for_key_last_pressed) {
-                            vm.end_wait_for_key(index);
-                            wait_for_key_last_pressed = index;
-                        } else {
-                            vm.keypad.set_key_state(index, state);
-                        }
-                    }
-                    Keystate::Released => {
-                        wait_for_key_last_pressed = 0xFF;
-                        if !waiting_for_key {
-                            vm.keypad.set_key_state(index, state);
-                        }
+                UpdateFrameCaptureStatus(capture) => capturing = capture,
+                UpdateKeyStatus(index, state) => vm.keypad_mut().set_key_state(index, state),
+                UpdateSecondKeyStatus(index, state) => {
+                    if let Some(keypad) = vm.second_keypad_mut() {
+                        keypad.set_key_state(index, state);
                     }
-                },
+                }
                 Reset => vm.reset(),
                 Quit => {
                     running = false;
+                    log_diagnostics(vm);
                     info!("terminating the virtual machine thread...");
                     tx.send(Finished).unwrap();
                     break 'vm;
@@ -226,35 +464,30 @@ pub fn exec_vm(
             _ => {}
         }
 
-        // CPU
+        // CPU and timers, a frame at a time
         t = Instant::now();
-        if t - last_t_cpu >= cpu_step {
-            last_t_cpu = t;
-            if running && !waiting_for_key {
-                vm.emulate_cycle();
-                if vm.display.dirty {
-                    let display = vm.display.clone();
-                    tx.send(UpdateDisplay(display)).unwrap();
-                    vm.display.dirty = false;
-                }
-            }
-            waiting_for_key = vm.is_waiting_for_key();
-        }
-
-        // Timers
-        t = Instant::now(); // this may be overkill
         if t - last_t_timers >= timers_step {
             last_t_timers = t;
             if running {
-                if vm.delay_timer > 0 {
-                    vm.delay_timer -= 1;
+                // the program being done, the machine idles until the UI quits
+                vm.run_frame(cycles_per_frame);
+                if vm.display().dirty {
+                    let display = vm.display().clone();
+                    tx.send(UpdateDisplay(display)).unwrap();
+                    vm.display_mut().dirty = false;
                 }
-                if vm.sound_timer > 0 {
-                    vm.sound_timer -= 1;
-                    if beeping != (vm.sound_timer > 0) {
-                        beeping = !beeping;
-                        tx.send(UpdateBeepingStatus(beeping)).unwrap();
-                    }
+                if beeping != vm.is_beeping() {
+                    beeping = !beeping;
+                    tx.send(UpdateBeepingStatus(beeping)).unwrap();
+                }
+                let sound = vm.digitised_sound();
+                if sound_serial != sound.map(|(_, serial)| serial) {
+                    sound_serial = sound.map(|(_, serial)| serial);
+                    let sound = sound.map(|(sound, _)| sound.clone());
+                    tx.send(UpdateSound(sound)).unwrap();
+                }
+                if capturing {
+                    tx.send(CaptureFrame(vm.display().clone())).unwrap();
                 }
             }
         }
@@ -266,16 +499,181 @@ pub fn exec_vm(
     }
 }
 
+/// Log the findings of the strict mode of the given machine, if any.
+pub fn log_diagnostics<M: Machine>(vm: &M) {
+    let diagnostics = vm.diagnostics();
+    if !diagnostics.is_empty() {
+        warn!("the strict mode found {} issues :", diagnostics.len());
+        for diagnostic in diagnostics {
+            warn!("{}", diagnostic);
+        }
+    }
+}
+
 /// Return the best (pixel_scale, width, height) combination with the given
-pub fn get_display_size(w_width: u16, w_height: u16) -> (u16, u16, u16) {
-    let scale_w = w_width / (DISPLAY_WIDTH as u16);
-    let scale_h = w_height / (DISPLAY_HEIGHT as u16);
+/// window dimensions, for a display of the given size in CHIP 8 pixels.
+pub fn get_display_size(
+    w_width: u16,
+    w_height: u16,
+    display_size: (usize, usize),
+) -> (u16, u16, u16) {
+    let (display_width, display_height) = (display_size.0 as u16, display_size.1 as u16);
+    let scale_w = w_width / display_width;
+    let scale_h = w_height / display_height;
     let scale = cmp::min(scale_w, scale_h);
 
     // adjust to the smallest scale and recompute the window dimensions
-    (
-        scale,
-        scale * (DISPLAY_WIDTH as u16),
-        scale * (DISPLAY_HEIGHT as u16),
-    )
+    (scale, scale * display_width, scale * display_height)
+}
+
+/// Return a timestamped filepath in the current directory with the given
+/// extension, for screenshots and recordings.
+pub fn timestamped_filepath(extension: &str) -> PathBuf {
+    let timestamp = OffsetDateTime::now_utc().format("%Y%m%d-%H%M%S");
+    PathBuf::from(format!("chip8-{}.{}", timestamp, extension))
+}
+
+/// Save the given display as a PNG image at the given filepath, using the
+/// palette and scale from the given 'Chip8Config'.
+pub fn save_screenshot(config: &Chip8Config, display: &Display, filepath: &Path) -> io::Result<()> {
+    let image = png::encode(display, &config.palette, config.screenshot_scale as usize);
+    fs::write(filepath, image)?;
+    info!("saved a screenshot to \"{}\"", filepath.display());
+    Ok(())
+}
+
+/// A recording to a file or the standard output.
+/// The encoder is sized from the first display recorded ; when the display
+/// resolution changes (e.g. in the SUPER-CHIP hires mode), the recording
+/// goes on in a new numbered file, or fails on the standard output.
+pub struct Recording {
+    filepath: PathBuf,
+    format: RecordFormat,
+    palette: Palette,
+    scale: usize,
+    /// The writer of the next file, until its first display is recorded.
+    writer: Option<Box<dyn Write>>,
+    /// The current encoder, and the size of the displays it records.
+    recorder: Option<Recorder<Box<dyn Write>>>,
+    size: (usize, usize),
+    /// The number of files written, and of frames recorded in the previous ones.
+    parts: u32,
+    frames: u64,
+}
+
+impl Recording {
+    /// Return the number of frames recorded so far.
+    pub fn frames(&self) -> u64 {
+        self.frames + self.recorder.as_ref().map_or(0, |r| r.frames())
+    }
+
+    /// Record the given display as the next 60 Hz frame.
+    pub fn record_frame(&mut self, display: &Display) -> io::Result<()> {
+        let size = display.size();
+        if self.recorder.is_some() && self.size != size {
+            let recorder = self.recorder.take().unwrap();
+            self.frames += recorder.frames();
+            recorder.finish()?;
+            if is_stdout(&self.filepath) {
+                return Err(io::Error::other(format!(
+                    "the display resolution changed to {}x{}",
+                    size.0, size.1
+                )));
+            }
+            self.parts += 1;
+            let filepath = numbered_filepath(&self.filepath, self.parts);
+            self.writer = Some(Box::new(io::BufWriter::new(fs::File::create(&filepath)?)));
+            info!(
+                "the display resolution changed to {}x{} : recording to \"{}\"",
+                size.0,
+                size.1,
+                filepath.display()
+            );
+        }
+        if self.recorder.is_none() {
+            let writer = self.writer.take().unwrap();
+            let recorder = Recorder::new(writer, self.format, &self.palette, size, self.scale)?;
+            self.recorder = Some(recorder);
+            self.size = size;
+        }
+        self.recorder.as_mut().unwrap().record_frame(display)
+    }
+
+    /// Finish the recording, flushing the current file.
+    pub fn finish(self) -> io::Result<()> {
+        match (self.recorder, self.writer) {
+            (Some(recorder), _) => recorder.finish().map(|_| ()),
+            (None, Some(mut writer)) => writer.flush(),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Return true if the given recording filepath means the standard output.
+fn is_stdout(filepath: &Path) -> bool {
+    filepath == Path::new("-")
+}
+
+/// Return the filepath of the given part of a recording, e.g. "maze-2.gif"
+/// for the second part of "maze.gif".
+pub fn numbered_filepath(filepath: &Path, part: u32) -> PathBuf {
+    let stem = filepath
+        .file_stem()
+        .map_or("".into(), |s| s.to_string_lossy());
+    let name = match filepath.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, part, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, part),
+    };
+    filepath.with_file_name(name)
+}
+
+/// Start recording to the given filepath, "-" meaning the standard output.
+/// The format is taken from the 'Chip8Config' if set, and guessed from the
+/// file extension otherwise (Y4M for the standard output, GIF by default).
+/// Return None, after logging why, if the recording could not be started.
+pub fn start_recording(config: &Chip8Config, filepath: &Path) -> Option<Recording> {
+    let to_stdout = is_stdout(filepath);
+    let format = config.record_format.unwrap_or_else(|| {
+        match filepath.extension().and_then(|e| e.to_str()) {
+            Some(extension) => RecordFormat::from_name(extension),
+            None => None,
+        }
+        .unwrap_or(if to_stdout {
+            RecordFormat::Y4m
+        } else {
+            RecordFormat::Gif
+        })
+    });
+    let writer: Box<dyn Write> = if to_stdout {
+        Box::new(io::stdout())
+    } else {
+        match fs::File::create(filepath) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(why) => {
+                error!("cannot create \"{}\" : {}", filepath.display(), why);
+                return None;
+            }
+        }
+    };
+    info!("recording to \"{}\" as {:?}", filepath.display(), format);
+    Some(Recording {
+        filepath: filepath.to_path_buf(),
+        format,
+        palette: config.palette.clone(),
+        scale: config.screenshot_scale as usize,
+        writer: Some(writer),
+        recorder: None,
+        size: (0, 0),
+        parts: 1,
+        frames: 0,
+    })
+}
+
+/// Finish the given recording, logging any error.
+pub fn stop_recording(recording: Recording) {
+    let frames = recording.frames();
+    match recording.finish() {
+        Ok(_) => info!("recording finished ({} frames)", frames),
+        Err(why) => error!("cannot finish the recording : {}", why),
+    }
 }
@@ -1,35 +1,77 @@
+use std::cmp;
 use std::sync::mpsc::{Receiver, Sender};
 
+use self::sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use self::sdl2::event::Event;
 use self::sdl2::keyboard::Keycode;
 use self::sdl2::pixels::{Color, PixelFormatEnum};
 use self::sdl2::rect::Rect;
 use self::sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use self::sdl2::video::WindowContext;
+use self::sdl2::AudioSubsystem;
 use sdl2;
 
-use self::chip8vm::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use self::chip8vm::display::Display;
 use self::chip8vm::keypad::Keystate::{Pressed, Released};
+use self::chip8vm::megachip::Sound;
+use self::chip8vm::palette::{Palette, Rgb, Theme, THEMES};
+use self::chip8vm::persistence::{Persistence, PersistenceMode};
+use self::chip8vm::record::RecordFormat;
 use crate::chip8app::Chip8UICommand::*;
 use crate::chip8app::Chip8VMCommand::*;
 use crate::chip8app::{
-    get_display_size, Chip8Config, Chip8EmulatorBackend, Chip8UICommand, Chip8VMCommand,
+    get_display_size, save_screenshot, start_recording, stop_recording, timestamped_filepath,
+    Chip8Config, Chip8EmulatorBackend, Chip8UICommand, Chip8VMCommand,
 };
 use chip8vm;
 
-// todo : make this a backend-agnostic option
-const COLOR_PIXEL_OFF: Color = Color {
-    r: 0,
-    g: 0,
-    b: 0,
-    a: 0xFF,
-};
-const COLOR_PIXEL_ON: Color = Color {
-    r: 0xFF,
-    g: 0xFF,
-    b: 0xFF,
-    a: 0xFF,
-};
+/// Convert a palette colour to its SDL2 equivalent.
+fn sdl_color(color: Rgb) -> Color {
+    Color::RGB(color.r, color.g, color.b)
+}
+
+/// The value of the unsigned 8 bits samples for silence.
+const SILENCE: u8 = 0x80;
+
+/// Plays a digitised sound from the SDL2 audio thread, once or repeated.
+struct SoundPlayer {
+    sound: Sound,
+    /// The index of the next sample to play.
+    position: usize,
+}
+
+impl AudioCallback for SoundPlayer {
+    type Channel = u8;
+
+    fn callback(&mut self, out: &mut [u8]) {
+        for sample in out.iter_mut() {
+            if self.position >= self.sound.samples.len() && self.sound.looping {
+                self.position = 0;
+            }
+            *sample = match self.sound.samples.get(self.position) {
+                Some(&value) => {
+                    self.position += 1;
+                    value
+                }
+                None => SILENCE,
+            };
+        }
+    }
+}
+
+/// Open an audio device playing the given sound at its sample rate, the
+/// samples being converted by SDL2 to the format of the output.
+/// If the operation fails, return a String explaining why.
+fn play_sound(audio: &AudioSubsystem, sound: Sound) -> Result<AudioDevice<SoundPlayer>, String> {
+    let desired = AudioSpecDesired {
+        freq: Some(sound.rate as i32),
+        channels: Some(1),
+        samples: None,
+    };
+    let device = audio.open_playback(None, &desired, |_| SoundPlayer { sound, position: 0 })?;
+    device.resume();
+    Ok(device)
+}
 
 /// The SDL2 backend for the Chip8 emulator.
 pub struct Chip8BackendSDL2;
@@ -38,11 +80,13 @@ impl Chip8BackendSDL2 {
     fn render_display<'c>(
         t: &'c TextureCreator<WindowContext>,
         c: &mut WindowCanvas,
-        display: Display,
+        display: &Display,
+        palette: &Palette,
+        persistence: Option<&Persistence>,
         scale: u32,
     ) -> Texture<'c> {
-        let display_width = DISPLAY_WIDTH as u32;
-        let display_height = DISPLAY_HEIGHT as u32;
+        let display_width = display.width() as u32;
+        let display_height = display.height() as u32;
         let pixel_size = scale as i32;
 
         let mut texture = t
@@ -52,15 +96,23 @@ impl Chip8BackendSDL2 {
                 display_height * scale,
             )
             .unwrap();
+        // the CHIP-8X and MEGA-CHIP colours are drawn as is, without
+        // persistence
+        let persistence = persistence.filter(|_| display.uses_palette());
         c.with_texture_canvas(&mut texture, |texture_canvas| {
-            texture_canvas.set_draw_color(COLOR_PIXEL_OFF);
+            let background = display.background_color(palette);
+            texture_canvas.set_draw_color(sdl_color(background));
             texture_canvas.clear();
-            texture_canvas.set_draw_color(COLOR_PIXEL_ON);
             for y in 0i32..(display_height as i32) {
                 for x in 0i32..(display_width as i32) {
                     // TODO : precompute the used Rect ?
                     // since they only change at window resize...
-                    if display.gfx[y as usize][x as usize] == 1u8 {
+                    let color = match persistence {
+                        Some(p) => p.color(x as usize, y as usize, palette),
+                        None => display.color(x as usize, y as usize, palette),
+                    };
+                    if color != background {
+                        texture_canvas.set_draw_color(sdl_color(color));
                         let _ = texture_canvas.fill_rect(Rect::new(
                             x * pixel_size,
                             y * pixel_size,
@@ -74,6 +126,40 @@ impl Chip8BackendSDL2 {
         .unwrap();
         texture
     }
+
+    /// Render the given display and copy it to the window's canvas.
+    /// If a persistence state is given, the pixels are rendered from it.
+    /// The scale is reduced if needed for the display to fit in the window,
+    /// e.g. in the MEGA-CHIP mode.
+    fn draw_display(
+        t: &TextureCreator<WindowContext>,
+        c: &mut WindowCanvas,
+        display: &Display,
+        palette: &Palette,
+        persistence: Option<&Persistence>,
+        scale: u32,
+    ) {
+        let (w_width, w_height) = c.output_size().unwrap();
+        let fit = cmp::min(
+            w_width / display.width().max(1) as u32,
+            w_height / display.height().max(1) as u32,
+        );
+        let scale = cmp::max(cmp::min(scale, fit), 1);
+        c.set_draw_color(sdl_color(display.background_color(palette)));
+        c.clear();
+        let texture = Chip8BackendSDL2::render_display(t, c, display, palette, persistence, scale);
+        c.copy(
+            &texture,
+            None,
+            Some(Rect::new(
+                0,
+                0,
+                display.width() as u32 * scale,
+                display.height() as u32 * scale,
+            )),
+        )
+        .unwrap();
+    }
 }
 
 impl Chip8EmulatorBackend for Chip8BackendSDL2 {
@@ -88,16 +174,31 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
         info!("starting the main application / rendering thread");
 
         // window dimensions
-        let (scale, width, height) = get_display_size(config.window_width, config.window_height);
+        let (scale, width, height) = get_display_size(
+            config.window_width,
+            config.window_height,
+            config.platform.display_size(),
+        );
         info!("chosen scale : {} pixels per CHIP 8 pixel", scale);
 
         // window creation and rendering setup
         info!("creating the application window...");
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
+        let controller_subsystem = sdl_context.game_controller().unwrap();
         let mut timer_subsystem = sdl_context.timer().unwrap();
+        // the emulation goes on without sound if there is no audio device
+        let audio_subsystem = match sdl_context.audio() {
+            Ok(audio) => Some(audio),
+            Err(why) => {
+                warn!("cannot initialize the audio : {}", why);
+                None
+            }
+        };
+        // the device playing the digitised sound, if any
+        let mut sound_device: Option<AudioDevice<SoundPlayer>> = None;
         let window = video_subsystem
-            .window(config.window_title, width as u32, height as u32)
+            .window(&config.window_title, width as u32, height as u32)
             .position_centered()
             .opengl()
             .build()
@@ -105,15 +206,21 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
         let mut canvas = window.into_canvas().accelerated().build().unwrap();
         let texture_creator = canvas.texture_creator();
 
-        let pixel_size = scale as u32;
-        let display_width = DISPLAY_WIDTH as u32;
-        let display_height = DISPLAY_HEIGHT as u32;
-        canvas.set_draw_color(COLOR_PIXEL_OFF);
+        let mut palette = config.palette.clone();
+        // the built-in theme currently used, if any, for cycling
+        let mut theme = THEMES.iter().cloned().find(|t| t.palette() == palette);
+        canvas.set_draw_color(sdl_color(palette.color(0)));
         canvas.clear();
         canvas.present();
 
         let mut event_pump = sdl_context.event_pump().unwrap();
         let key_binds = crate::input::get_sdl_key_bindings(&config.keypad_binding);
+        let second_key_binds = crate::input::get_sdl_second_key_bindings(&config.keypad_binding);
+        let pad_binds = crate::input::get_sdl_controller_bindings(&config.controller_binding);
+        // the opened game controllers, which must be kept alive to receive
+        // their events ; SDL2 emits a 'ControllerDeviceAdded' event for every
+        // controller already plugged in at startup
+        let mut controllers = Vec::new();
         // avoid spamming the channel with redundant 'pressed' events
         let mut keys_pressed = Vec::new();
 
@@ -129,6 +236,22 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
 
         // Emulation state
         let mut paused = false;
+        // the last display received, to redraw it when the palette changes
+        let mut last_display: Option<Display> = None;
+        // the recording in progress, if any
+        let mut recording = match config.record_filepath {
+            Some(ref filepath) => start_recording(config, filepath),
+            None => None,
+        };
+        // the phosphor persistence state, if enabled : the display is then
+        // rendered at every 60 Hz frame instead of at every display update
+        let mut persistence = match config.persistence {
+            PersistenceMode::Off => None,
+            mode => Some(Persistence::new(mode)),
+        };
+        if recording.is_some() || persistence.is_some() {
+            tx.send(UpdateFrameCaptureStatus(true)).unwrap();
+        }
 
         'main: loop {
             // Frame time
@@ -162,10 +285,56 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
                                 info!("Reinitializing the virtual machine.");
                                 tx.send(Reset).unwrap();
                             }
+                            // take a screenshot on F12
+                            Keycode::F12 => {
+                                if let Some(ref display) = last_display {
+                                    let filepath = timestamped_filepath("png");
+                                    if let Err(why) = save_screenshot(config, display, &filepath) {
+                                        warn!("cannot save the screenshot : {}", why);
+                                    }
+                                }
+                            }
+                            // start or stop recording on F10
+                            Keycode::F10 => {
+                                match recording.take() {
+                                    Some(r) => stop_recording(r),
+                                    None => {
+                                        let extension = match config.record_format {
+                                            Some(RecordFormat::Y4m) => "y4m",
+                                            Some(RecordFormat::RawRgb) => "rgb",
+                                            _ => "gif",
+                                        };
+                                        let filepath = timestamped_filepath(extension);
+                                        recording = start_recording(config, &filepath);
+                                    }
+                                }
+                                let capture = recording.is_some() || persistence.is_some();
+                                tx.send(UpdateFrameCaptureStatus(capture)).unwrap();
+                            }
+                            // cycle through the built-in themes on F2
+                            Keycode::F2 => {
+                                let next = theme.map_or(Theme::Classic, |t| t.next());
+                                info!("switching to the \"{}\" theme", next.name());
+                                palette = next.palette();
+                                theme = Some(next);
+                                if let Some(ref display) = last_display {
+                                    Chip8BackendSDL2::draw_display(
+                                        &texture_creator,
+                                        &mut canvas,
+                                        display,
+                                        &palette,
+                                        persistence.as_ref(),
+                                        scale as u32,
+                                    );
+                                }
+                            }
                             _ => {
                                 if !paused {
-                                    if let Some(index) = key_binds.get(&keycode.unwrap()) {
+                                    let keycode = keycode.unwrap();
+                                    if let Some(index) = key_binds.get(&keycode) {
                                         tx.send(UpdateKeyStatus(*index, Pressed)).unwrap();
+                                    } else if let Some(index) = second_key_binds.get(&keycode) {
+                                        tx.send(UpdateSecondKeyStatus(*index, Pressed)).unwrap();
                                     }
                                 }
                             }
@@ -179,7 +348,43 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
                                 break;
                             }
                         }
-                        if let Some(index) = key_binds.get(&keycode.unwrap()) {
+                        let keycode = keycode.unwrap();
+                        if let Some(index) = key_binds.get(&keycode) {
+                            tx.send(UpdateKeyStatus(*index, Released)).unwrap();
+                        } else if let Some(index) = second_key_binds.get(&keycode) {
+                            tx.send(UpdateSecondKeyStatus(*index, Released)).unwrap();
+                        }
+                    }
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        if controller_subsystem.is_game_controller(which) {
+                            match controller_subsystem.open(which) {
+                                Ok(controller) => {
+                                    info!("game controller connected : {}", controller.name());
+                                    controllers.push(controller);
+                                }
+                                Err(why) => warn!("cannot open game controller : {}", why),
+                            }
+                        }
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        controllers.retain(|controller| {
+                            if controller.instance_id() as u32 == which {
+                                info!("game controller disconnected : {}", controller.name());
+                                false
+                            } else {
+                                true
+                            }
+                        });
+                    }
+                    Event::ControllerButtonDown { button, .. } => {
+                        if !paused {
+                            if let Some(index) = pad_binds.get(&button) {
+                                tx.send(UpdateKeyStatus(*index, Pressed)).unwrap();
+                            }
+                        }
+                    }
+                    Event::ControllerButtonUp { button, .. } => {
+                        if let Some(index) = pad_binds.get(&button) {
                             tx.send(UpdateKeyStatus(*index, Released)).unwrap();
                         }
                     }
@@ -195,28 +400,53 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
                         UpdateBeepingStatus(beeping) => {
                             // TODO
                             if beeping {
-                                println!("BEEP !");
+                                debug!("BEEP !");
+                            }
+                        }
+                        UpdateSound(sound) => {
+                            if let Some(device) = sound_device.take() {
+                                device.pause();
+                            }
+                            if let (Some(ref audio), Some(sound)) = (&audio_subsystem, sound) {
+                                match play_sound(audio, sound) {
+                                    Ok(device) => sound_device = Some(device),
+                                    Err(why) => warn!("cannot play the sound : {}", why),
+                                }
                             }
                         }
                         UpdateDisplay(display) => {
-                            let texture = Chip8BackendSDL2::render_display(
-                                &texture_creator,
-                                &mut canvas,
-                                display,
-                                scale as u32,
-                            );
-                            canvas
-                                .copy(
-                                    &texture,
+                            if persistence.is_none() {
+                                Chip8BackendSDL2::draw_display(
+                                    &texture_creator,
+                                    &mut canvas,
+                                    &display,
+                                    &palette,
                                     None,
-                                    Some(Rect::new(
-                                        0,
-                                        0,
-                                        display_width * pixel_size,
-                                        display_height * pixel_size,
-                                    )),
-                                )
-                                .unwrap();
+                                    scale as u32,
+                                );
+                            }
+                            last_display = Some(display);
+                        }
+                        CaptureFrame(display) => {
+                            if let Some(ref mut p) = persistence {
+                                p.push_frame(&display);
+                                Chip8BackendSDL2::draw_display(
+                                    &texture_creator,
+                                    &mut canvas,
+                                    &display,
+                                    &palette,
+                                    Some(p),
+                                    scale as u32,
+                                );
+                            }
+                            if let Some(ref mut r) = recording {
+                                if let Err(why) = r.record_frame(&display) {
+                                    error!("recording error : {}", why);
+                                    stop_recording(recording.take().unwrap());
+                                    let capture = persistence.is_some();
+                                    tx.send(UpdateFrameCaptureStatus(capture)).unwrap();
+                                }
+                            }
                         }
                         Finished => break 'main,
                     }
@@ -232,6 +462,10 @@ impl Chip8EmulatorBackend for Chip8BackendSDL2 {
             update_timer += dt as f32;
         }
 
+        if let Some(r) = recording {
+            stop_recording(r);
+        }
+
         info!("terminating the main application thread")
     }
 }
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use self::chip8vm::display::Display;
+use self::chip8vm::keypad::Keystate::{self, Pressed, Released};
+use self::chip8vm::palette::{Palette, Rgb};
+use self::chip8vm::persistence::{Persistence, PersistenceMode};
+use crate::chip8app::Chip8UICommand::*;
+use crate::chip8app::Chip8VMCommand::*;
+use crate::chip8app::{Chip8Config, Chip8EmulatorBackend, Chip8UICommand, Chip8VMCommand};
+use crate::logger;
+use chip8vm;
+
+/// The upper half block character : its foreground colour draws the top
+/// pixel and its background colour the bottom one.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// Terminals only report key presses, repeated while the key is held : a key
+/// is considered released when no press was received for this long.
+/// This assumes the usual auto-repeat settings of the terminals, i.e. a first
+/// repeat ~500ms after the press : the first timeout must exceed this delay.
+const KEY_FIRST_TIMEOUT: Duration = Duration::from_millis(550);
+/// The timeout once the key repeats, assuming a repeat rate of at least
+/// ~10 per second (usually 25 to 30).
+const KEY_REPEAT_TIMEOUT: Duration = Duration::from_millis(120);
+
+/// The minimum delay between two redraws, to avoid flooding the terminal.
+const FRAME_DURATION: Duration = Duration::from_millis(1000 / 60);
+
+/// How long the start of an escape sequence waits for the rest of it : a lone
+/// ESC still alone after this delay is the Escape key.
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The terminal backend for the Chip8 emulator, drawing the display with
+/// Unicode half blocks and ANSI escape sequences.
+/// Relies on 'stty' to put the terminal in raw mode.
+pub struct Chip8BackendTerminal;
+
+/// Get the length of the escape sequence at the start of the given input,
+/// which starts with ESC : a CSI sequence (ESC '[', parameter and
+/// intermediate bytes, then a final byte), an SS3 sequence (ESC 'O' and a
+/// final byte), or ESC followed by any other byte (e.g. Alt + key).
+/// Return None if the input ends before the sequence, e.g. with a lone ESC.
+pub fn escape_sequence_len(bytes: &[u8]) -> Option<usize> {
+    match bytes.get(1) {
+        None => None,
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|&b| (0x40..=0x7E).contains(&b))
+            .map(|end| end + 3),
+        Some(b'O') if bytes.len() < 3 => None,
+        Some(b'O') => Some(3),
+        Some(_) => Some(2),
+    }
+}
+
+/// Get the command updating the state of the key bound to the given
+/// character on the first or the second keypad, if any.
+fn key_command(
+    key_binds: &HashMap<char, usize>,
+    second_key_binds: &HashMap<char, usize>,
+    c: char,
+    state: Keystate,
+) -> Option<Chip8VMCommand> {
+    match (key_binds.get(&c), second_key_binds.get(&c)) {
+        (Some(&index), _) => Some(UpdateKeyStatus(index, state)),
+        (None, Some(&index)) => Some(UpdateSecondKeyStatus(index, state)),
+        (None, None) => None,
+    }
+}
+
+/// Run 'stty' on the terminal with the given arguments, returning its output.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ))
+    }
+}
+
+/// Puts the terminal in raw mode for as long as it is alive.
+struct RawMode {
+    /// The terminal settings to restore, as given by 'stty -g'.
+    saved: String,
+}
+
+impl RawMode {
+    /// The logging is held back meanwhile, the standard error being the
+    /// terminal too.
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        logger::hold();
+        // alternate screen, cursor hidden
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        if let Err(why) = stty(&[&self.saved]) {
+            error!("cannot restore the terminal settings : {}", why);
+        }
+        logger::release();
+    }
+}
+
+/// The state of a key of the virtual keypad, as guessed from the input.
+struct HeldKey {
+    /// When the last press of the key was received.
+    last_seen: Instant,
+    /// Has the key been repeated since it was first pressed ?
+    repeated: bool,
+}
+
+impl Chip8BackendTerminal {
+    /// Draw the given display on the terminal.
+    /// If a persistence state is given, the pixels are rendered from it.
+    fn render_display(
+        out: &mut dyn Write,
+        display: &Display,
+        palette: &Palette,
+        persistence: Option<&Persistence>,
+        status: &str,
+    ) -> io::Result<()> {
+        // the CHIP-8X and MEGA-CHIP colours are drawn as is, without
+        // persistence
+        let color = |x: usize, y: usize| -> Rgb {
+            match persistence {
+                Some(p) if display.uses_palette() => p.color(x, y, palette),
+                _ => display.color(x, y, palette),
+            }
+        };
+        let height = display.gfx.len();
+        let width = if height > 0 { display.gfx[0].len() } else { 0 };
+
+        let mut frame = String::new();
+        let mut current: Option<(Rgb, Rgb)> = None;
+        for row in 0..height.div_ceil(2) {
+            frame.push_str(&format!("\x1b[{};1H", row + 1));
+            for x in 0..width {
+                let top = color(x, 2 * row);
+                let bottom = if 2 * row + 1 < height {
+                    color(x, 2 * row + 1)
+                } else {
+                    display.background_color(palette)
+                };
+                if current != Some((top, bottom)) {
+                    frame.push_str(&format!(
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                        top.r, top.g, top.b, bottom.r, bottom.g, bottom.b
+                    ));
+                    current = Some((top, bottom));
+                }
+                frame.push(UPPER_HALF_BLOCK);
+            }
+        }
+        frame.push_str(&format!(
+            "\x1b[0m\x1b[{};1H\x1b[2K{}",
+            height.div_ceil(2) + 1,
+            status
+        ));
+        out.write_all(frame.as_bytes())?;
+        out.flush()
+    }
+}
+
+impl Chip8EmulatorBackend for Chip8BackendTerminal {
+    /// Initialize and run the emulation.
+    /// Will panic if the terminal cannot be put in raw mode.
+    fn exec(
+        &mut self,
+        config: &Chip8Config,
+        tx: Sender<Chip8VMCommand>,
+        rx: Receiver<Chip8UICommand>,
+    ) {
+        info!("starting the terminal application thread");
+        warn!("the screenshots and the theme cycling are only available with SDL2");
+        let raw_mode = RawMode::enable().expect("cannot put the terminal in raw mode");
+
+        // stdin is read in a separate thread since reading blocks
+        let (tx_input, rx_input) = channel::<Vec<u8>>();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            let mut buffer = [0u8; 64];
+            loop {
+                match stdin.lock().read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx_input.send(buffer[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let key_binds = crate::input::get_char_key_bindings(&config.keypad_binding);
+        let second_key_binds = crate::input::get_char_second_key_bindings(&config.keypad_binding);
+        // the keys held, by character
+        let mut held_keys: HashMap<char, HeldKey> = HashMap::new();
+        // the start of an escape sequence cut by the end of the last input,
+        // and when it was received
+        let mut pending: Vec<u8> = Vec::new();
+        let mut pending_since = Instant::now();
+        let stdout = io::stdout();
+
+        // Emulation state
+        let mut paused = false;
+        let mut last_display: Option<Display> = None;
+        let mut dirty = false;
+        let mut last_draw = Instant::now();
+        let mut persistence = match config.persistence {
+            PersistenceMode::Off => None,
+            mode => Some(Persistence::new(mode)),
+        };
+        if persistence.is_some() {
+            tx.send(UpdateFrameCaptureStatus(true)).unwrap();
+        }
+
+        'main: loop {
+            // Terminal input
+            while let Ok(input) = rx_input.try_recv() {
+                let now = Instant::now();
+                let mut bytes = pending.split_off(0);
+                bytes.extend_from_slice(&input);
+                let mut i = 0;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        // skip the escape sequences (arrows, function keys...),
+                        // waiting for the next input if cut
+                        0x1B => match escape_sequence_len(&bytes[i..]) {
+                            Some(len) => {
+                                i += len;
+                                continue;
+                            }
+                            None => {
+                                pending = bytes[i..].to_vec();
+                                pending_since = now;
+                                break;
+                            }
+                        },
+                        // quit on Ctrl-C ; the virtual machine may already
+                        // be gone
+                        0x03 => {
+                            paused = true;
+                            let _ = tx.send(Quit);
+                        }
+                        // toggle pause on Return
+                        b'\r' | b'\n' => {
+                            tx.send(UpdateRunStatus(paused)).unwrap();
+                            paused = !paused;
+                            dirty = true;
+                        }
+                        // reset on backspace
+                        0x7F | 0x08 => {
+                            info!("Reinitializing the virtual machine.");
+                            tx.send(Reset).unwrap();
+                        }
+                        byte => {
+                            let c = (byte as char).to_ascii_lowercase();
+                            match held_keys.get_mut(&c) {
+                                Some(key) => {
+                                    key.last_seen = now;
+                                    key.repeated = true;
+                                }
+                                None => {
+                                    let command =
+                                        key_command(&key_binds, &second_key_binds, c, Pressed);
+                                    if let (false, Some(command)) = (paused, command) {
+                                        tx.send(command).unwrap();
+                                        held_keys.insert(
+                                            c,
+                                            HeldKey {
+                                                last_seen: now,
+                                                repeated: false,
+                                            },
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    i += 1;
+                }
+            }
+
+            // quit on a lone Escape, dropping the other cut sequences ; the
+            // virtual machine may already be gone
+            if !pending.is_empty() && pending_since.elapsed() >= ESCAPE_TIMEOUT {
+                if pending == [0x1B] {
+                    paused = true;
+                    let _ = tx.send(Quit);
+                }
+                pending.clear();
+            }
+
+            // Key release heuristic
+            let now = Instant::now();
+            held_keys.retain(|&c, key| {
+                let timeout = if key.repeated {
+                    KEY_REPEAT_TIMEOUT
+                } else {
+                    KEY_FIRST_TIMEOUT
+                };
+                if now - key.last_seen > timeout {
+                    if let Some(command) = key_command(&key_binds, &second_key_binds, c, Released) {
+                        tx.send(command).unwrap();
+                    }
+                    false
+                } else {
+                    true
+                }
+            });
+
+            // Commands from the VM
+            while let Ok(ui_command) = rx.try_recv() {
+                match ui_command {
+                    UpdateBeepingStatus(beeping) => {
+                        if beeping {
+                            // terminal bell
+                            print!("\x07");
+                        }
+                    }
+                    // the digitised sounds cannot be played in a terminal
+                    UpdateSound(_) => {}
+                    UpdateDisplay(display) => {
+                        last_display = Some(display);
+                        dirty |= persistence.is_none();
+                    }
+                    CaptureFrame(display) => {
+                        if let Some(ref mut p) = persistence {
+                            p.push_frame(&display);
+                            dirty = true;
+                        }
+                    }
+                    Finished => break 'main,
+                }
+            }
+
+            // Rendering, throttled to 60 FPS
+            if dirty && last_draw.elapsed() >= FRAME_DURATION {
+                if let Some(ref display) = last_display {
+                    let status = if paused {
+                        "PAUSED - Return: resume, Backspace: reset, Escape: quit"
+                    } else {
+                        "Return: pause, Backspace: reset, Escape: quit"
+                    };
+                    let status = format!("{} | {}", config.window_title, status);
+                    let mut out = stdout.lock();
+                    if let Err(why) = Chip8BackendTerminal::render_display(
+                        &mut out,
+                        display,
+                        &config.palette,
+                        persistence.as_ref(),
+                        &status,
+                    ) {
+                        error!("cannot draw to the terminal : {}", why);
+                    }
+                }
+                dirty = false;
+                last_draw = Instant::now();
+            }
+
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        drop(raw_mode);
+        info!("terminating the terminal application thread")
+    }
+}
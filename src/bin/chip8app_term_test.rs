@@ -0,0 +1,14 @@
+use super::chip8app_term::escape_sequence_len;
+
+#[test]
+fn escape_sequences() {
+    // arrow up (CSI), F1 (SS3), Alt + a, and F5 with its parameters
+    assert_eq!(escape_sequence_len(b"\x1b[Ax"), Some(3));
+    assert_eq!(escape_sequence_len(b"\x1bOPx"), Some(3));
+    assert_eq!(escape_sequence_len(b"\x1bax"), Some(2));
+    assert_eq!(escape_sequence_len(b"\x1b[15~"), Some(5));
+    // a lone ESC or a sequence cut by the end of the input waits for more
+    assert_eq!(escape_sequence_len(b"\x1b"), None);
+    assert_eq!(escape_sequence_len(b"\x1b[1"), None);
+    assert_eq!(escape_sequence_len(b"\x1bO"), None);
+}
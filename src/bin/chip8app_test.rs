@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::channel;
+
+use chip8vm::display::Display;
+use chip8vm::keypad::Keystate::Pressed;
+use chip8vm::platform::Platform;
+use chip8vm::vm::Chip8;
+
+use super::chip8app::Chip8VMCommand::*;
+use super::chip8app::{exec_vm, numbered_filepath, start_recording, stop_recording, Chip8Config};
+
+#[test]
+fn second_keypad_command() {
+    for &(platform, skip) in [(Platform::Chip8X, 4), (Platform::Chip8, 2)].iter() {
+        let mut vm = Chip8::new();
+        assert!(vm.set_platform(platform).is_ok());
+        let (tx, rx) = channel();
+        let (ui_tx, _ui_rx) = channel();
+        tx.send(UpdateRunStatus(false)).unwrap();
+        tx.send(UpdateSecondKeyStatus(0x1, Pressed)).unwrap();
+        tx.send(Quit).unwrap();
+        exec_vm(&mut vm, 600, ui_tx, rx);
+
+        // EXF2 skips if the key VX of the second keypad is pressed
+        vm.set_register(3, 0x1);
+        let pc = vm.pc();
+        vm.execute_opcode(0xE3F2);
+        assert_eq!(vm.pc(), pc + skip, "{}", platform);
+    }
+}
+
+#[test]
+fn recording_resolution_change() {
+    assert_eq!(
+        numbered_filepath(Path::new("out/maze.gif"), 2),
+        Path::new("out/maze-2.gif")
+    );
+    assert_eq!(numbered_filepath(Path::new("maze"), 3), Path::new("maze-3"));
+
+    let filepath = env::temp_dir().join(format!("chip8app-test-{}.rgb", std::process::id()));
+    let second = numbered_filepath(&filepath, 2);
+    let config = Chip8Config::new();
+    let mut recording = start_recording(&config, &filepath).unwrap();
+    let scale = config.screenshot_scale as usize;
+    recording.record_frame(&Display::with_size(64, 32)).unwrap();
+    recording
+        .record_frame(&Display::with_size(128, 64))
+        .unwrap();
+    recording
+        .record_frame(&Display::with_size(128, 64))
+        .unwrap();
+    assert_eq!(recording.frames(), 3);
+    stop_recording(recording);
+
+    // the encoder is sized from the display, and a new file holds the hires frames
+    let sizes = (fs::metadata(&filepath), fs::metadata(&second));
+    let _ = (fs::remove_file(&filepath), fs::remove_file(&second));
+    assert_eq!(sizes.0.unwrap().len() as usize, 64 * 32 * scale * scale * 3);
+    assert_eq!(
+        sizes.1.unwrap().len() as usize,
+        2 * 128 * 64 * scale * scale * 3
+    );
+}
@@ -0,0 +1,83 @@
+use std::cmp;
+use std::path::Path;
+
+use crate::chip8app::{
+    load_vip, load_vm, log_diagnostics, save_screenshot, start_recording, stop_recording,
+    Chip8Config,
+};
+use chip8vm::machine::Machine;
+use chip8vm::vm::TIMERS_CLOCK;
+
+/// Run the given ROM without any UI for the given number of frames (at
+/// 'TIMERS_CLOCK' Hz), as fast as possible.
+/// If a screenshot filepath is given, save the final display there.
+/// If the 'Chip8Config' asks for a recording, every frame is recorded.
+/// Return true if all went well, false otherwise.
+pub fn run_rom(config: &Chip8Config, rom: &[u8], frames: u32, screenshot: Option<&Path>) -> bool {
+    if config.vip.is_some() {
+        match load_vip(config, rom) {
+            Some(mut vip) => run_machine(config, &mut vip, frames, screenshot),
+            None => false,
+        }
+    } else {
+        match load_vm(config, rom) {
+            Some(mut vm) => run_machine(config, &mut vm, frames, screenshot),
+            None => false,
+        }
+    }
+}
+
+/// Run the given machine, its program already loaded, as 'run_rom' does.
+fn run_machine<M: Machine>(
+    config: &Chip8Config,
+    vm: &mut M,
+    frames: u32,
+    screenshot: Option<&Path>,
+) -> bool {
+    let cycles_per_frame = cmp::max(1, config.vm_cpu_clock / TIMERS_CLOCK);
+    info!(
+        "running headless for {} frames of {} cycles",
+        frames, cycles_per_frame
+    );
+
+    let mut recording = match config.record_filepath {
+        Some(ref filepath) => match start_recording(config, filepath) {
+            Some(recording) => Some(recording),
+            None => return false,
+        },
+        None => None,
+    };
+
+    for frame in 0..frames {
+        // there is no input in headless mode
+        if vm.is_waiting_for_key() {
+            info!("waiting for a key press at frame {} - stopping", frame);
+            break;
+        }
+        if vm.run_frame(cycles_per_frame) {
+            info!("program finished at frame {}", frame);
+            break;
+        }
+        if let Some(ref mut r) = recording {
+            if let Err(why) = r.record_frame(vm.display()) {
+                error!("recording error : {}", why);
+                return false;
+            }
+        }
+    }
+    if let Some(r) = recording {
+        stop_recording(r);
+    }
+    log_diagnostics(vm);
+
+    match screenshot {
+        Some(filepath) => match save_screenshot(config, vm.display(), filepath) {
+            Ok(_) => true,
+            Err(why) => {
+                error!("cannot save the screenshot : {}", why);
+                false
+            }
+        },
+        None => true,
+    }
+}
@@ -1,6 +1,8 @@
+use self::sdl2::controller::Button;
 use self::sdl2::keyboard::Keycode;
 use sdl2;
 use std::collections::HashMap;
+use std::path::Path;
 
 /// Enumerates the supported keyboard bindings for the virtual keypad.
 /// TODO : add a Custom(...key bindings...) type, loaded from a file ?
@@ -49,3 +51,267 @@ pub fn get_sdl_key_bindings(keyboard: &KeyboardBinding) -> HashMap<Keycode, usiz
 
     hm
 }
+
+/// Return the HashMap<char, usize> corresponding to the given keyboard
+/// configuration, for the backends receiving characters instead of key
+/// events (e.g. the terminal). Lowercase characters only.
+pub fn get_char_key_bindings(keyboard: &KeyboardBinding) -> HashMap<char, usize> {
+    let mut hm = HashMap::new();
+
+    // since we only support AZERTY and QWERTY for now, insert the common keys
+    hm.insert('1', 0x1);
+    hm.insert('2', 0x2);
+    hm.insert('3', 0x3);
+    hm.insert('4', 0xC);
+    hm.insert('e', 0x6);
+    hm.insert('r', 0xD);
+    hm.insert('s', 0x8);
+    hm.insert('d', 0x9);
+    hm.insert('f', 0xE);
+    hm.insert('c', 0xB);
+    hm.insert('v', 0xF);
+
+    match *keyboard {
+        KeyboardBinding::QWERTY => {
+            hm.insert('q', 0x4);
+            hm.insert('w', 0x5);
+            hm.insert('a', 0x7);
+            hm.insert('z', 0xA);
+            hm.insert('x', 0x0);
+        }
+        KeyboardBinding::AZERTY => {
+            hm.insert('q', 0x7);
+            hm.insert('w', 0xA);
+            hm.insert('a', 0x4);
+            hm.insert('z', 0x5);
+            hm.insert('x', 0x0);
+        }
+    }
+
+    assert_eq!(hm.len(), 16);
+
+    hm
+}
+
+/// Return the HashMap<Keycode, usize> corresponding to the given keyboard
+/// configuration which allows to simulate the second keypad of CHIP-8X :
+/// the keys on the right of the keyboard, laid out as the first keypad.
+pub fn get_sdl_second_key_bindings(keyboard: &KeyboardBinding) -> HashMap<Keycode, usize> {
+    let mut hm = HashMap::new();
+
+    hm.insert(Keycode::Num7, 0x1);
+    hm.insert(Keycode::Num8, 0x2);
+    hm.insert(Keycode::Num9, 0x3);
+    hm.insert(Keycode::Num0, 0xC);
+    hm.insert(Keycode::U, 0x4);
+    hm.insert(Keycode::I, 0x5);
+    hm.insert(Keycode::O, 0x6);
+    hm.insert(Keycode::P, 0xD);
+    hm.insert(Keycode::J, 0x7);
+    hm.insert(Keycode::K, 0x8);
+    hm.insert(Keycode::L, 0x9);
+
+    match *keyboard {
+        KeyboardBinding::QWERTY => {
+            hm.insert(Keycode::Semicolon, 0xE);
+            hm.insert(Keycode::M, 0xA);
+            hm.insert(Keycode::Comma, 0x0);
+            hm.insert(Keycode::Period, 0xB);
+            hm.insert(Keycode::Slash, 0xF);
+        }
+        KeyboardBinding::AZERTY => {
+            hm.insert(Keycode::M, 0xE);
+            hm.insert(Keycode::Comma, 0xA);
+            hm.insert(Keycode::Semicolon, 0x0);
+            hm.insert(Keycode::Colon, 0xB);
+            hm.insert(Keycode::Exclaim, 0xF);
+        }
+    }
+
+    assert_eq!(hm.len(), 16);
+
+    hm
+}
+
+/// Return the HashMap<char, usize> corresponding to the given keyboard
+/// configuration for the second keypad of CHIP-8X, see
+/// 'get_sdl_second_key_bindings' and 'get_char_key_bindings'.
+pub fn get_char_second_key_bindings(keyboard: &KeyboardBinding) -> HashMap<char, usize> {
+    let mut hm = HashMap::new();
+
+    hm.insert('7', 0x1);
+    hm.insert('8', 0x2);
+    hm.insert('9', 0x3);
+    hm.insert('0', 0xC);
+    hm.insert('u', 0x4);
+    hm.insert('i', 0x5);
+    hm.insert('o', 0x6);
+    hm.insert('p', 0xD);
+    hm.insert('j', 0x7);
+    hm.insert('k', 0x8);
+    hm.insert('l', 0x9);
+
+    match *keyboard {
+        KeyboardBinding::QWERTY => {
+            hm.insert(';', 0xE);
+            hm.insert('m', 0xA);
+            hm.insert(',', 0x0);
+            hm.insert('.', 0xB);
+            hm.insert('/', 0xF);
+        }
+        KeyboardBinding::AZERTY => {
+            hm.insert('m', 0xE);
+            hm.insert(',', 0xA);
+            hm.insert(';', 0x0);
+            hm.insert(':', 0xB);
+            hm.insert('!', 0xF);
+        }
+    }
+
+    assert_eq!(hm.len(), 16);
+
+    hm
+}
+
+/// Enumerates the supported game controller bindings for the virtual keypad.
+/// Buttons are identified by their SDL2 game controller mapping names
+/// (e.g. "dpup", "a", "leftshoulder") so that the configuration stays
+/// backend-agnostic.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerBinding {
+    /// D-pad on 2/4/6/8 and the A button on 5, which suits most games.
+    Directional,
+    /// A custom list of (button name, keypad index) pairs.
+    Custom(Vec<(String, usize)>),
+}
+
+/// The controller layout used by 'ControllerBinding::Directional'.
+/// The remaining face and shoulder buttons cover the corner keys.
+static DIRECTIONAL_LAYOUT: [(&str, usize); 10] = [
+    ("dpup", 0x2),
+    ("dpleft", 0x4),
+    ("dpright", 0x6),
+    ("dpdown", 0x8),
+    ("a", 0x5),
+    ("b", 0x0),
+    ("x", 0x7),
+    ("y", 0x9),
+    ("leftshoulder", 0x1),
+    ("rightshoulder", 0x3),
+];
+
+/// Per-ROM controller layouts for games which do not use the directional
+/// layout, keyed by the (uppercase) ROM file stem.
+static ROM_CONTROLLER_LAYOUTS: [(&str, &[(&str, usize)]); 10] = [
+    ("PONG", &[("dpup", 0x1), ("dpdown", 0x4)]),
+    ("PONG2", &[("dpup", 0x1), ("dpdown", 0x4)]),
+    (
+        "TETRIS",
+        &[
+            ("dpleft", 0x5),
+            ("dpright", 0x6),
+            ("dpdown", 0x7),
+            ("a", 0x4),
+        ],
+    ),
+    ("INVADERS", &[("dpleft", 0x4), ("dpright", 0x6), ("a", 0x5)]),
+    ("BRIX", &[("dpleft", 0x4), ("dpright", 0x6)]),
+    ("BREAKOUT", &[("dpleft", 0x4), ("dpright", 0x6)]),
+    ("WIPEOFF", &[("dpleft", 0x4), ("dpright", 0x6)]),
+    ("UFO", &[("dpleft", 0x4), ("dpup", 0x5), ("dpright", 0x6)]),
+    ("BLITZ", &[("a", 0x5)]),
+    ("MISSILE", &[("a", 0x8)]),
+];
+
+/// Return the default controller binding for the ROM at the given filepath.
+/// Falls back to 'ControllerBinding::Directional' for unknown ROMs.
+pub fn default_controller_binding(rom_filepath: &Path) -> ControllerBinding {
+    let stem = match rom_filepath.file_stem() {
+        Some(stem) => stem.to_string_lossy().to_uppercase(),
+        None => return ControllerBinding::Directional,
+    };
+    for &(name, layout) in ROM_CONTROLLER_LAYOUTS.iter() {
+        if name == stem {
+            return ControllerBinding::Custom(
+                layout
+                    .iter()
+                    .map(|&(button, index)| (button.to_owned(), index))
+                    .collect(),
+            );
+        }
+    }
+    ControllerBinding::Directional
+}
+
+/// Return the controller binding for the keys of a ROM as described by the
+/// ROM database, i.e. (direction or button name, keypad index) pairs where
+/// the directions are "up", "down", "left" and "right".
+pub fn controller_binding_from_keys(keys: &[(String, usize)]) -> ControllerBinding {
+    ControllerBinding::Custom(
+        keys.iter()
+            .map(|&(ref name, index)| {
+                let button = match &name[..] {
+                    "up" | "down" | "left" | "right" => format!("dp{}", name),
+                    _ => name.to_lowercase(),
+                };
+                (button, index)
+            })
+            .collect(),
+    )
+}
+
+/// Parse a controller binding of the form "dpup=2,dpdown=8,a=5".
+/// The keypad indices are hexadecimal digits.
+/// If the operation fails, return a String explaining why.
+pub fn parse_controller_binding(string: &str) -> Result<ControllerBinding, String> {
+    let mut binds = Vec::new();
+    for bind in string
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+    {
+        let mut parts = bind.splitn(2, '=');
+        let button = parts.next().unwrap().trim().to_lowercase();
+        let index = match parts.next() {
+            Some(index) => match usize::from_str_radix(index.trim(), 16) {
+                Ok(index) if index < 16 => index,
+                _ => return Err(format!("invalid keypad index in \"{}\"", bind)),
+            },
+            None => return Err(format!("missing keypad index in \"{}\"", bind)),
+        };
+        if Button::from_string(&button).is_none() {
+            return Err(format!("unknown controller button \"{}\"", button));
+        }
+        binds.push((button, index));
+    }
+    if binds.is_empty() {
+        return Err("empty controller binding".to_owned());
+    }
+    Ok(ControllerBinding::Custom(binds))
+}
+
+/// Return the HashMap<Button, usize> corresponding to the given controller
+/// configuration.
+pub fn get_sdl_controller_bindings(controller: &ControllerBinding) -> HashMap<Button, usize> {
+    let mut hm = HashMap::new();
+
+    match *controller {
+        ControllerBinding::Directional => {
+            for &(name, index) in DIRECTIONAL_LAYOUT.iter() {
+                hm.insert(Button::from_string(name).unwrap(), index);
+            }
+        }
+        ControllerBinding::Custom(ref binds) => {
+            for &(ref name, index) in binds.iter() {
+                match Button::from_string(name) {
+                    Some(button) => {
+                        hm.insert(button, index);
+                    }
+                    None => warn!("unknown controller button \"{}\" - ignoring", name),
+                }
+            }
+        }
+    }
+
+    hm
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use sdl2::controller::Button;
+
+use super::input::*;
+
+#[test]
+fn parse_bindings() {
+    assert_eq!(
+        parse_controller_binding("dpup=2, DPDOWN=8,a=f,"),
+        Ok(ControllerBinding::Custom(vec![
+            ("dpup".to_owned(), 0x2),
+            ("dpdown".to_owned(), 0x8),
+            ("a".to_owned(), 0xF),
+        ]))
+    );
+    assert!(parse_controller_binding("").is_err());
+    assert!(parse_controller_binding("dpup").is_err());
+    assert!(parse_controller_binding("dpup=10").is_err());
+    assert!(parse_controller_binding("dpup=z").is_err());
+    assert!(parse_controller_binding("trigger=1").is_err());
+
+    let binds = get_sdl_controller_bindings(&parse_controller_binding("b=3").unwrap());
+    assert_eq!(binds.len(), 1);
+    assert_eq!(binds.get(&Button::B), Some(&0x3));
+    let binds = get_sdl_controller_bindings(&ControllerBinding::Directional);
+    assert_eq!(binds.get(&Button::DPadUp), Some(&0x2));
+    assert_eq!(binds.get(&Button::A), Some(&0x5));
+}
+
+#[test]
+fn rom_layouts() {
+    let pong =
+        ControllerBinding::Custom(vec![("dpup".to_owned(), 0x1), ("dpdown".to_owned(), 0x4)]);
+    assert_eq!(default_controller_binding(Path::new("roms/PONG")), pong);
+    assert_eq!(default_controller_binding(Path::new("roms/pong.ch8")), pong);
+    assert_eq!(default_controller_binding(Path::new("Pong2.ch8")), pong);
+    assert_eq!(
+        default_controller_binding(Path::new("roms/PONG3")),
+        ControllerBinding::Directional
+    );
+}
+
+#[test]
+fn second_keypad_bindings() {
+    for keyboard in [KeyboardBinding::QWERTY, KeyboardBinding::AZERTY].iter() {
+        let first = get_sdl_key_bindings(keyboard);
+        let second = get_sdl_second_key_bindings(keyboard);
+        assert!(second.keys().all(|key| !first.contains_key(key)));
+        let mut indices: Vec<usize> = second.values().cloned().collect();
+        indices.sort();
+        assert_eq!(indices, (0..16).collect::<Vec<usize>>());
+
+        let first = get_char_key_bindings(keyboard);
+        let second = get_char_second_key_bindings(keyboard);
+        assert!(second.keys().all(|c| !first.contains_key(c)));
+        assert_eq!(second.get(&'7'), Some(&0x1));
+    }
+}
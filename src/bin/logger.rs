@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use log::{self, Log, Metadata, Record};
+
+/// The maximum number of log lines held back, the oldest being dropped.
+const HELD_LINES_MAX: usize = 1000;
+
+/// The log lines held back while the terminal is in use, with the number of
+/// lines dropped, if holding.
+static HELD: Mutex<Option<(VecDeque<String>, usize)>> = Mutex::new(None);
+
+/// The env_logger logger, whose records can be held back while a backend
+/// draws on the terminal, to be written to the standard error afterwards.
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        if let Some((ref mut lines, ref mut dropped)) = *HELD.lock().unwrap() {
+            if lines.len() == HELD_LINES_MAX {
+                lines.pop_front();
+                *dropped += 1;
+            }
+            lines.push_back(format!(
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+            return;
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Initialize the logging, configured by the RUST_LOG environment variable
+/// as with 'env_logger::init'.
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    log::set_max_level(inner.filter());
+    log::set_boxed_logger(Box::new(Logger { inner })).expect("cannot initialize the logging");
+}
+
+/// Hold the log lines back until 'release' is called.
+pub fn hold() {
+    let mut held = HELD.lock().unwrap();
+    if held.is_none() {
+        *held = Some((VecDeque::new(), 0));
+    }
+}
+
+/// Write the log lines held back, if any, to the standard error.
+pub fn release() {
+    let held = HELD.lock().unwrap().take();
+    if let Some((lines, dropped)) = held {
+        if dropped > 0 {
+            eprintln!("({} earlier log lines dropped)", dropped);
+        }
+        for line in lines {
+            eprintln!("{}", line);
+        }
+    }
+}
@@ -1,17 +1,41 @@
+use std::cmp;
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[macro_use]
 extern crate log;
-use env_logger;
 
 use getopts::{Matches, Options};
 
+use chip8vm::analyzer;
+use chip8vm::database::{self, RomDatabase, RomInfo};
+use chip8vm::diagnostics::StrictMode;
+use chip8vm::font::Font;
+use chip8vm::loader;
+use chip8vm::palette::{Palette, Theme, THEMES};
+use chip8vm::persistence::PersistenceMode;
+use chip8vm::platform::Platform;
+use chip8vm::quirks::Quirks;
+use chip8vm::record::RecordFormat;
+use chip8vm::symbols::SymbolTable;
+use chip8vm::vm::{MEMORY_SIZE, TIMERS_CLOCK};
+
 mod chip8app;
 mod chip8app_sdl2;
+mod chip8app_term;
+#[cfg(test)]
+mod chip8app_term_test;
+#[cfg(test)]
+mod chip8app_test;
+mod headless;
 mod input;
-use crate::chip8app::{Chip8Config, Chip8Emulator, Chip8EmulatorBackend};
+#[cfg(test)]
+mod input_test;
+mod logger;
+use crate::chip8app::{Chip8Config, Chip8Emulator, Chip8EmulatorBackend, VipImages};
 use crate::chip8app_sdl2::Chip8BackendSDL2;
+use crate::chip8app_term::Chip8BackendTerminal;
 
 /// CPU clock hard limit.
 /// Above 5000Hz or so, without emulation throttling (thread::sleep_ms)
@@ -19,11 +43,12 @@ use crate::chip8app_sdl2::Chip8BackendSDL2;
 pub const CPU_CLOCK_MAX: u32 = 3000;
 
 fn print_usage(opts: Options) {
-    let brief = "rust-chip8 emulator.\n\nUsage:\n   rust-chip8 [OPTIONS] ROM_FILE\n";
+    let brief = "rust-chip8 emulator.\n\nUsage:\n   rust-chip8 [OPTIONS] ROM_FILE\n\n\
+                 With \"-\" as ROM_FILE, the ROM is read from the standard input.";
     println!("{}", opts.usage(&brief));
 }
 
-fn config_from_matches(matches: &Matches) -> Chip8Config {
+fn config_from_matches(matches: &Matches, rom_filepath: &Path) -> Chip8Config {
     let mut config = Chip8Config::new();
 
     let keyboard_config = match matches.opt_str("k") {
@@ -42,6 +67,18 @@ fn config_from_matches(matches: &Matches) -> Chip8Config {
     };
     config = config.key_binds(keyboard_config);
 
+    let controller_config = match matches.opt_str("g") {
+        Some(ref string) => match input::parse_controller_binding(string) {
+            Ok(binding) => binding,
+            Err(why) => {
+                warn!("invalid controller configuration : {}", why);
+                input::default_controller_binding(rom_filepath)
+            }
+        },
+        _ => input::default_controller_binding(rom_filepath),
+    };
+    config = config.pad_binds(controller_config);
+
     match matches.opt_str("c") {
         Some(ref string) => match string.parse::<u32>() {
             Ok(cpu_clock) => {
@@ -56,11 +93,216 @@ fn config_from_matches(matches: &Matches) -> Chip8Config {
         _ => {}
     }
 
+    match matches.opt_str("load-address") {
+        Some(ref string) => match usize::from_str_radix(string.trim_start_matches("0x"), 16) {
+            Ok(address) if address < MEMORY_SIZE => config = config.load_address(Some(address)),
+            _ => warn!("\"{}\" is not a valid load address", string),
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("platform") {
+        Some(ref string) if string == "auto" => {}
+        Some(ref string) => match Platform::from_name(string) {
+            Some(platform) => config = config.platform(platform),
+            None => warn!("unrecognized platform \"{}\".", string),
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("font") {
+        Some(ref string) => match Font::from_name(string) {
+            Some(font) => config = config.font(Some(font)),
+            None => match Font::from_file(Path::new(string)) {
+                Ok(font) => config = config.font(Some(font)),
+                Err(why) => warn!("invalid font \"{}\" : {}", string, why),
+            },
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("quirks") {
+        Some(ref string) => match Quirks::from_name(string) {
+            Some(quirks) => config = config.quirks(quirks),
+            None => warn!("unrecognized quirks preset \"{}\".", string),
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("colors") {
+        Some(ref string) => match Palette::from_hex_list(string) {
+            Ok(palette) => config = config.palette(palette),
+            Err(why) => warn!("invalid custom colours : {}", why),
+        },
+        _ => match matches.opt_str("p") {
+            Some(ref string) => match Theme::from_name(string) {
+                Some(theme) => config = config.palette(theme.palette()),
+                None => warn!("unrecognized palette theme \"{}\".", string),
+            },
+            _ => {}
+        },
+    }
+
+    match matches.opt_str("persistence") {
+        Some(ref string) => match PersistenceMode::from_name(string) {
+            Ok(mode) => config = config.persistence(mode),
+            Err(why) => warn!("{}", why),
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("screenshot-scale") {
+        Some(ref string) => match string.parse::<u32>() {
+            Ok(scale) if scale > 0 => config = config.screenshot_scale(scale),
+            _ => warn!("\"{}\" is not a valid screenshot scale", string),
+        },
+        _ => {}
+    }
+
+    match matches.opt_str("record-format") {
+        Some(ref string) => match RecordFormat::from_name(string) {
+            Some(format) => config = config.record_format(Some(format)),
+            None => warn!("unrecognized recording format \"{}\".", string),
+        },
+        _ => {}
+    }
+    config = config.record_filepath(matches.opt_str("record").map(PathBuf::from));
+    config = config.debug_syscalls(matches.opt_present("debug-syscalls"));
+    if matches.opt_present("strict") || matches.opt_present("random-ram") {
+        config = config.strict(Some(StrictMode {
+            random_ram: matches.opt_present("random-ram"),
+            ..StrictMode::default()
+        }));
+    }
+
+    match matches.opt_str("stack-depth") {
+        Some(ref string) if string == "unlimited" => config = config.stack_depth(Some(None)),
+        Some(ref string) => match string.parse::<usize>() {
+            Ok(depth) if depth > 0 => config = config.stack_depth(Some(Some(depth))),
+            _ => warn!("\"{}\" is not a valid stack depth", string),
+        },
+        _ => {}
+    }
+    config = config.stack_in_memory(matches.opt_present("stack-in-memory"));
+    config = config.key_wait_release(!matches.opt_present("key-wait-press"));
+
+    match matches.opt_str("symbols") {
+        Some(ref string) => match SymbolTable::from_file(Path::new(string)) {
+            Ok(symbols) => config = config.symbols(Some(symbols)),
+            Err(why) => warn!("invalid symbol file : {}", why),
+        },
+        _ => {}
+    }
+
+    if let Some(filepath) = matches.opt_str("vip-interpreter") {
+        match fs::read(&filepath) {
+            Ok(interpreter) => {
+                let monitor =
+                    matches
+                        .opt_str("vip-monitor")
+                        .and_then(|filepath| match fs::read(&filepath) {
+                            Ok(monitor) => Some(monitor),
+                            Err(why) => {
+                                warn!("cannot read the VIP monitor \"{}\" : {}", filepath, why);
+                                None
+                            }
+                        });
+                config = config.vip(Some(VipImages {
+                    interpreter,
+                    monitor,
+                }));
+            }
+            Err(why) => warn!("cannot read the VIP interpreter \"{}\" : {}", filepath, why),
+        }
+    }
+
+    config
+}
+
+/// Return the default ROM database files which exist, in the user's data
+/// directory : the community database then the local overrides.
+fn default_database_filepaths() -> Vec<PathBuf> {
+    let data_dir = if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("APPDATA") {
+        PathBuf::from(dir)
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".local").join("share")
+    } else {
+        return Vec::new();
+    };
+    let data_dir = data_dir.join("rust-chip8");
+    vec![
+        data_dir.join("programs.json"),
+        data_dir.join("overrides.json"),
+    ]
+    .into_iter()
+    .filter(|filepath| filepath.is_file())
+    .collect()
+}
+
+/// Load the ROM database from the default files then from the ones given on
+/// the command line, the files loaded last overriding the others.
+fn load_database(matches: &Matches) -> RomDatabase {
+    let mut database = RomDatabase::new();
+    let mut filepaths = default_database_filepaths();
+    filepaths.extend(matches.opt_strs("database").into_iter().map(PathBuf::from));
+    for filepath in filepaths {
+        match database.add_file(&filepath) {
+            Ok(()) => info!("loaded the ROM database \"{}\"", filepath.display()),
+            Err(why) => warn!("cannot load the ROM database : {}", why),
+        }
+    }
+    database
+}
+
+/// Were the quirks given on the command line, either directly or through a
+/// platform other than "auto" ?
+fn quirks_given(matches: &Matches) -> bool {
+    matches.opt_present("quirks") || matches.opt_str("platform").map_or(false, |p| p != "auto")
+}
+
+/// Apply the metadata found in the ROM database to the configuration,
+/// unless the corresponding options were given on the command line.
+fn apply_rom_info(mut config: Chip8Config, info: &RomInfo, matches: &Matches) -> Chip8Config {
+    let platform = info
+        .platform
+        .as_ref()
+        .and_then(|id| database::platform_from_id(id));
+    if let (false, Some(platform)) = (matches.opt_present("platform"), platform) {
+        let quirks = config.quirks;
+        config = config.platform(platform);
+        if quirks_given(matches) {
+            config = config.quirks(quirks);
+        }
+    }
+    if let (false, Some(quirks)) = (quirks_given(matches), info.quirks) {
+        config = config.quirks(quirks);
+    }
+    if let (false, Some(tickrate)) = (matches.opt_present("c"), info.tickrate) {
+        config = config.vm_cpu_clock(cmp::min(
+            tickrate.saturating_mul(TIMERS_CLOCK),
+            CPU_CLOCK_MAX,
+        ));
+    }
+    if let (false, false, Some(ref palette)) = (
+        matches.opt_present("p"),
+        matches.opt_present("colors"),
+        &info.palette,
+    ) {
+        config = config.palette(palette.clone());
+    }
+    if !matches.opt_present("g") && !info.keys.is_empty() {
+        config = config.pad_binds(input::controller_binding_from_keys(&info.keys));
+    }
+    if let (false, Some(address)) = (matches.opt_present("load-address"), info.start_address) {
+        config = config.load_address(Some(address));
+    }
     config
 }
 
 fn main() {
-    env_logger::init();
+    logger::init();
 
     // Program options
     let args: Vec<String> = env::args().collect();
@@ -73,12 +315,178 @@ fn main() {
         "The CPU clock speed to target. 600 Hz by default.",
         "CPU_CLOCK_SPEED",
     );
+    opts.optopt(
+        "",
+        "load-address",
+        "The hexadecimal address where the ROM is loaded and started. The entry \
+         point of the platform by default, i.e. 200 except on the ETI-660.",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "",
+        "quirks",
+        "The interpreter quirks to emulate. modern by default, unless the ROM \
+         is in the ROM database.",
+        "chip8/schip/xochip/modern",
+    );
+    opts.optopt(
+        "",
+        "platform",
+        "The platform the ROM is written for, setting the quirks and the memory \
+         layout. With auto, guessed from the code of the ROMs missing from the \
+         ROM database. modern by default.",
+        "chip8/chip8hires/chip8x/eti660/dream6800/schip/xochip/megachip/modern/auto",
+    );
+    opts.optopt(
+        "",
+        "font",
+        "The font set, by name or as a file of 80 bytes of small glyphs \
+         optionally followed by 100 or 160 bytes of big glyphs. The font of the \
+         platform by default.",
+        "vip/dream6800/eti660/fish/schip/xochip/modern/FILE",
+    );
+    opts.optmulti(
+        "",
+        "database",
+        "An additional ROM database file, in the community chip-8-database \
+         format. Overrides the default databases.",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "cartridge",
+        "An Octo cartridge whose options (quirks, CPU clock and colours) are \
+         applied to the ROM, assembled from the cartridge's source with Octo.",
+        "FILE",
+    );
+    opts.optopt(
+        "b",
+        "backend",
+        "The user interface to use. sdl by default ; terminal draws the display \
+         in the terminal, e.g. through SSH.",
+        "sdl/terminal",
+    );
     opts.optopt(
         "k",
         "keyboard",
         "The keyboard configuration to use. QWERTY by default.",
         "QWERTY/AZERTY",
     );
+    opts.optopt(
+        "g",
+        "gamepad",
+        "The game controller configuration to use, as a list of SDL2 button names \
+         and hexadecimal keypad indices. Defaults to a per-ROM layout.",
+        "dpup=2,dpdown=8,a=5",
+    );
+    let theme_names: Vec<&str> = THEMES.iter().map(|theme| theme.name()).collect();
+    opts.optopt(
+        "p",
+        "palette",
+        "The built-in palette theme to use. classic by default.",
+        &theme_names.join("/"),
+    );
+    opts.optopt(
+        "",
+        "colors",
+        "Custom palette, as 2 or 4 hexadecimal colours. Overrides --palette.",
+        "#000000,#FFFFFF",
+    );
+    opts.optopt(
+        "",
+        "persistence",
+        "Phosphor persistence to reduce flicker : off, blend (last two frames) \
+         or decay with an optional rate in ]0, 1]. off by default.",
+        "off/blend/decay[:RATE]",
+    );
+    opts.optopt(
+        "",
+        "screenshot-scale",
+        "The size of a CHIP 8 pixel in screenshots. 8 by default.",
+        "SCALE",
+    );
+    opts.optopt(
+        "",
+        "headless",
+        "Run the ROM without any UI for the given number of 60 Hz frames.",
+        "FRAMES",
+    );
+    opts.optopt(
+        "",
+        "screenshot",
+        "In headless mode, save the final display as a PNG image.",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "record",
+        "Record the emulation from the start to the given file, or to the \
+         standard output with \"-\". Toggle recording with F10 otherwise.",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "record-format",
+        "The recording format. Guessed from the file extension by default.",
+        "gif/y4m/rgb",
+    );
+    opts.optflag(
+        "",
+        "debug-syscalls",
+        "Let the ROM log V0 with 0001, the registers with 0002 and the V0 \
+         bytes at I as a string with 0003 (see RUST_LOG).",
+    );
+    opts.optflag(
+        "",
+        "strict",
+        "Report the bugs of the ROM (uninitialised memory, writes to the \
+         interpreter area, data executed, deep stack, I out of the memory, \
+         clipped sprites, collision flag lost) when the emulation ends.",
+    );
+    opts.optflag(
+        "",
+        "random-ram",
+        "In the strict mode, fill the memory with random bytes instead of zeroes.",
+    );
+    opts.optopt(
+        "",
+        "stack-depth",
+        "The maximum depth of the stack, or \"unlimited\". 12 levels on the \
+         COSMAC VIP platforms, 16 otherwise.",
+        "DEPTH",
+    );
+    opts.optflag(
+        "",
+        "stack-in-memory",
+        "Keep the stack in memory below 0xED0, where the COSMAC VIP \
+         interpreter keeps it.",
+    );
+    opts.optopt(
+        "",
+        "symbols",
+        "Name the subroutines in the backtraces logged with the errors, from \
+         a file of \"ADDRESS NAME\" lines.",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "key-wait-press",
+        "Let FX0A resume as soon as a key is pressed, instead of when it is \
+         released as on the COSMAC VIP.",
+    );
+    opts.optopt(
+        "",
+        "vip-interpreter",
+        "Run the ROM on an emulated COSMAC VIP, with the given CHIP 8 \
+         interpreter image loaded at 0x0000.",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "vip-monitor",
+        "The monitor ROM image (512 bytes) booting the emulated COSMAC VIP.",
+        "FILE",
+    );
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(why) => panic!(why.to_string()),
@@ -94,17 +502,96 @@ fn main() {
         return;
     };
 
+    let rom_filepath = Path::new(&rom_file);
+    let program = match chip8app::read_rom(&rom_filepath) {
+        Some(program) => program,
+        None => panic!("error while reading the ROM."),
+    };
+    let rom = program.rom;
+
     // Chip 8 virtual machine creation
-    let config = config_from_matches(&matches)
-        .w_title("rust-chip8 emulator")
-        .w_width(800)
-        .w_height(600);
-    let backend = Box::new(Chip8BackendSDL2) as Box<dyn Chip8EmulatorBackend>;
+    let mut config = config_from_matches(&matches, &rom_filepath);
+    config = apply_rom_info(config, &program.info, &matches);
+    let mut title = "rust-chip8 emulator".to_owned();
+    let mut known_quirks = program.info.quirks.is_some();
+    match load_database(&matches).lookup(&rom) {
+        Some(info) => {
+            let name = info.title.as_ref().map_or("unknown title", |t| &t[..]);
+            info!(
+                "found the ROM in the database : {} ({})",
+                name,
+                info.platform
+                    .as_ref()
+                    .map_or("unknown platform", |p| &p[..])
+            );
+            for &(ref key, index) in info.keys.iter() {
+                info!("key hint : {} = {:X}", key, index);
+            }
+            title = format!("{} - {}", title, name);
+            config = apply_rom_info(config, info, &matches);
+            known_quirks |= info.quirks.is_some();
+        }
+        None => info!("the ROM is not in the database"),
+    }
+    if let Some(filepath) = matches.opt_str("cartridge") {
+        let cartridge = fs::read(&filepath)
+            .map_err(|why| why.to_string())
+            .and_then(|data| loader::decode_cartridge(&data));
+        match cartridge {
+            Ok(cartridge) => {
+                info!("applying the options of the cartridge \"{}\"", filepath);
+                config = apply_rom_info(config, &cartridge.info, &matches);
+                known_quirks = true;
+            }
+            Err(why) => warn!("cannot read the Octo cartridge : {}", why),
+        }
+    }
+    if matches.opt_str("platform").map_or(false, |p| p == "auto")
+        && !matches.opt_present("quirks")
+        && !known_quirks
+    {
+        let analysis = analyzer::analyze(&rom, config.entry_point());
+        for line in analysis.to_string().lines() {
+            info!("{}", line);
+        }
+        config = config.platform(analysis.platform);
+    }
+    let config = config.w_title(title).w_width(800).w_height(600);
+
+    // Headless run
+    if let Some(string) = matches.opt_str("headless") {
+        let frames = match string.parse::<u32>() {
+            Ok(frames) => frames,
+            Err(_) => panic!("\"{}\" is not a valid number of frames", string),
+        };
+        let screenshot = matches.opt_str("screenshot");
+        let screenshot_filepath = screenshot.as_ref().map(Path::new);
+        if !headless::run_rom(&config, &rom, frames, screenshot_filepath) {
+            panic!("error while loading or running the ROM.");
+        }
+        return;
+    }
+
+    let backend = match matches.opt_str("b") {
+        Some(ref string) if string == "terminal" && rom_file == "-" => {
+            panic!("the terminal backend reads the keyboard from the standard input.");
+        }
+        Some(ref string) if string == "terminal" && config.record_filepath.is_some() => {
+            panic!("the terminal backend cannot record the display.");
+        }
+        Some(ref string) if string == "terminal" => {
+            Box::new(Chip8BackendTerminal) as Box<dyn Chip8EmulatorBackend>
+        }
+        Some(ref string) if string != "sdl" => {
+            warn!("unrecognized backend \"{}\", using SDL2.", string);
+            Box::new(Chip8BackendSDL2) as Box<dyn Chip8EmulatorBackend>
+        }
+        _ => Box::new(Chip8BackendSDL2) as Box<dyn Chip8EmulatorBackend>,
+    };
     let mut emulator = Chip8Emulator::new(config, backend);
 
     // Load the ROM and start the emulation
-    let rom_filepath = Path::new(&rom_file);
-    if !emulator.run_rom(&rom_filepath) {
+    if !emulator.run_rom(&rom) {
         panic!("error while loading or running the ROM.");
     }
 }
@@ -0,0 +1,52 @@
+//! The memory bus of the CHIP 8 virtual machine : the instructions fetch,
+//! read and write the memory through it (fetch, DXYN sprites, FX33, FX55 and
+//! FX65), so that a tool can observe the accesses, protect or mirror
+//! addresses, or map a virtual device over a range of addresses.
+//! The loader, the font, the save states and the debuggers access the RAM
+//! behind the bus directly.
+
+/// The memory bus of the virtual machine.
+/// Only the RAM is required : the reads and writes go to it by default, and
+/// an implementation overrides them to intercept the accesses.
+pub trait Bus {
+    /// Get the RAM behind the bus.
+    fn ram(&self) -> &[u8];
+
+    /// Get the RAM behind the bus, to modify or resize it.
+    fn ram_mut(&mut self) -> &mut Vec<u8>;
+
+    /// Read the byte at the given address for an instruction.
+    fn read(&mut self, address: usize) -> u8 {
+        self.ram()[address]
+    }
+
+    /// Write the given byte at the given address for an instruction.
+    fn write(&mut self, address: usize, value: u8) {
+        self.ram_mut()[address] = value;
+    }
+}
+
+/// The default bus : the plain RAM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ram {
+    bytes: Vec<u8>,
+}
+
+impl Ram {
+    /// Create a RAM of the given size, cleared.
+    pub fn new(size: usize) -> Ram {
+        Ram {
+            bytes: vec![0u8; size],
+        }
+    }
+}
+
+impl Bus for Ram {
+    fn ram(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.bytes
+    }
+}
@@ -0,0 +1,78 @@
+use super::bus::{Bus, Ram};
+use super::machine::Machine;
+use super::vm::Chip8;
+
+/// A bus protecting the interpreter area, mirroring the memory past its end,
+/// mapping a console at 0xF00-0xF0F and counting the reads.
+struct TestBus {
+    ram: Ram,
+    console: Vec<u8>,
+    reads: usize,
+}
+
+impl Bus for TestBus {
+    fn ram(&self) -> &[u8] {
+        self.ram.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut Vec<u8> {
+        self.ram.ram_mut()
+    }
+
+    fn read(&mut self, address: usize) -> u8 {
+        self.reads += 1;
+        self.ram.read(address % self.ram().len())
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        match address {
+            0x000..=0x1FF => {}
+            0xF00..=0xF0F => self.console.push(value),
+            _ => {
+                let size = self.ram().len();
+                self.ram.write(address % size, value)
+            }
+        }
+    }
+}
+
+#[test]
+fn bus_devices() {
+    let bus = TestBus {
+        ram: Ram::new(0x10),
+        console: Vec::new(),
+        reads: 0,
+    };
+    let mut vm = Chip8::with_bus(bus);
+    assert_eq!(vm.memory().len(), 0x1000);
+    let rom = [
+        0xA1, 0xF0, 0x60, 0x2A, 0xF0, 0x55, 0xAF, 0x00, 0xF0, 0x33, 0xAF, 0xFF, 0xF1, 0x65, 0x12,
+        0x0E,
+    ];
+    assert!(vm.load_bytes(&rom).is_ok());
+    vm.memory_mut()[0x000] = 0x33;
+    vm.memory_mut()[0xFFF] = 0x77;
+    for _ in 0..7 {
+        vm.emulate_cycle();
+    }
+
+    // the write to the interpreter area is dropped, the BCD goes to the console
+    assert_eq!(vm.memory()[0x1F0], 0x00);
+    assert_eq!(vm.bus().console, vec![0, 4, 2]);
+    // FX65 reads 0xFFF then the mirrored 0x000
+    assert_eq!((vm.register(0), vm.register(1)), (0x77, 0x33));
+    assert_eq!(vm.bus().reads, 7 * 2 + 2);
+    vm.bus_mut().reads = 0;
+
+    // decoding and saving the state do not go through the bus
+    assert_eq!(vm.decode(), Some(0x120E));
+    let state = vm.save_state();
+    assert!(vm.load_state(&state).is_ok());
+    assert_eq!(vm.bus().reads, 0);
+
+    // the writes past the end of the memory are mirrored too
+    vm.set_index(0x1200);
+    vm.set_register(0, 0x5A);
+    vm.execute_opcode(0xF055);
+    assert_eq!(vm.memory()[0x200], 0x5A);
+}
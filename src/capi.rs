@@ -0,0 +1,300 @@
+/// C API of the library, allowing to embed the virtual machine in programs
+/// written in other languages.
+/// Built with the "capi" feature ; the matching header is 'include/chip8vm.h'
+/// (generated with cbindgen, see 'cbindgen.toml').
+///
+/// The virtual machine is exposed as an opaque 'Chip8' handle. Every function
+/// returns a 'Chip8Status' and never lets a panic unwind into the caller.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::keypad::Keystate;
+use crate::vm::{Chip8, LoadError};
+
+/// The status codes returned by the C API.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Chip8Status {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// An argument is out of range (e.g. a key index above 0xF, an empty
+    /// ROM).
+    InvalidArgument = 2,
+    /// The ROM does not fit in memory.
+    RomTooLarge = 3,
+    /// The output buffer is too small ; the required size is reported when
+    /// the function allows it.
+    BufferTooSmall = 4,
+    /// The snapshot is corrupted or comes from an incompatible version.
+    InvalidSnapshot = 5,
+    /// The loaded program is done.
+    Finished = 6,
+    /// An internal error occurred. The handle should be destroyed.
+    Panic = 7,
+}
+
+/// The registers of the virtual machine, as seen from C.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Chip8Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// Run the given closure, turning any panic into 'Chip8Status::Panic'.
+fn guard<F: FnOnce() -> Chip8Status>(f: F) -> Chip8Status {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(Chip8Status::Panic)
+}
+
+/// Create a new virtual machine. The handle must be released with
+/// 'chip8_destroy'. Return null if the allocation failed.
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Chip8 {
+    catch_unwind(|| Box::into_raw(Box::new(Chip8::new()))).unwrap_or(ptr::null_mut())
+}
+
+/// Destroy a virtual machine created by 'chip8_create'. Null is ignored.
+///
+/// # Safety
+/// 'vm' must be null or a handle returned by 'chip8_create' which has not
+/// been destroyed yet.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(vm: *mut Chip8) {
+    if !vm.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(vm))));
+    }
+}
+
+/// Reset the virtual machine and load the ROM of 'len' bytes at 'data'.
+///
+/// # Safety
+/// 'vm' must be a valid handle and 'data' must point to 'len' readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load(vm: *mut Chip8, data: *const u8, len: usize) -> Chip8Status {
+    if vm.is_null() || data.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let (vm, rom) = (&mut *vm, slice::from_raw_parts(data, len));
+    guard(|| {
+        let mut fresh = Chip8::new();
+        match fresh.load_bytes(rom) {
+            Ok(()) => {
+                *vm = fresh;
+                Chip8Status::Ok
+            }
+            Err(LoadError::TooLarge { .. }) => Chip8Status::RomTooLarge,
+            Err(_) => Chip8Status::InvalidArgument,
+        }
+    })
+}
+
+/// Emulate the given number of CPU cycles then update the timers once, i.e.
+/// run a 60 Hz frame (use 'cycles' = clock / 60, e.g. 10 for 600 Hz).
+/// Return 'Finished' when the program is done.
+///
+/// # Safety
+/// 'vm' must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(vm: *mut Chip8, cycles: u32) -> Chip8Status {
+    if vm.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let vm = &mut *vm;
+    guard(|| {
+        for _ in 0..cycles {
+            if vm.emulate_cycle() {
+                return Chip8Status::Finished;
+            }
+        }
+        vm.update_timers();
+        Chip8Status::Ok
+    })
+}
+
+/// Press ('pressed' != 0) or release the key of the virtual keypad at the
+/// given index (0x0 to 0xF).
+///
+/// # Safety
+/// 'vm' must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(vm: *mut Chip8, key: u8, pressed: i32) -> Chip8Status {
+    if vm.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    if key > 0xF {
+        return Chip8Status::InvalidArgument;
+    }
+    let vm = &mut *vm;
+    guard(|| {
+        let key = key as usize;
+        let state = if pressed != 0 {
+            Keystate::Pressed
+        } else {
+            Keystate::Released
+        };
+        vm.keypad.set_key_state(key, state);
+        Chip8Status::Ok
+    })
+}
+
+/// Copy the framebuffer to 'out', one byte per pixel (0 = off) row by row,
+/// and store its dimensions in 'width' and 'height'.
+/// If 'out' is null or too small, only the dimensions are stored and
+/// 'BufferTooSmall' is returned.
+///
+/// # Safety
+/// 'vm', 'width' and 'height' must be valid pointers ; 'out' must be null or
+/// point to 'len' writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    vm: *const Chip8,
+    out: *mut u8,
+    len: usize,
+    width: *mut u32,
+    height: *mut u32,
+) -> Chip8Status {
+    if vm.is_null() || width.is_null() || height.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let vm = &*vm;
+    let (display_width, display_height) = vm.display.size();
+    *width = display_width as u32;
+    *height = display_height as u32;
+    if out.is_null() || len < display_width * display_height {
+        return Chip8Status::BufferTooSmall;
+    }
+    let out = slice::from_raw_parts_mut(out, len);
+    guard(|| {
+        for (row, pixels) in out.chunks_mut(display_width).zip(vm.display.gfx.iter()) {
+            row.copy_from_slice(pixels);
+        }
+        Chip8Status::Ok
+    })
+}
+
+/// Store the registers of the virtual machine in 'registers'.
+/// Only the 16 oldest entries of a deeper stack are stored.
+///
+/// # Safety
+/// 'vm' and 'registers' must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_registers(
+    vm: *const Chip8,
+    registers: *mut Chip8Registers,
+) -> Chip8Status {
+    if vm.is_null() || registers.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let (vm, registers) = (&*vm, &mut *registers);
+    guard(|| {
+        for (x, v) in registers.v.iter_mut().enumerate() {
+            *v = vm.register(x);
+        }
+        registers.i = vm.index() as u16;
+        registers.pc = vm.pc() as u16;
+        let stack = vm.stack();
+        let depth = stack.len().min(registers.stack.len());
+        registers.sp = depth as u8;
+        registers.stack = [0; 16];
+        registers.stack[..depth].copy_from_slice(&stack[..depth]);
+        registers.delay_timer = vm.delay_timer;
+        registers.sound_timer = vm.sound_timer;
+        Chip8Status::Ok
+    })
+}
+
+/// Overwrite the registers of the virtual machine with 'registers'.
+/// The index, program counter and stack pointer must stay within bounds,
+/// the stack within the maximum depth of the virtual machine.
+///
+/// # Safety
+/// 'vm' and 'registers' must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_registers(
+    vm: *mut Chip8,
+    registers: *const Chip8Registers,
+) -> Chip8Status {
+    if vm.is_null() || registers.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let (vm, registers) = (&mut *vm, &*registers);
+    guard(|| {
+        if registers.i as usize >= vm.memory().len()
+            || registers.pc as usize >= vm.memory().len()
+            || registers.sp as usize > registers.stack.len()
+        {
+            return Chip8Status::InvalidArgument;
+        }
+        if vm
+            .set_stack(&registers.stack[..registers.sp as usize])
+            .is_err()
+        {
+            return Chip8Status::InvalidArgument;
+        }
+        for (x, &v) in registers.v.iter().enumerate() {
+            vm.set_register(x, v);
+        }
+        vm.set_index(registers.i as usize);
+        vm.set_pc(registers.pc as usize);
+        vm.delay_timer = registers.delay_timer;
+        vm.sound_timer = registers.sound_timer;
+        Chip8Status::Ok
+    })
+}
+
+/// Serialize the whole state of the virtual machine to 'out'.
+/// The size of the snapshot is always stored in 'written' ; if 'out' is
+/// null or too small 'BufferTooSmall' is returned and nothing is copied.
+///
+/// # Safety
+/// 'vm' and 'written' must be valid pointers ; 'out' must be null or point
+/// to 'len' writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_snapshot(
+    vm: *const Chip8,
+    out: *mut u8,
+    len: usize,
+    written: *mut usize,
+) -> Chip8Status {
+    if vm.is_null() || written.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let vm = &*vm;
+    let state = match catch_unwind(AssertUnwindSafe(|| vm.save_state())) {
+        Ok(state) => state,
+        Err(_) => return Chip8Status::Panic,
+    };
+    *written = state.len();
+    if out.is_null() || len < state.len() {
+        return Chip8Status::BufferTooSmall;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+    Chip8Status::Ok
+}
+
+/// Restore a snapshot taken by 'chip8_snapshot'. On failure the virtual
+/// machine is left untouched.
+///
+/// # Safety
+/// 'vm' must be a valid handle and 'data' must point to 'len' readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_restore(vm: *mut Chip8, data: *const u8, len: usize) -> Chip8Status {
+    if vm.is_null() || data.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let (vm, state) = (&mut *vm, slice::from_raw_parts(data, len));
+    guard(|| match vm.load_state(state) {
+        Ok(()) => Chip8Status::Ok,
+        Err(why) => {
+            warn!("cannot restore the snapshot : {}", why);
+            Chip8Status::InvalidSnapshot
+        }
+    })
+}
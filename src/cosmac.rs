@@ -0,0 +1,879 @@
+/// Low-level emulation of the COSMAC VIP : its RCA CDP1802 CPU, its memory
+/// map and the DMA of its CDP1861 video chip, running the original CHIP 8
+/// interpreter instead of 'Chip8::execute_opcode'.
+///
+/// No ROM or interpreter image is bundled : the 512 bytes of the interpreter
+/// must be supplied, and the monitor ROM too since the interpreter of the VIP
+/// calls its interrupt (display and timers) and keypad routines.
+///
+/// References :
+/// - RCA CDP1802 user manual (MPM-201)
+/// - RCA COSMAC VIP instruction manual (VIP-311)
+use crate::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::keypad::{Keypad, Keystate};
+use crate::machine::Machine;
+use crate::platform::MemoryMap;
+use crate::state::{StateReader, StateWriter};
+use crate::vm::LoadError;
+
+/// The frequency of the machine cycles of the VIP, in Hz : its 1.76064 MHz
+/// clock divided by the 8 clock pulses of a machine cycle.
+pub const MACHINE_CLOCK: u32 = 220_080;
+/// The number of machine cycles of a display line of the 1861.
+const CYCLES_PER_LINE: u32 = 14;
+/// The number of display lines of a frame, blanking included.
+const LINES_PER_FRAME: u32 = 262;
+/// The number of machine cycles of a 60 Hz frame.
+pub const CYCLES_PER_FRAME: u32 = CYCLES_PER_LINE * LINES_PER_FRAME;
+/// The first line displayed, fetched by DMA.
+const FIRST_DISPLAY_LINE: u32 = 80;
+/// The number of lines displayed, 8 bytes each.
+const DISPLAY_LINES: u32 = 128;
+/// The bytes fetched by DMA for each line.
+const BYTES_PER_LINE: usize = DISPLAY_WIDTH / 8;
+/// The 1861 requests an interrupt 29 machine cycles before the first DMA.
+const INTERRUPT_CYCLE: u32 = FIRST_DISPLAY_LINE * CYCLES_PER_LINE - 29;
+/// The size of the RAM of the VIP, in bytes, mirrored up to 0x7FFF.
+pub const VIP_RAM_SIZE: usize = 0x1000;
+/// The size of the monitor ROM, mirrored from 0x8000 to 0xFFFF.
+pub const MONITOR_SIZE: usize = 0x200;
+/// The highest address line, selecting the monitor ROM.
+const ROM_SELECT: u16 = 0x8000;
+/// The magic bytes starting the serialized states.
+const STATE_MAGIC: &[u8] = b"VIPST\x01";
+
+/// The signals the CDP1802 exchanges with the rest of the computer.
+pub trait Cdp1802Bus {
+    /// Read the byte at the given address.
+    fn read(&mut self, address: u16) -> u8;
+    /// Write the given byte at the given address.
+    fn write(&mut self, address: u16, value: u8);
+    /// OUT N, N from 1 to 7 : the byte read from memory is on the data bus.
+    fn output(&mut self, port: u8, value: u8);
+    /// INP N, N from 1 to 7 : return the byte put on the data bus.
+    fn input(&mut self, port: u8) -> u8;
+    /// Is the external flag EF1 to EF4 asserted ?
+    fn flag(&self, n: u8) -> bool;
+}
+
+/// The RCA CDP1802 CPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cdp1802 {
+    /// The 16 scratchpad registers R0 to RF.
+    pub r: [u16; 16],
+    /// The index of the program counter register.
+    pub p: u8,
+    /// The index of the data pointer register.
+    pub x: u8,
+    /// The accumulator.
+    pub d: u8,
+    /// The carry / not borrow flag.
+    pub df: bool,
+    /// The X and P saved by an interrupt or MARK.
+    pub t: u8,
+    /// Are the interrupts enabled ?
+    pub ie: bool,
+    /// The Q output, driving the speaker of the VIP.
+    pub q: bool,
+    /// Is the CPU stopped by IDL, until a DMA or an interrupt ?
+    pub idle: bool,
+}
+
+impl Cdp1802 {
+    /// Create a CPU in its reset state.
+    pub fn new() -> Cdp1802 {
+        Cdp1802 {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    /// Reset the CPU : X, P, Q and R0 are cleared and the interrupts
+    /// enabled, the other registers are kept.
+    pub fn reset(&mut self) {
+        self.p = 0;
+        self.x = 0;
+        self.r[0] = 0;
+        self.q = false;
+        self.ie = true;
+        self.idle = false;
+    }
+
+    /// Service an interrupt : save X and P in T, then run the interrupt
+    /// routine with R1 as the program counter and R2 as the data pointer.
+    /// Takes 1 machine cycle.
+    pub fn interrupt(&mut self) {
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+    }
+
+    /// Perform a DMA output cycle : return the byte at R0, then increment
+    /// R0. Takes 1 machine cycle.
+    pub fn dma_out<B: Cdp1802Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    /// Fetch and execute an instruction.
+    /// Return the number of machine cycles it took : 3 for the long
+    /// branches and skips, 2 otherwise.
+    pub fn execute<B: Cdp1802Bus>(&mut self, bus: &mut B) -> u32 {
+        let op = self.immediate(bus);
+        self.execute_instruction(op, bus)
+    }
+
+    /// Execute the given instruction, the program counter pointing right
+    /// after it.
+    /// Return the number of machine cycles it took, see 'execute'.
+    pub fn execute_instruction<B: Cdp1802Bus>(&mut self, op: u8, bus: &mut B) -> u32 {
+        let n = op & 0x0F;
+        let rn = n as usize;
+        match op >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            // LDN
+            0x0 => self.d = bus.read(self.r[rn]),
+            0x1 => self.r[rn] = self.r[rn].wrapping_add(1),
+            0x2 => self.r[rn] = self.r[rn].wrapping_sub(1),
+            0x3 => {
+                let taken = self.condition(n & 0x07, bus) != (n & 0x08 != 0);
+                self.short_branch(taken, bus);
+            }
+            // LDA
+            0x4 => self.d = self.load_advance(rn, bus),
+            // STR
+            0x5 => bus.write(self.r[rn], self.d),
+            // IRX
+            0x6 if n == 0 => self.advance_x(),
+            // OUT N
+            0x6 if n < 8 => {
+                let value = self.load_advance(self.x as usize, bus);
+                bus.output(n, value);
+            }
+            // not used by the CDP1802
+            0x6 if n == 8 => {}
+            // INP N
+            0x6 => {
+                let value = bus.input(n - 8);
+                bus.write(self.r[self.x as usize], value);
+                self.d = value;
+            }
+            0x7 => self.execute_control(n, bus),
+            // GLO, GHI, PLO, PHI
+            0x8 => self.d = self.r[rn] as u8,
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            0xA => self.r[rn] = self.r[rn] & 0xFF00 | self.d as u16,
+            0xB => self.r[rn] = self.r[rn] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.execute_long(n, bus);
+                return 3;
+            }
+            // SEP, SEX
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => self.execute_arithmetic(n, bus),
+        }
+        2
+    }
+
+    /// Execute the 7N instructions : returns, memory and arithmetic with
+    /// carry, Q control.
+    fn execute_control<B: Cdp1802Bus>(&mut self, n: u8, bus: &mut B) {
+        match n {
+            // RET, DIS
+            0x0 | 0x1 => {
+                let value = self.load_advance(self.x as usize, bus);
+                self.x = value >> 4;
+                self.p = value & 0x0F;
+                self.ie = n == 0x0;
+            }
+            // LDXA
+            0x2 => self.d = self.load_advance(self.x as usize, bus),
+            // STXD
+            0x3 => {
+                bus.write(self.r[self.x as usize], self.d);
+                let rx = &mut self.r[self.x as usize];
+                *rx = rx.wrapping_sub(1);
+            }
+            // ADC, SDB, SMB
+            0x4 => {
+                let m = bus.read(self.r[self.x as usize]);
+                self.add(m, self.d, self.df);
+            }
+            0x5 => {
+                let m = bus.read(self.r[self.x as usize]);
+                self.add(m, !self.d, self.df);
+            }
+            0x7 => {
+                let m = bus.read(self.r[self.x as usize]);
+                self.add(self.d, !m, self.df);
+            }
+            // SHRC
+            0x6 => {
+                let carry = self.d & 0x01 != 0;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            // SAV
+            0x8 => bus.write(self.r[self.x as usize], self.t),
+            // MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            // REQ, SEQ
+            0xA => self.q = false,
+            0xB => self.q = true,
+            // ADCI, SDBI, SMBI
+            0xC => {
+                let m = self.immediate(bus);
+                self.add(m, self.d, self.df);
+            }
+            0xD => {
+                let m = self.immediate(bus);
+                self.add(m, !self.d, self.df);
+            }
+            0xF => {
+                let m = self.immediate(bus);
+                self.add(self.d, !m, self.df);
+            }
+            // SHLC
+            _ => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+        }
+    }
+
+    /// Execute the CN instructions : long branches and long skips.
+    fn execute_long<B: Cdp1802Bus>(&mut self, n: u8, bus: &mut B) {
+        let p = self.p as usize;
+        match n {
+            // NOP
+            0x4 => {}
+            // LSNQ, LSNZ, LSNF, LSKP, LSIE, LSQ, LSZ, LSDF
+            0x5..=0x8 | 0xC..=0xF => {
+                let skip = match n {
+                    0x5 => !self.q,
+                    0x6 => self.d != 0,
+                    0x7 => !self.df,
+                    0x8 => true,
+                    0xC => self.ie,
+                    0xD => self.q,
+                    0xE => self.d == 0,
+                    _ => self.df,
+                };
+                if skip {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LBR, LBQ, LBZ, LBDF, LBNQ, LBNZ, LBNF
+            _ => {
+                if self.condition(n & 0x03, bus) != (n & 0x08 != 0) {
+                    let high = bus.read(self.r[p]) as u16;
+                    let low = bus.read(self.r[p].wrapping_add(1)) as u16;
+                    self.r[p] = high << 8 | low;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+        }
+    }
+
+    /// Execute the FN instructions : logic and arithmetic with memory or
+    /// immediate operands, shifts.
+    fn execute_arithmetic<B: Cdp1802Bus>(&mut self, n: u8, bus: &mut B) {
+        // SHR, SHL
+        if n & 0x07 == 0x06 {
+            if n == 0x06 {
+                self.df = self.d & 0x01 != 0;
+                self.d >>= 1;
+            } else {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            return;
+        }
+        let m = if n < 0x08 {
+            bus.read(self.r[self.x as usize])
+        } else {
+            self.immediate(bus)
+        };
+        match n & 0x07 {
+            // LDX, LDI
+            0x0 => self.d = m,
+            0x1 => self.d |= m,
+            0x2 => self.d &= m,
+            0x3 => self.d ^= m,
+            // ADD, ADI
+            0x4 => self.add(m, self.d, false),
+            // SD, SDI
+            0x5 => self.add(m, !self.d, true),
+            // SM, SMI
+            _ => self.add(self.d, !m, true),
+        }
+    }
+
+    /// Set D to a + b + carry, and DF to the carry out. The subtractions
+    /// add the complement, DF being then set when there is no borrow.
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// Return the condition of the branches of the given index : always,
+    /// Q, D zero, DF, then EF1 to EF4.
+    fn condition<B: Cdp1802Bus>(&self, index: u8, bus: &B) -> bool {
+        match index {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            _ => bus.flag(index - 3),
+        }
+    }
+
+    /// Take the short branch to the address in the same page given by the
+    /// immediate byte, or skip it.
+    fn short_branch<B: Cdp1802Bus>(&mut self, taken: bool, bus: &mut B) {
+        let p = self.p as usize;
+        if taken {
+            let low = bus.read(self.r[p]) as u16;
+            self.r[p] = self.r[p] & 0xFF00 | low;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    /// Read the byte at the program counter, and advance it.
+    fn immediate<B: Cdp1802Bus>(&mut self, bus: &mut B) -> u8 {
+        self.load_advance(self.p as usize, bus)
+    }
+
+    /// Read the byte pointed to by the given register, then increment it.
+    fn load_advance<B: Cdp1802Bus>(&mut self, n: usize, bus: &mut B) -> u8 {
+        let value = bus.read(self.r[n]);
+        self.r[n] = self.r[n].wrapping_add(1);
+        value
+    }
+
+    /// Increment the data pointer.
+    fn advance_x(&mut self) {
+        let rx = &mut self.r[self.x as usize];
+        *rx = rx.wrapping_add(1);
+    }
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Cdp1802 {
+        Cdp1802::new()
+    }
+}
+
+/// The memory and the devices of the VIP, as seen by the CPU.
+struct Board {
+    ram: Vec<u8>,
+    monitor: Vec<u8>,
+    /// Is the monitor ROM also mapped at 0x0000 ? Set on reset, until the
+    /// first access to an address with A15 high.
+    shadow: bool,
+    /// The key of the hexadecimal keypad selected by OUT 2, reported on EF3.
+    key_latch: u8,
+    /// Is the 1861 displaying (INP 1) or off (OUT 1) ?
+    display_enabled: bool,
+    /// The 1861 status reported on EF1 : asserted during the 4 lines before
+    /// the display and its 4 last lines.
+    display_status: bool,
+}
+
+impl Board {
+    /// Return the byte at the given address, without side effects.
+    fn peek(&self, address: u16) -> u8 {
+        if address & ROM_SELECT != 0 || self.shadow {
+            self.read_rom(address)
+        } else {
+            self.ram[address as usize % VIP_RAM_SIZE]
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if self.monitor.is_empty() {
+            // nothing on the data bus : reads as IDL
+            0x00
+        } else {
+            self.monitor[address as usize % self.monitor.len()]
+        }
+    }
+}
+
+/// The board and the keypad, for the duration of an instruction.
+struct VipBus<'a> {
+    board: &'a mut Board,
+    keypad: &'a Keypad,
+}
+
+impl<'a> Cdp1802Bus for VipBus<'a> {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & ROM_SELECT != 0 {
+            self.board.shadow = false;
+        }
+        self.board.peek(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & ROM_SELECT != 0 {
+            self.board.shadow = false;
+            return;
+        }
+        self.board.ram[address as usize % VIP_RAM_SIZE] = value;
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.board.display_enabled = false,
+            2 => self.board.key_latch = value & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.board.display_enabled = true;
+        }
+        0x00
+    }
+
+    fn flag(&self, n: u8) -> bool {
+        match n {
+            1 => self.board.display_status,
+            3 => match self.keypad.get_key_state(self.board.key_latch as usize) {
+                Keystate::Pressed => true,
+                Keystate::Released => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// The COSMAC VIP, with 4 KB of RAM, running the CHIP 8 interpreter loaded
+/// at 0x0000 and the program at 0x200.
+/// It exposes the same display, keypad and timers as 'Chip8', the display
+/// being updated by the DMA of the 1861 and the timers by the interrupt
+/// routine of the monitor.
+pub struct Vip {
+    pub cpu: Cdp1802,
+    board: Board,
+    /// The 64x32 display, or 64x64 with the two-page hi-res interpreter.
+    pub display: Display,
+    pub keypad: Keypad,
+    /// The number of display lines repeated for each row of the display.
+    lines_per_row: u32,
+    /// The machine cycles elapsed in the current frame.
+    frame_cycle: u32,
+    /// The next display line to fetch by DMA in the current frame.
+    dma_line: u32,
+    /// The machine cycles elapsed since the reset.
+    cycles: u64,
+}
+
+impl Vip {
+    /// Create a VIP with a cleared RAM and no ROM.
+    pub fn new() -> Vip {
+        let mut vip = Vip {
+            cpu: Cdp1802::new(),
+            board: Board {
+                ram: vec![0; VIP_RAM_SIZE],
+                monitor: Vec::new(),
+                shadow: false,
+                key_latch: 0,
+                display_enabled: false,
+                display_status: false,
+            },
+            display: Display::new(),
+            keypad: Keypad::new(),
+            lines_per_row: DISPLAY_LINES / DISPLAY_HEIGHT as u32,
+            frame_cycle: 0,
+            dma_line: 0,
+            cycles: 0,
+        };
+        vip.reset();
+        vip
+    }
+
+    /// Reset the VIP, as its RUN switch : the RAM is kept, and the monitor
+    /// ROM if any boots, mapped at 0x0000, then starts the interpreter
+    /// unless the C key is held.
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.board.shadow = !self.board.monitor.is_empty();
+        self.board.key_latch = 0;
+        self.board.display_enabled = false;
+        self.board.display_status = false;
+        self.display =
+            Display::with_size(DISPLAY_WIDTH, (DISPLAY_LINES / self.lines_per_row) as usize);
+        self.frame_cycle = 0;
+        self.dma_line = 0;
+        self.cycles = 0;
+    }
+
+    /// Load the monitor ROM, mirrored from 0x8000, and reset.
+    /// If the operation fails, return a String explaining why.
+    pub fn load_monitor(&mut self, rom: &[u8]) -> Result<(), String> {
+        if rom.len() != MONITOR_SIZE {
+            return Err(format!(
+                "invalid monitor ROM size ({} bytes, {} expected)",
+                rom.len(),
+                MONITOR_SIZE
+            ));
+        }
+        self.board.monitor = rom.to_vec();
+        self.reset();
+        Ok(())
+    }
+
+    /// Load the interpreter image at 0x0000, up to the program area.
+    /// If the operation fails, return a String explaining why.
+    pub fn load_interpreter(&mut self, image: &[u8]) -> Result<(), String> {
+        let max = MemoryMap::vip().entry_point;
+        if image.is_empty() || image.len() > max {
+            return Err(format!(
+                "invalid interpreter size ({} bytes, {} at most)",
+                image.len(),
+                max
+            ));
+        }
+        self.board.ram[..image.len()].copy_from_slice(image);
+        Ok(())
+    }
+
+    /// Return the maximum size of a program : from 0x200 up to the variables
+    /// of the interpreter.
+    pub fn max_program_size(&self) -> usize {
+        let memory_map = MemoryMap::vip();
+        memory_map.program_end(memory_map.entry_point) - memory_map.entry_point
+    }
+
+    /// Load the given CHIP 8 program at 0x200.
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        let max = self.max_program_size();
+        if rom.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        if rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        let start = MemoryMap::vip().entry_point;
+        self.board.ram[start..start + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Set the height of the display : 32 rows for the standard interpreter,
+    /// which repeats each row on 4 display lines, or 64 rows for the two-page
+    /// hi-res interpreter. Resets the VIP.
+    /// If the operation fails, return a String explaining why.
+    pub fn set_display_height(&mut self, height: usize) -> Result<(), String> {
+        match height {
+            32 | 64 | 128 => {
+                self.lines_per_row = DISPLAY_LINES / height as u32;
+                self.reset();
+                Ok(())
+            }
+            _ => Err(format!("invalid display height : {}", height)),
+        }
+    }
+
+    /// Return the RAM.
+    pub fn ram(&self) -> &[u8] {
+        &self.board.ram
+    }
+
+    /// Return the machine cycles elapsed since the reset.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Return the delay timer, kept in R8.1 by the VIP interpreter.
+    pub fn delay_timer(&self) -> u8 {
+        (self.cpu.r[8] >> 8) as u8
+    }
+
+    /// Return the sound timer, kept in R8.0 by the VIP interpreter.
+    pub fn sound_timer(&self) -> u8 {
+        self.cpu.r[8] as u8
+    }
+
+    /// Is the speaker on, driven by Q ?
+    pub fn is_beeping(&self) -> bool {
+        self.cpu.q
+    }
+
+    /// Run the VIP for a 60 Hz frame.
+    pub fn run_frame(&mut self) {
+        while self.frame_cycle < CYCLES_PER_FRAME {
+            self.step();
+        }
+        self.frame_cycle -= CYCLES_PER_FRAME;
+        self.dma_line = 0;
+        if !self.board.display_enabled && self.display.gfx.iter().flatten().any(|&p| p != 0) {
+            self.display.clear();
+        }
+    }
+
+    /// Run the VIP until the next instruction, DMA or interrupt is done.
+    pub fn step(&mut self) {
+        let enabled = self.board.display_enabled;
+        let line = self.frame_cycle / CYCLES_PER_LINE;
+        let status_lines = [
+            FIRST_DISPLAY_LINE - 4..FIRST_DISPLAY_LINE,
+            FIRST_DISPLAY_LINE + DISPLAY_LINES - 4..FIRST_DISPLAY_LINE + DISPLAY_LINES,
+        ];
+        self.board.display_status =
+            enabled && status_lines.iter().any(|lines| lines.contains(&line));
+
+        let dma_cycle = (FIRST_DISPLAY_LINE + self.dma_line) * CYCLES_PER_LINE;
+        if enabled && self.dma_line < DISPLAY_LINES && self.frame_cycle >= dma_cycle {
+            self.dma();
+            return;
+        }
+        let interrupt = enabled
+            && self.dma_line == 0
+            && (INTERRUPT_CYCLE..FIRST_DISPLAY_LINE * CYCLES_PER_LINE).contains(&self.frame_cycle);
+        if interrupt && self.cpu.ie {
+            self.cpu.interrupt();
+            self.advance(1);
+        } else if self.cpu.idle {
+            self.advance(1);
+        } else {
+            let mut bus = VipBus {
+                board: &mut self.board,
+                keypad: &self.keypad,
+            };
+            let cycles = self.cpu.execute(&mut bus);
+            self.advance(cycles);
+        }
+    }
+
+    /// Fetch the next display line by DMA, and update the display row it
+    /// belongs to.
+    fn dma(&mut self) {
+        let line = self.dma_line;
+        let mut bus = VipBus {
+            board: &mut self.board,
+            keypad: &self.keypad,
+        };
+        let mut bytes = [0; BYTES_PER_LINE];
+        for byte in bytes.iter_mut() {
+            *byte = self.cpu.dma_out(&mut bus);
+        }
+        if line.is_multiple_of(self.lines_per_row) {
+            let row = &mut self.display.gfx[(line / self.lines_per_row) as usize];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let value = bytes[x / 8] >> (7 - x % 8) & 0x01;
+                if *pixel != value {
+                    *pixel = value;
+                    self.display.dirty = true;
+                }
+            }
+        }
+        self.dma_line += 1;
+        self.advance(BYTES_PER_LINE as u32);
+    }
+
+    /// Count the given machine cycles.
+    fn advance(&mut self, cycles: u32) {
+        self.frame_cycle += cycles;
+        self.cycles += cycles as u64;
+    }
+
+    /// Serialize the whole state of the VIP (CPU, RAM, devices, display and
+    /// keypad), for instance to implement save states.
+    /// The ROM is not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(STATE_MAGIC);
+        let cpu = &self.cpu;
+        for &r in cpu.r.iter() {
+            state.write_u16(r);
+        }
+        state.write_bytes(&[cpu.p, cpu.x, cpu.d, cpu.t]);
+        for &flag in [cpu.df, cpu.ie, cpu.q, cpu.idle].iter() {
+            state.write_bool(flag);
+        }
+        state.write_bytes(&self.board.ram);
+        state.write_bool(self.board.shadow);
+        state.write_u8(self.board.key_latch);
+        state.write_bool(self.board.display_enabled);
+        state.write_bool(self.board.display_status);
+        state.write_u8(self.lines_per_row as u8);
+        state.write_u32(self.frame_cycle);
+        state.write_u32(self.dma_line);
+        state.write_u32((self.cycles >> 32) as u32);
+        state.write_u32(self.cycles as u32);
+        for row in self.display.gfx.iter() {
+            state.write_bytes(row);
+        }
+        for key in 0..16 {
+            state.write_bool(match self.keypad.get_key_state(key) {
+                Keystate::Pressed => true,
+                Keystate::Released => false,
+            });
+        }
+        state.finish()
+    }
+
+    /// Restore a state serialized by 'save_state'.
+    /// If the operation fails, return a String explaining why and leave the
+    /// VIP untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut state = StateReader::new(data, STATE_MAGIC)?;
+        let mut cpu = Cdp1802::new();
+        for r in cpu.r.iter_mut() {
+            *r = state.read_u16()?;
+        }
+        let bytes = state.read_bytes(4)?;
+        cpu.p = bytes[0] & 0x0F;
+        cpu.x = bytes[1] & 0x0F;
+        cpu.d = bytes[2];
+        cpu.t = bytes[3];
+        cpu.df = state.read_bool()?;
+        cpu.ie = state.read_bool()?;
+        cpu.q = state.read_bool()?;
+        cpu.idle = state.read_bool()?;
+        let ram = state.read_bytes(VIP_RAM_SIZE)?.to_vec();
+        let shadow = state.read_bool()?;
+        let key_latch = state.read_u8()? & 0x0F;
+        let display_enabled = state.read_bool()?;
+        let display_status = state.read_bool()?;
+        let lines_per_row = state.read_u8()? as u32;
+        if !matches!(lines_per_row, 1 | 2 | 4) {
+            return Err(format!("invalid state : {} lines per row", lines_per_row));
+        }
+        let frame_cycle = state.read_u32()?;
+        let dma_line = state.read_u32()?;
+        if frame_cycle > CYCLES_PER_FRAME + BYTES_PER_LINE as u32 || dma_line > DISPLAY_LINES {
+            return Err("invalid state : invalid display timing".to_owned());
+        }
+        let cycles = (state.read_u32()? as u64) << 32 | state.read_u32()? as u64;
+        let mut display =
+            Display::with_size(DISPLAY_WIDTH, (DISPLAY_LINES / lines_per_row) as usize);
+        for row in display.gfx.iter_mut() {
+            row.copy_from_slice(state.read_bytes(DISPLAY_WIDTH)?);
+        }
+        let mut keypad = Keypad::new();
+        for key in 0..16 {
+            if state.read_bool()? {
+                keypad.set_key_state(key, Keystate::Pressed);
+            }
+        }
+        state.finish()?;
+
+        self.cpu = cpu;
+        self.board.ram = ram;
+        self.board.shadow = shadow && !self.board.monitor.is_empty();
+        self.board.key_latch = key_latch;
+        self.board.display_enabled = display_enabled;
+        self.board.display_status = display_status;
+        self.lines_per_row = lines_per_row;
+        self.frame_cycle = frame_cycle;
+        self.dma_line = dma_line;
+        self.cycles = cycles;
+        self.display = display;
+        self.keypad = keypad;
+        Ok(())
+    }
+}
+
+impl Default for Vip {
+    fn default() -> Vip {
+        Vip::new()
+    }
+}
+
+impl Machine for Vip {
+    /// The CDP1802 instruction, i.e. its first byte : the instructions with
+    /// operands read them when executed.
+    type Instruction = u8;
+
+    fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        Vip::load_bytes(self, rom)
+    }
+
+    fn reset(&mut self) {
+        Vip::reset(self)
+    }
+
+    fn decode(&self) -> Option<u8> {
+        Some(self.board.peek(self.cpu.r[self.cpu.p as usize]))
+    }
+
+    fn execute(&mut self, op: u8) {
+        let p = self.cpu.p as usize;
+        self.cpu.r[p] = self.cpu.r[p].wrapping_add(1);
+        let mut bus = VipBus {
+            board: &mut self.board,
+            keypad: &self.keypad,
+        };
+        let cycles = self.cpu.execute_instruction(op, &mut bus);
+        self.advance(cycles);
+    }
+
+    /// Run a frame of the VIP, whose clock is fixed : the number of
+    /// instructions is ignored.
+    fn run_frame(&mut self, _cycles: u32) -> bool {
+        Vip::run_frame(self);
+        false
+    }
+
+    fn display(&self) -> &Display {
+        &self.display
+    }
+
+    fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn delay_timer(&self) -> u8 {
+        Vip::delay_timer(self)
+    }
+
+    fn sound_timer(&self) -> u8 {
+        Vip::sound_timer(self)
+    }
+
+    fn is_beeping(&self) -> bool {
+        Vip::is_beeping(self)
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        Vip::save_state(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        Vip::load_state(self, data)
+    }
+}
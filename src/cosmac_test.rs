@@ -0,0 +1,179 @@
+use super::cosmac::{Cdp1802, Cdp1802Bus, Vip, CYCLES_PER_FRAME};
+use super::keypad::Keystate::*;
+
+/// 256 bytes of RAM, with the output ports and flags recorded.
+struct TestBus {
+    ram: Vec<u8>,
+    outputs: Vec<(u8, u8)>,
+    flags: [bool; 4],
+}
+
+impl Cdp1802Bus for TestBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.ram[address as usize & 0xFF]
+    }
+    fn write(&mut self, address: u16, value: u8) {
+        self.ram[address as usize & 0xFF] = value;
+    }
+    fn output(&mut self, port: u8, value: u8) {
+        self.outputs.push((port, value));
+    }
+    fn input(&mut self, port: u8) -> u8 {
+        0x10 + port
+    }
+    fn flag(&self, n: u8) -> bool {
+        self.flags[n as usize - 1]
+    }
+}
+
+/// Run the given program from 0x0000 until it stops on IDL.
+fn run(program: &[u8], flags: [bool; 4]) -> (Cdp1802, TestBus) {
+    let mut bus = TestBus {
+        ram: vec![0; 0x100],
+        outputs: Vec::new(),
+        flags,
+    };
+    bus.ram[..program.len()].copy_from_slice(program);
+    let mut cpu = Cdp1802::new();
+    let mut cycles = 0;
+    while !cpu.idle && cycles < 1000 {
+        cycles += cpu.execute(&mut bus);
+    }
+    (cpu, bus)
+}
+
+#[test]
+fn cdp1802_arithmetic() {
+    // 0xF0 + 0x20 with a carry, then 0x10 - 0x30 with a borrow
+    let (cpu, _) = run(
+        &[0xF8, 0xF0, 0xFC, 0x20, 0xA3, 0xF8, 0x10, 0xFF, 0x30, 0x00],
+        [false; 4],
+    );
+    assert_eq!(cpu.r[3] & 0xFF, 0x10);
+    assert_eq!((cpu.d, cpu.df), (0xE0, false));
+
+    // SHRC through DF, SHL, subtraction with borrow
+    let (cpu, _) = run(
+        &[0xF8, 0x81, 0x76, 0x76, 0xFE, 0x7F, 0x01, 0x00],
+        [false; 4],
+    );
+    assert_eq!(cpu.d, 0x3F);
+    assert!(cpu.df);
+
+    // STXD / IRX with R2, then M(R2) - 0x40 with SD
+    let (cpu, bus) = run(
+        &[
+            0xF8, 0x80, 0xA2, 0xE2, 0xF8, 0x55, 0x73, 0x60, 0xF8, 0x40, 0xF5, 0x00,
+        ],
+        [false; 4],
+    );
+    assert_eq!(bus.ram[0x80], 0x55);
+    assert_eq!((cpu.d, cpu.df), (0x15, true));
+}
+
+#[test]
+fn cdp1802_control() {
+    // SEP to a subroutine using R3, which sets Q and returns with SEP R0
+    let (cpu, _) = run(
+        &[
+            0xF8, 0x10, 0xA3, 0xD3, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7B, 0xD0,
+        ],
+        [false; 4],
+    );
+    assert!(cpu.q);
+    assert_eq!((cpu.p, cpu.r[0]), (0, 0x0005));
+
+    // the short branch is taken on EF2 only, the long branch on a zero D
+    let program = [
+        0x35, 0x04, 0x00, 0x00, 0xF8, 0x00, 0xC2, 0x00, 0x0B, 0x00, 0x00, 0x7B, 0x00,
+    ];
+    let (cpu, _) = run(&program, [false; 4]);
+    assert_eq!((cpu.r[0], cpu.q), (0x0003, false));
+    let (cpu, _) = run(&program, [false, true, false, false]);
+    assert_eq!((cpu.r[0], cpu.q), (0x000D, true));
+
+    // OUT reads from M(R(X)), INP writes to it
+    let (_, bus) = run(
+        &[0xE3, 0xF8, 0x08, 0xA3, 0x62, 0x6B, 0x00, 0x00, 0x42],
+        [false; 4],
+    );
+    assert_eq!(bus.outputs, vec![(2, 0x42)]);
+    assert_eq!(bus.ram[0x09], 0x13);
+
+    // interrupt : T saved, R1 as the program counter, returning with RET
+    let mut cpu = Cdp1802::new();
+    let mut bus = TestBus {
+        ram: vec![0; 0x100],
+        outputs: Vec::new(),
+        flags: [false; 4],
+    };
+    bus.ram[0x40..0x43].copy_from_slice(&[0x78, 0x7A, 0x70]);
+    cpu.r[1] = 0x40;
+    cpu.r[2] = 0x80;
+    cpu.p = 3;
+    cpu.q = true;
+    cpu.interrupt();
+    assert!(!cpu.ie);
+    assert_eq!((cpu.p, cpu.x), (1, 2));
+    for _ in 0..3 {
+        cpu.execute(&mut bus);
+    }
+    assert_eq!((cpu.p, cpu.x, cpu.q, cpu.ie), (3, 0, false, true));
+}
+
+/// A small interpreter showing the 64x32 bits at 0x200 : it enables the
+/// display, runs from R3 and sets the delay timer in R8.1 then Q while the
+/// key 5 is pressed ; its interrupt routine points R0 to the bits to display
+/// and waits for the end of the display.
+const INTERPRETER: &[u8] = &[
+    // 0x00 : R3 = 0x06, SEP R3
+    0xF8, 0x06, 0xA3, 0xD3, 0x00, 0x00, //
+    // 0x06 : R1 = 0x31, R2 = 0xFF, X = 2, display on, R8.1 = 0x20
+    0xF8, 0x31, 0xA1, 0xF8, 0xFF, 0xA2, 0xE2, 0x69, 0xF8, 0x20, 0xB8, //
+    // 0x11 : key latch = 5, Q = key pressed
+    0xF8, 0x05, 0x52, 0x62, 0x22, 0x36, 0x1B, 0x7A, 0x30, 0x11, 0x7B, 0x30, 0x11, //
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, //
+    // 0x30 : return, then entry : save T, R0 = 0x200
+    0x70, 0x22, 0x78, 0xF8, 0x02, 0xB0, 0xF8, 0x00, 0xA0, //
+    // 0x39 : wait while EF1, then wait for EF1 and return
+    0x34, 0x39, 0x3C, 0x3B, 0x30, 0x30,
+];
+
+#[test]
+fn vip_display_and_keypad() {
+    let mut vip = Vip::new();
+    assert_eq!(INTERPRETER.len(), 0x3F);
+    assert!(vip.load_interpreter(INTERPRETER).is_ok());
+    assert!(vip.load_interpreter(&[0; 0x201]).is_err());
+    assert_eq!(vip.max_program_size(), 0xEA0 - 0x200);
+
+    // the display fetches the bytes from 0x200, one row every 4 lines
+    let mut bits = [0u8; 0x100];
+    bits[0] = 0xFF;
+    bits[4 * 8] = 0x81;
+    assert!(vip.load_bytes(&bits).is_ok());
+    vip.run_frame();
+    assert!(vip.cycles() >= CYCLES_PER_FRAME as u64);
+    assert_eq!(vip.delay_timer(), 0x20);
+    assert_eq!(vip.display.size(), (64, 32));
+    assert_eq!(vip.display.gfx[0][..9], [1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    assert_eq!(vip.display.gfx[1][..8], [1, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(vip.display.gfx[2][..8], [0; 8]);
+
+    // EF3 reports the key selected by OUT 2
+    assert!(!vip.is_beeping());
+    vip.keypad.set_key_state(0x4, Pressed);
+    vip.run_frame();
+    assert!(!vip.is_beeping());
+    vip.keypad.set_key_state(0x5, Pressed);
+    vip.run_frame();
+    assert!(vip.is_beeping());
+
+    // the 64x64 display of the hi-res interpreter, one row every 2 lines
+    assert!(vip.set_display_height(64).is_ok());
+    vip.run_frame();
+    assert_eq!(vip.display.size(), (64, 64));
+    assert_eq!(vip.display.gfx[2][..2], [1, 0]);
+    assert!(vip.set_display_height(48).is_err());
+}
@@ -0,0 +1,264 @@
+/// ROM metadata database, identifying the ROMs by the SHA-1 digest of their
+/// content to know which quirks, clock speed, colours and keys they expect.
+/// The database files use the format of the community CHIP 8 database
+/// ('programs.json'), see https://github.com/chip-8/chip-8-database :
+/// an array of programs, each with a "title" and a "roms" object mapping the
+/// SHA-1 digests to the ROM metadata.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::json::Json;
+use crate::palette::Palette;
+use crate::platform::Platform;
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::sha1::sha1_hex;
+
+/// The metadata of a ROM. Every field is optional since the local database
+/// files may only override some of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomInfo {
+    /// The title of the program.
+    pub title: Option<String>,
+    /// The identifier of the preferred platform (e.g. "originalChip8").
+    pub platform: Option<String>,
+    /// The quirks of the preferred platform, with the ROM's own exceptions.
+    pub quirks: Option<Quirks>,
+    /// The number of instructions executed per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    /// The colours of the pixels.
+    pub palette: Option<Palette>,
+    /// The keys used by the ROM, as (direction or button name, keypad index)
+    /// pairs. The names are "up", "down", "left", "right", "a" and "b".
+    pub keys: Vec<(String, usize)>,
+    /// The address where the ROM must be loaded.
+    pub start_address: Option<usize>,
+}
+
+impl RomInfo {
+    /// Override the fields of this metadata with the ones set in 'other'.
+    pub fn merge(&mut self, other: RomInfo) {
+        if other.title.is_some() {
+            self.title = other.title;
+        }
+        if other.platform.is_some() {
+            self.platform = other.platform;
+        }
+        if other.quirks.is_some() {
+            self.quirks = other.quirks;
+        }
+        if other.tickrate.is_some() {
+            self.tickrate = other.tickrate;
+        }
+        if other.palette.is_some() {
+            self.palette = other.palette;
+        }
+        if !other.keys.is_empty() {
+            self.keys = other.keys;
+        }
+        if other.start_address.is_some() {
+            self.start_address = other.start_address;
+        }
+    }
+}
+
+/// Return the quirks of the platform with the given identifier, as defined
+/// by the community database ('platforms.json').
+pub fn platform_quirks(platform: &str) -> Option<Quirks> {
+    let quirks = |shift, memory_increment, wrap, jump, vblank, logic| Quirks {
+        shift,
+        memory_increment,
+        jump,
+        logic,
+        wrap,
+        vblank,
+    };
+    use self::MemoryIncrement::*;
+    match platform {
+        "originalChip8" | "hybridVIP" | "chip8x" => {
+            Some(quirks(false, ByXPlusOne, false, false, true, true))
+        }
+        "modernChip8" => Some(quirks(false, ByXPlusOne, false, false, false, false)),
+        "chip48" => Some(quirks(true, ByX, false, true, false, false)),
+        "superchip1" => Some(quirks(true, ByX, false, true, false, false)),
+        "superchip" | "megachip8" => Some(quirks(true, Unchanged, false, true, false, false)),
+        "xochip" => Some(quirks(false, ByXPlusOne, true, false, false, false)),
+        _ => None,
+    }
+}
+
+/// Return the platform running the programs of the platform with the given
+/// identifier, as defined by the community database ('platforms.json').
+pub fn platform_from_id(platform: &str) -> Option<Platform> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(Platform::Chip8),
+        "modernChip8" => Some(Platform::Modern),
+        "chip8x" => Some(Platform::Chip8X),
+        "chip48" | "superchip1" | "superchip" => Some(Platform::SuperChip),
+        "xochip" => Some(Platform::XoChip),
+        "megachip8" => Some(Platform::MegaChip),
+        _ => None,
+    }
+}
+
+/// Apply the quirk overrides of the given JSON object (e.g. a ROM's
+/// "quirkyPlatforms" entry) to the quirks.
+fn apply_quirks(quirks: &mut Quirks, json: &Json) {
+    let flag = |name: &str| json.get(name).and_then(Json::as_bool);
+    if let Some(shift) = flag("shift") {
+        quirks.shift = shift;
+    }
+    if let Some(true) = flag("memoryIncrementByX") {
+        quirks.memory_increment = MemoryIncrement::ByX;
+    } else if let Some(true) = flag("memoryLeaveIUnchanged") {
+        quirks.memory_increment = MemoryIncrement::Unchanged;
+    } else if let (Some(false), Some(false)) =
+        (flag("memoryIncrementByX"), flag("memoryLeaveIUnchanged"))
+    {
+        quirks.memory_increment = MemoryIncrement::ByXPlusOne;
+    }
+    if let Some(wrap) = flag("wrap") {
+        quirks.wrap = wrap;
+    }
+    if let Some(jump) = flag("jump") {
+        quirks.jump = jump;
+    }
+    if let Some(vblank) = flag("vblank") {
+        quirks.vblank = vblank;
+    }
+    if let Some(logic) = flag("logic") {
+        quirks.logic = logic;
+    }
+}
+
+/// Parse the metadata of a ROM from its entry in a program's "roms" object.
+fn parse_rom(title: Option<&str>, rom: &Json) -> RomInfo {
+    let mut info = RomInfo {
+        title: title.map(str::to_owned),
+        ..RomInfo::default()
+    };
+
+    info.platform = rom
+        .get("platforms")
+        .and_then(Json::as_array)
+        .and_then(|platforms| platforms.first())
+        .and_then(Json::as_str)
+        .map(str::to_owned);
+    if let Some(ref platform) = info.platform {
+        info.quirks = platform_quirks(platform);
+        let overrides = rom.get("quirkyPlatforms").and_then(|q| q.get(platform));
+        if let Some(overrides) = overrides {
+            let mut quirks = info.quirks.unwrap_or_default();
+            apply_quirks(&mut quirks, overrides);
+            info.quirks = Some(quirks);
+        }
+    }
+
+    info.tickrate = rom
+        .get("tickrate")
+        .and_then(Json::as_f64)
+        .filter(|&tickrate| tickrate >= 1.0)
+        .map(|tickrate| tickrate as u32);
+    info.start_address = rom
+        .get("startAddress")
+        .and_then(Json::as_f64)
+        .filter(|address| (0.0..4096.0).contains(address))
+        .map(|address| address as usize);
+
+    if let Some(pixels) = rom
+        .get("colors")
+        .and_then(|colors| colors.get("pixels"))
+        .and_then(Json::as_array)
+    {
+        let hex: Vec<&str> = pixels.iter().filter_map(Json::as_str).collect();
+        let count = if hex.len() >= 4 { 4 } else { 2 };
+        if hex.len() >= count {
+            match Palette::from_hex_list(&hex[..count].join(",")) {
+                Ok(palette) => info.palette = Some(palette),
+                Err(why) => warn!("ignoring the colours of \"{:?}\" : {}", info.title, why),
+            }
+        }
+    }
+
+    if let Some(keys) = rom.get("keys").and_then(Json::as_object) {
+        for (name, index) in keys {
+            match index.as_f64() {
+                Some(index) if (0.0..16.0).contains(&index) => {
+                    info.keys.push((name.clone(), index as usize))
+                }
+                _ => warn!("ignoring the invalid key \"{}\" of {:?}", name, info.title),
+            }
+        }
+    }
+
+    info
+}
+
+/// The ROM metadata, indexed by the lowercase hexadecimal SHA-1 digest of
+/// the ROMs.
+#[derive(Default)]
+pub struct RomDatabase {
+    entries: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// Create and return a new, empty database.
+    pub fn new() -> RomDatabase {
+        RomDatabase::default()
+    }
+
+    /// Return the number of ROMs in the database.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add the ROMs of the given database file contents. The metadata of the
+    /// ROMs already known is overridden field by field, so that the local
+    /// files loaded last win.
+    /// If the operation fails, return a String explaining why and leave the
+    /// database untouched.
+    pub fn add_json(&mut self, text: &str) -> Result<(), String> {
+        let json = Json::parse(text)?;
+        let programs = match json.as_array() {
+            Some(programs) => programs,
+            None => return Err("the database must be an array of programs".to_owned()),
+        };
+        let mut entries = Vec::new();
+        for program in programs {
+            let title = program.get("title").and_then(Json::as_str);
+            let roms = match program.get("roms").and_then(Json::as_object) {
+                Some(roms) => roms,
+                None => continue,
+            };
+            for (hash, rom) in roms {
+                entries.push((hash.to_lowercase(), parse_rom(title, rom)));
+            }
+        }
+        for (hash, info) in entries {
+            self.entries.entry(hash).or_default().merge(info);
+        }
+        Ok(())
+    }
+
+    /// Add the ROMs of the database file at the given path, see 'add_json'.
+    pub fn add_file(&mut self, filepath: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(filepath)
+            .map_err(|why| format!("cannot read \"{}\" : {}", filepath.display(), why))?;
+        self.add_json(&text)
+            .map_err(|why| format!("\"{}\" : {}", filepath.display(), why))
+    }
+
+    /// Return the metadata of the ROM with the given SHA-1 digest, if known.
+    pub fn lookup_hash(&self, hash: &str) -> Option<&RomInfo> {
+        self.entries.get(&hash.to_lowercase())
+    }
+
+    /// Return the metadata of the given ROM, if known.
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.lookup_hash(&sha1_hex(rom))
+    }
+}
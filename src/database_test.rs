@@ -0,0 +1,123 @@
+use super::database::{platform_from_id, platform_quirks, RomDatabase};
+use super::json::Json;
+use super::palette::Rgb;
+use super::platform::Platform;
+use super::quirks::MemoryIncrement;
+use super::sha1::sha1_hex;
+
+#[test]
+fn sha1() {
+    assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+        sha1_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+    assert_eq!(
+        sha1_hex(&[b'a'; 1000][..]),
+        "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+    );
+}
+
+#[test]
+fn json() {
+    let json =
+        Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": "\u00e9\n\"", "a": {}} "#).unwrap();
+    assert_eq!(json.get("a"), Some(&Json::Object(Vec::new())));
+    assert_eq!(json.get("b").and_then(Json::as_str), Some("\u{e9}\n\""));
+    let first = &json.as_object().unwrap()[0].1;
+    assert_eq!(
+        first.as_array().unwrap(),
+        &[
+            Json::Number(1.0),
+            Json::Number(-25.0),
+            Json::Bool(true),
+            Json::Null
+        ][..]
+    );
+    assert_eq!(
+        Json::parse(r#""\ud83d\ude00""#).unwrap().as_str(),
+        Some("\u{1F600}")
+    );
+
+    assert!(Json::parse("").is_err());
+    assert!(Json::parse("[1, 2").is_err());
+    assert!(Json::parse("{\"a\" 1}").is_err());
+    assert!(Json::parse("[1] 2").is_err());
+    assert!(Json::parse("\"\\x\"").is_err());
+}
+
+static PROGRAMS: &str = r##"[
+  {
+    "title": "Test Program",
+    "roms": {
+      "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+        "file": "abc.ch8",
+        "platforms": ["superchip", "xochip"],
+        "quirkyPlatforms": { "superchip": { "memoryIncrementByX": true } },
+        "tickrate": 30,
+        "keys": { "up": 5, "down": 8, "a": 6, "b": 16 },
+        "colors": { "pixels": ["#101010", "#F0F0F0"] }
+      }
+    }
+  },
+  { "title": "No ROM" }
+]"##;
+
+static OVERRIDES: &str = r##"[
+  {
+    "roms": {
+      "a9993e364706816aba3e25717850c26c9cd0d89d": { "tickrate": 12 }
+    }
+  }
+]"##;
+
+#[test]
+fn database() {
+    let mut database = RomDatabase::new();
+    assert!(database.add_json("{}").is_err());
+    assert!(database.add_json(PROGRAMS).is_ok());
+    assert_eq!(database.len(), 1);
+    assert!(database.lookup(b"abd").is_none());
+
+    let info = database.lookup(b"abc").unwrap();
+    assert_eq!(info.title, Some("Test Program".to_owned()));
+    assert_eq!(info.platform, Some("superchip".to_owned()));
+    let platform = info.platform.as_ref().and_then(|id| platform_from_id(id));
+    assert_eq!(platform, Some(Platform::SuperChip));
+    let quirks = info.quirks.unwrap();
+    assert_eq!(quirks.memory_increment, MemoryIncrement::ByX);
+    assert_eq!(quirks.jump, platform_quirks("superchip").unwrap().jump);
+    assert_eq!(info.tickrate, Some(30));
+    assert_eq!(
+        info.keys,
+        vec![
+            ("up".to_owned(), 5),
+            ("down".to_owned(), 8),
+            ("a".to_owned(), 6)
+        ]
+    );
+    let palette = info.palette.clone().unwrap();
+    assert_eq!(palette.colors[0], Rgb::new(0x10, 0x10, 0x10));
+    assert_eq!(palette.colors[1], Rgb::new(0xF0, 0xF0, 0xF0));
+
+    // local overrides win, field by field
+    assert!(database.add_json(OVERRIDES).is_ok());
+    assert_eq!(database.len(), 1);
+    let info = database.lookup(b"abc").unwrap();
+    assert_eq!(info.tickrate, Some(12));
+    assert_eq!(info.title, Some("Test Program".to_owned()));
+}
+
+#[test]
+fn database_platforms() {
+    for id in ["chip48", "superchip1", "superchip"].iter() {
+        assert_eq!(platform_from_id(id), Some(Platform::SuperChip), "{}", id);
+    }
+    assert_eq!(platform_from_id("originalChip8"), Some(Platform::Chip8));
+    assert_eq!(platform_from_id("hybridVIP"), Some(Platform::Chip8));
+    assert_eq!(platform_from_id("modernChip8"), Some(Platform::Modern));
+    assert_eq!(platform_from_id("xochip"), Some(Platform::XoChip));
+    assert_eq!(platform_from_id("megachip8"), Some(Platform::MegaChip));
+    assert_eq!(platform_from_id("schip"), None);
+}
@@ -0,0 +1,288 @@
+//! The strict mode of the CHIP 8 virtual machine, reporting the bugs of the
+//! programs which the interpreters mask or crash on : uninitialised memory,
+//! writes over the interpreter, data executed, deep stacks, I out of the
+//! memory, clipped sprites and collision flags lost.
+//! Each finding is reported once per program counter, with the cycle it was
+//! first made at.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::platform::MemoryMap;
+use crate::quirks::Quirks;
+use crate::vm::FLAG;
+
+/// The options of the strict mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrictMode {
+    /// The stack depth above which the calls are reported : 12 levels by
+    /// default, as on the COSMAC VIP.
+    pub stack_threshold: usize,
+    /// Should the memory be filled with random bytes instead of zeroes, as on
+    /// the real hardware, to expose the reads of uninitialised memory ?
+    pub random_ram: bool,
+}
+
+impl Default for StrictMode {
+    fn default() -> StrictMode {
+        StrictMode {
+            stack_threshold: 12,
+            random_ram: false,
+        }
+    }
+}
+
+/// A bug found in the program.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Issue {
+    /// The byte at the address was read before being written.
+    UninitializedRead(usize),
+    /// The byte at the address, reserved to the interpreter (e.g. the font),
+    /// was overwritten.
+    ReservedWrite(usize),
+    /// The instruction at the address was executed from data : bytes read or
+    /// written by the instructions, or never written.
+    DataExecuted(usize),
+    /// A call nested the stack to the given depth, above the threshold.
+    StackDepth(usize),
+    /// I points to the given address, past the end of the memory.
+    IndexOutOfMemory(usize),
+    /// A sprite drawn at the given position is clipped by the screen edges.
+    ClippedSprite(usize, usize),
+    /// The collision flag set by DXYN was overwritten right after by the
+    /// given opcode, without being read.
+    FlagClobbered(u16),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Issue::UninitializedRead(address) => {
+                write!(f, "read of the uninitialised byte at {:0>3X}", address)
+            }
+            Issue::ReservedWrite(address) => {
+                write!(f, "write to the interpreter area at {:0>3X}", address)
+            }
+            Issue::DataExecuted(address) => write!(f, "execution of data at {:0>3X}", address),
+            Issue::StackDepth(depth) => write!(f, "stack depth of {}", depth),
+            Issue::IndexOutOfMemory(address) => {
+                write!(f, "I = {:0>3X} past the end of the memory", address)
+            }
+            Issue::ClippedSprite(x, y) => write!(f, "sprite clipped at ({}, {})", x, y),
+            Issue::FlagClobbered(opcode) => write!(
+                f,
+                "collision flag overwritten by {:0>4X} right after DXYN",
+                opcode
+            ),
+        }
+    }
+}
+
+/// A finding of the strict mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// The program counter of the instruction at fault.
+    pub pc: usize,
+    /// The number of instructions executed before, since the strict mode
+    /// was enabled.
+    pub cycle: u64,
+    pub issue: Issue,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:0>3X} @ {}] {}", self.pc, self.cycle, self.issue)
+    }
+}
+
+/// What a byte of the memory was last used for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Usage {
+    /// Never written.
+    Uninitialized,
+    /// Loaded with the program, not yet used as data.
+    Loaded,
+    /// Read or written by the instructions, or a font glyph.
+    Data,
+}
+
+/// The state of the strict mode : the usage of the memory and the report.
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    mode: StrictMode,
+    usage: Vec<Usage>,
+    /// The areas reserved to the interpreter, from the memory map.
+    reserved: Vec<(usize, usize)>,
+    /// The program counter and cycle of the current instruction.
+    pc: usize,
+    cycle: u64,
+    /// Was the previous instruction DXYN, setting the collision flag ?
+    flag_pending: bool,
+    report: Vec<Diagnostic>,
+    reported: HashSet<(usize, Issue)>,
+}
+
+impl Diagnostics {
+    /// Create the strict mode state for the given memory layout, with the
+    /// whole memory uninitialised.
+    pub fn new(mode: StrictMode, memory_map: &MemoryMap) -> Diagnostics {
+        Diagnostics {
+            mode,
+            usage: vec![Usage::Uninitialized; memory_map.memory_size],
+            reserved: memory_map.reserved.clone(),
+            pc: 0,
+            cycle: 0,
+            flag_pending: false,
+            report: Vec::new(),
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Get the options of the strict mode.
+    pub fn mode(&self) -> StrictMode {
+        self.mode
+    }
+
+    /// Get the findings, in the order they were made.
+    pub fn report(&self) -> &[Diagnostic] {
+        &self.report
+    }
+
+    /// Report the given issue for the current instruction, unless already
+    /// reported.
+    fn add(&mut self, issue: Issue) {
+        if self.reported.insert((self.pc, issue)) {
+            self.report.push(Diagnostic {
+                pc: self.pc,
+                cycle: self.cycle,
+                issue,
+            });
+        }
+    }
+
+    /// Mark the given range of the memory as loaded with the program.
+    pub fn loaded(&mut self, start: usize, end: usize) {
+        let end = end.min(self.usage.len());
+        for usage in &mut self.usage[start.min(end)..end] {
+            *usage = Usage::Loaded;
+        }
+    }
+
+    /// Mark the given range of the memory as data, e.g. the font.
+    pub fn data(&mut self, start: usize, end: usize) {
+        let end = end.min(self.usage.len());
+        for usage in &mut self.usage[start.min(end)..end] {
+            *usage = Usage::Data;
+        }
+    }
+
+    /// Check the instruction about to be executed at the given address.
+    pub fn before_instruction(&mut self, pc: usize, opcode: u16, quirks: &Quirks) {
+        self.pc = pc;
+        self.cycle += 1;
+        let usage = &self.usage[pc.min(self.usage.len())..(pc + 2).min(self.usage.len())];
+        if usage.iter().any(|&usage| usage != Usage::Loaded) {
+            self.add(Issue::DataExecuted(pc));
+        }
+
+        let (reads, writes) = flag_access(opcode, quirks);
+        if self.flag_pending && writes && !reads {
+            self.add(Issue::FlagClobbered(opcode));
+        }
+        self.flag_pending = opcode & 0xF000 == 0xD000;
+    }
+
+    /// Check I after the execution of the instruction.
+    pub fn after_instruction(&mut self, i: usize) {
+        if i >= self.usage.len() {
+            self.add(Issue::IndexOutOfMemory(i));
+        }
+    }
+
+    /// Check a read of the memory by an instruction.
+    /// Return false if the address is outside the memory.
+    pub fn read(&mut self, address: usize) -> bool {
+        match self.usage.get(address) {
+            Some(Usage::Uninitialized) => self.add(Issue::UninitializedRead(address)),
+            Some(_) => self.usage[address] = Usage::Data,
+            None => {
+                self.add(Issue::IndexOutOfMemory(address));
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Check a write of the memory by an instruction.
+    /// Return false if the address is outside the memory.
+    pub fn write(&mut self, address: usize) -> bool {
+        if address >= self.usage.len() {
+            self.add(Issue::IndexOutOfMemory(address));
+            return false;
+        }
+        self.usage[address] = Usage::Data;
+        if self
+            .reserved
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&address))
+        {
+            self.add(Issue::ReservedWrite(address));
+        }
+        true
+    }
+
+    /// Check a call nesting the stack to the given depth.
+    pub fn call(&mut self, depth: usize) {
+        if depth > self.mode.stack_threshold {
+            self.add(Issue::StackDepth(depth));
+        }
+    }
+
+    /// Check a sprite drawn at the given position, with the screen of the
+    /// given size clipping the sprites.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], size: (usize, usize)) {
+        let (width, height) = size;
+        let (xpos, ypos) = (x % width, y % height);
+        // the bits of the pixels past the right edge
+        let right = if xpos + 8 > width {
+            0xFFu8 >> (width - xpos)
+        } else {
+            0
+        };
+        let clipped = sprite
+            .iter()
+            .enumerate()
+            .any(|(j, &row)| row != 0 && (ypos + j >= height || row & right != 0));
+        if clipped {
+            self.add(Issue::ClippedSprite(x, y));
+        }
+    }
+}
+
+/// Return whether the given opcode reads and writes the flag register VF, as
+/// (reads, writes). The instructions both reading and writing it, like
+/// 7F01, read it first. DXYN is not counted as a write, the programs
+/// ignoring the collisions drawing in a row.
+fn flag_access(opcode: u16, quirks: &Quirks) -> (bool, bool) {
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+    let (vx, vy) = (x == FLAG, y == FLAG);
+    match (opcode >> 12, opcode & 0x000F) {
+        (0x3, _) | (0x4, _) | (0xE, _) => (vx, false),
+        (0x5, _) | (0x9, _) => (vx || vy, false),
+        (0x6, _) | (0xC, _) => (false, vx),
+        (0x7, _) => (vx, vx),
+        (0x8, 0x0) => (vy, vx),
+        (0x8, 0x1..=0x3) => (vx || vy, vx || quirks.logic),
+        (0x8, _) => (vx || vy, true),
+        (0xB, _) => (quirks.jump && vx, false),
+        (0xD, _) => (vx || vy, false),
+        (0xF, _) => match opcode & 0x00FF {
+            0x07 | 0x0A => (false, vx),
+            0x55 => (vx, false),
+            0x65 => (false, vx),
+            _ => (vx, false),
+        },
+        _ => (false, false),
+    }
+}
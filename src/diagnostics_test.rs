@@ -0,0 +1,101 @@
+use super::diagnostics::{Issue, StrictMode};
+use super::machine::Machine;
+use super::platform::Platform;
+use super::quirks::Quirks;
+use super::vm::Chip8;
+
+/// Run the given ROM in the strict mode for the given number of cycles and
+/// return the findings, as (pc, cycle, issue).
+fn run(rom: &[u8], cycles: usize, mode: StrictMode) -> Vec<(usize, u64, Issue)> {
+    let mut vm = Chip8::new();
+    vm.set_quirks(Quirks {
+        wrap: false,
+        ..Quirks::modern()
+    });
+    vm.set_strict_mode(Some(mode));
+    assert!(vm.load_bytes(rom).is_ok());
+    for _ in 0..cycles {
+        vm.emulate_cycle();
+    }
+    Machine::diagnostics(&vm)
+        .iter()
+        .map(|d| (d.pc, d.cycle, d.issue))
+        .collect()
+}
+
+#[test]
+fn strict_memory() {
+    // read 0x300, write 0x010, read the ROM as data then execute it
+    let rom = [
+        0xA3, 0x00, 0xF0, 0x65, 0xA0, 0x10, 0xF0, 0x55, 0xA2, 0x00, 0xF0, 0x65, 0x12, 0x00,
+    ];
+    assert_eq!(
+        run(&rom, 8, StrictMode::default()),
+        vec![
+            (0x202, 2, Issue::UninitializedRead(0x300)),
+            (0x206, 4, Issue::ReservedWrite(0x010)),
+            (0x200, 8, Issue::DataExecuted(0x200)),
+        ]
+    );
+
+    // past the end of the memory, the reads do not crash
+    let rom = [0xAF, 0xFF, 0xF1, 0x65];
+    assert_eq!(
+        run(&rom, 2, StrictMode::default()),
+        vec![
+            (0x202, 2, Issue::UninitializedRead(0xFFF)),
+            (0x202, 2, Issue::IndexOutOfMemory(0x1000)),
+            (0x202, 2, Issue::IndexOutOfMemory(0x1001)),
+        ]
+    );
+
+    // the RAM outside the font and the program is random
+    let mut vm = Chip8::new();
+    vm.set_strict_mode(Some(StrictMode {
+        random_ram: true,
+        ..StrictMode::default()
+    }));
+    assert!(vm.load_bytes(&[0x12, 0x00]).is_ok());
+    assert_eq!(&vm.memory()[0x200..0x202], &[0x12, 0x00]);
+    assert_eq!(vm.memory()[0x000], 0xF0);
+    assert!(vm.memory()[0x300..0x400].iter().any(|&byte| byte != 0));
+
+    // the stack kept in the interpreter area of the VIP can be read back
+    let mut vm = Chip8::new();
+    assert!(vm.set_platform(Platform::Chip8).is_ok());
+    vm.set_stack_in_memory(true);
+    vm.set_strict_mode(Some(StrictMode::default()));
+    let rom = [0x22, 0x04, 0x00, 0x00, 0xAE, 0xCE, 0xF1, 0x65, 0x00, 0xEE];
+    assert!(vm.load_bytes(&rom).is_ok());
+    for _ in 0..4 {
+        vm.emulate_cycle();
+    }
+    assert_eq!(
+        (vm.pc(), vm.register(0), vm.register(1)),
+        (0x202, 0x02, 0x00)
+    );
+    assert!(Machine::diagnostics(&vm).is_empty());
+}
+
+#[test]
+fn strict_execution() {
+    // a clipped sprite, VF overwritten then read after the next one, and
+    // 3 nested calls
+    let rom = [
+        0x60, 0x3C, 0xA0, 0x50, 0xD0, 0x05, 0x6F, 0x00, 0x3F, 0x01, 0xD0, 0x05, 0x3F, 0x00, 0x22,
+        0x10, 0x22, 0x12, 0x22, 0x14,
+    ];
+    let mode = StrictMode {
+        stack_threshold: 2,
+        ..StrictMode::default()
+    };
+    assert_eq!(
+        run(&rom, 10, mode),
+        vec![
+            (0x204, 3, Issue::ClippedSprite(60, 60)),
+            (0x206, 4, Issue::FlagClobbered(0x6F00)),
+            (0x20A, 6, Issue::ClippedSprite(60, 60)),
+            (0x212, 10, Issue::StackDepth(3)),
+        ]
+    );
+}
@@ -1,13 +1,21 @@
 /// The display crate handles the display component of the CHIP 8 virtual
 /// machine.
+use crate::megachip::Framebuffer;
+use crate::palette::{Palette, Rgb};
 
 /// The CHIP 8 display width, in pixels.
 pub const DISPLAY_WIDTH: usize = 64;
 /// The CHIP 8 display height, in pixels.
 pub const DISPLAY_HEIGHT: usize = 32;
+/// The width of the high resolution mode of SUPER-CHIP, in pixels.
+pub const HIRES_WIDTH: usize = 128;
+/// The height of the high resolution mode of SUPER-CHIP, in pixels.
+pub const HIRES_HEIGHT: usize = 64;
 
 /// The graphics component of a Chip 8 virtual machine.
-/// The Chip 8 uses a 64x32 monochrome display with the format :
+/// The Chip 8 uses a 64x32 monochrome display by default (64x64 with the
+/// two-page hi-res interpreter, 128x64 in the high resolution mode of
+/// SUPER-CHIP) with the format :
 /// O-----------------> X
 /// |(0,0)      (63,0)|
 /// |                 |
@@ -16,29 +24,95 @@ pub const DISPLAY_HEIGHT: usize = 32;
 /// Y
 #[derive(Clone)]
 pub struct Display {
-    /// 64x32 black and white screen.
-    /// 'gfx[i]' contains the pixel column number 'i'.
+    /// Black and white screen, 64x32 by default.
+    /// 'gfx[i]' contains the pixel row number 'i'.
     /// For a single pixel, '1' means white and '0' black.
     /// Using bytes instead of booleans will make drawing instructions easier
     /// to implement for the same memory cost.
-    pub gfx: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    pub gfx: Vec<Vec<u8>>,
     /// Has the display been modified since the last time it was drawn ?
     /// Should be set to false by the emulator application after every draw.
     pub dirty: bool,
+    /// The colour attributes of the CHIP-8X, if enabled. They replace the
+    /// palette.
+    pub colors: Option<ColorLayer>,
+    /// The true colour frame of the MEGA-CHIP mode, if enabled, with the
+    /// same size as 'gfx'. It replaces the palette, 'gfx' then holding the
+    /// palette indices of the sprites.
+    pub framebuffer: Option<Framebuffer>,
 }
 
 impl Display {
-    /// Create and return a new Display instance.
+    /// Create and return a new Display instance, of the default size.
     pub fn new() -> Display {
+        Display::with_size(DISPLAY_WIDTH, DISPLAY_HEIGHT)
+    }
+
+    /// Create and return a new Display instance of the given size, in pixels.
+    pub fn with_size(width: usize, height: usize) -> Display {
         Display {
-            gfx: [[0u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            gfx: vec![vec![0u8; width]; height],
             dirty: true,
+            colors: None,
+            framebuffer: None,
+        }
+    }
+
+    /// Enable the colour attributes of the CHIP-8X, in their initial state.
+    pub fn enable_colors(&mut self) {
+        self.colors = Some(ColorLayer::new(self.width(), self.height()));
+        self.dirty = true;
+    }
+
+    /// Are the pixels drawn with the palette, i.e. neither the CHIP-8X
+    /// colours nor the MEGA-CHIP mode are enabled ?
+    pub fn uses_palette(&self) -> bool {
+        self.colors.is_none() && self.framebuffer.is_none()
+    }
+
+    /// Return the background colour : black in the MEGA-CHIP mode, from the
+    /// colour attributes if enabled, from the given palette otherwise.
+    pub fn background_color(&self, palette: &Palette) -> Rgb {
+        match (&self.framebuffer, &self.colors) {
+            (Some(_), _) => Rgb::new(0, 0, 0),
+            (None, Some(colors)) => colors.color(0, 0, false),
+            (None, None) => palette.color(0),
+        }
+    }
+
+    /// Return the colour of the pixel at the given position : from the
+    /// MEGA-CHIP frame or the colour attributes if enabled, from the given
+    /// palette otherwise.
+    pub fn color(&self, x: usize, y: usize, palette: &Palette) -> Rgb {
+        match (&self.framebuffer, &self.colors) {
+            (Some(framebuffer), _) => framebuffer.pixel(x, y),
+            (None, Some(colors)) => colors.color(x, y, self.gfx[y][x] != 0),
+            (None, None) => palette.color(self.gfx[y][x]),
         }
     }
 
+    /// Get the width of the display, in pixels.
+    pub fn width(&self) -> usize {
+        self.gfx.first().map_or(0, |row| row.len())
+    }
+
+    /// Get the height of the display, in pixels.
+    pub fn height(&self) -> usize {
+        self.gfx.len()
+    }
+
+    /// Get the size of the display as (width, height), in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+
     /// Clear the screen (set it to uniform black).
     pub fn clear(&mut self) {
-        self.gfx = [[0u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        for row in self.gfx.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = 0;
+            }
+        }
         self.dirty = true;
     }
 
@@ -47,19 +121,51 @@ impl Display {
     /// Return true if there was a collision (i.e. if any of the written pixels
     /// changed from 1 to 0).
     pub fn draw(&mut self, xpos: usize, ypos: usize, sprite: &[u8]) -> bool {
+        self.draw_with(xpos, ypos, sprite, true)
+    }
+
+    /// Same as 'draw', but if 'wrap' is false the parts of the sprite going
+    /// past the screen edges are clipped instead of wrapped around.
+    /// The starting position always wraps around.
+    pub fn draw_with(&mut self, xpos: usize, ypos: usize, sprite: &[u8], wrap: bool) -> bool {
+        self.draw_sprite(xpos, ypos, sprite, 8, wrap)
+    }
+
+    /// Same as 'draw_with', for a sprite of 16x16 pixels (SUPER-CHIP) whose
+    /// rows are 2 bytes long.
+    pub fn draw_wide_with(&mut self, xpos: usize, ypos: usize, sprite: &[u8], wrap: bool) -> bool {
+        self.draw_sprite(xpos, ypos, sprite, 16, wrap)
+    }
+
+    /// Draw the given sprite of the given width, a multiple of 8 pixels, see
+    /// 'draw_with'.
+    fn draw_sprite(
+        &mut self,
+        xpos: usize,
+        ypos: usize,
+        sprite: &[u8],
+        sprite_width: usize,
+        wrap: bool,
+    ) -> bool {
         let mut collision = false;
-        let h = sprite.len();
+        let row_bytes = sprite_width / 8;
+        let h = sprite.len() / row_bytes;
+        let (width, height) = self.size();
+        let (xpos, ypos) = (xpos % width, ypos % height);
 
         for j in 0..h {
-            for i in 0..8 {
+            for i in 0..sprite_width {
+                if !wrap && (xpos + i >= width || ypos + j >= height) {
+                    continue;
+                }
                 // screen wrap if necessary
-                let y = (ypos + j) % DISPLAY_HEIGHT;
-                let x = (xpos + i) % DISPLAY_WIDTH;
+                let y = (ypos + j) % height;
+                let x = (xpos + i) % width;
 
                 // draw each sprite pixel with a XOR operation
                 // i.e. toggle the pixel
                 // 0x80 = 1000 0000 : allows to check each pixel in the sprite
-                if (sprite[j] & (0x80 >> i)) != 0x00 {
+                if (sprite[j * row_bytes + i / 8] & (0x80 >> (i % 8))) != 0x00 {
                     if self.gfx[y][x] == 0x01 {
                         collision = true;
                     }
@@ -71,6 +177,127 @@ impl Display {
 
         collision
     }
+
+    /// Scroll the pixels down by the given number of rows, the rows scrolled
+    /// in at the top being blank.
+    pub fn scroll_down(&mut self, rows: usize) {
+        let rows = rows.min(self.height());
+        self.gfx.rotate_right(rows);
+        for row in self.gfx[..rows].iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = 0;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Scroll the pixels right by the given number of columns, the columns
+    /// scrolled in on the left being blank.
+    pub fn scroll_right(&mut self, columns: usize) {
+        let columns = columns.min(self.width());
+        for row in self.gfx.iter_mut() {
+            row.rotate_right(columns);
+            for pixel in row[..columns].iter_mut() {
+                *pixel = 0;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Scroll the pixels left by the given number of columns, the columns
+    /// scrolled in on the right being blank.
+    pub fn scroll_left(&mut self, columns: usize) {
+        let columns = columns.min(self.width());
+        for row in self.gfx.iter_mut() {
+            row.rotate_left(columns);
+            let width = row.len();
+            for pixel in row[width - columns..].iter_mut() {
+                *pixel = 0;
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+/// The width in pixels of a CHIP-8X colour zone.
+pub const COLOR_ZONE_WIDTH: usize = 8;
+/// The height in pixels of the CHIP-8X colour zones set by BXY0.
+pub const COLOR_ZONE_HEIGHT: usize = 4;
+
+/// The foreground colours of the CHIP-8X (VP-590 colour board) : black, red,
+/// blue, violet, green, yellow, aqua and white.
+pub static CHIP8X_COLORS: [Rgb; 8] = [
+    Rgb::new(0x00, 0x00, 0x00),
+    Rgb::new(0xFF, 0x00, 0x00),
+    Rgb::new(0x00, 0x00, 0xFF),
+    Rgb::new(0xFF, 0x00, 0xFF),
+    Rgb::new(0x00, 0xFF, 0x00),
+    Rgb::new(0xFF, 0xFF, 0x00),
+    Rgb::new(0x00, 0xFF, 0xFF),
+    Rgb::new(0xFF, 0xFF, 0xFF),
+];
+
+/// The background colours of the CHIP-8X, in the order cycled through by
+/// 02A0 : dark blue, black, dark green and dark red.
+pub static CHIP8X_BACKGROUNDS: [Rgb; 4] = [
+    Rgb::new(0x00, 0x00, 0x80),
+    Rgb::new(0x00, 0x00, 0x00),
+    Rgb::new(0x00, 0x80, 0x00),
+    Rgb::new(0x80, 0x00, 0x00),
+];
+
+/// The colour attribute layer of the CHIP-8X, on top of the monochrome
+/// pixels : a background colour for the whole screen, and a foreground
+/// colour for each zone of 8x1 pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorLayer {
+    /// The background colour, as an index in 'CHIP8X_BACKGROUNDS'.
+    pub background: u8,
+    /// The foreground colours, as indices in 'CHIP8X_COLORS'.
+    /// 'zones[y][x / COLOR_ZONE_WIDTH]' is the colour of the pixel (x, y).
+    pub zones: Vec<Vec<u8>>,
+}
+
+impl ColorLayer {
+    /// Create the colour layer of a display of the given size, in its state
+    /// at power on : red on dark blue.
+    pub fn new(width: usize, height: usize) -> ColorLayer {
+        let columns = width.div_ceil(COLOR_ZONE_WIDTH);
+        ColorLayer {
+            background: 0,
+            zones: vec![vec![1; columns]; height],
+        }
+    }
+
+    /// Switch to the next background colour.
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % CHIP8X_BACKGROUNDS.len() as u8;
+    }
+
+    /// Set the foreground colour of the zones in the given columns (of
+    /// 'COLOR_ZONE_WIDTH' pixels) and pixel rows, wrapping around the
+    /// display.
+    pub fn fill(&mut self, columns: (usize, usize), rows: (usize, usize), color: u8) {
+        let height = self.zones.len();
+        let width = self.zones.first().map_or(0, |row| row.len());
+        if width == 0 || height == 0 {
+            return;
+        }
+        for y in rows.0..rows.0 + rows.1 {
+            for x in columns.0..columns.0 + columns.1 {
+                self.zones[y % height][x % width] = color % CHIP8X_COLORS.len() as u8;
+            }
+        }
+    }
+
+    /// Return the colour of the pixel at the given position, lit or not.
+    pub fn color(&self, x: usize, y: usize, on: bool) -> Rgb {
+        if on {
+            CHIP8X_COLORS[self.zones[y][x / COLOR_ZONE_WIDTH] as usize]
+        } else {
+            CHIP8X_BACKGROUNDS[self.background as usize]
+        }
+    }
 }
 
 /// Chip8 font set.
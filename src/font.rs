@@ -0,0 +1,237 @@
+/// The hexadecimal fonts of the interpreters, drawn by the programs with the
+/// addresses given by FX29 (small font, 4x5 pixels) and FX30 (big font, 8x10
+/// pixels).
+use std::fs;
+use std::path::Path;
+
+use crate::display::FONT_SET;
+
+/// The size in bytes of a glyph of the small font.
+pub const SMALL_GLYPH_SIZE: usize = 5;
+/// The size in bytes of a glyph of the big font.
+pub const BIG_GLYPH_SIZE: usize = 10;
+/// The size in bytes of the small font : 16 glyphs.
+pub const SMALL_FONT_SIZE: usize = 16 * SMALL_GLYPH_SIZE;
+
+/// A font set : the small glyphs of the 16 hexadecimal digits, and the
+/// optional big glyphs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Font {
+    /// The small glyphs, 5 bytes each.
+    pub small: Vec<u8>,
+    /// The big glyphs, 10 bytes each : none, the 10 decimal digits (as
+    /// SUPER-CHIP) or the 16 hexadecimal digits.
+    pub big: Vec<u8>,
+}
+
+impl Font {
+    /// The font of most modern interpreters, with the big font of Octo
+    /// (XO-CHIP).
+    pub fn modern() -> Font {
+        Font {
+            small: FONT_SET.to_vec(),
+            big: XOCHIP_BIG.to_vec(),
+        }
+    }
+
+    /// The font of the COSMAC VIP, without big font.
+    pub fn vip() -> Font {
+        Font {
+            small: VIP_SMALL.to_vec(),
+            big: Vec::new(),
+        }
+    }
+
+    /// The font of the DREAM 6800, without big font.
+    pub fn dream6800() -> Font {
+        Font {
+            small: DREAM6800_SMALL.to_vec(),
+            big: Vec::new(),
+        }
+    }
+
+    /// The font of the ETI-660, without big font.
+    pub fn eti660() -> Font {
+        Font {
+            small: ETI660_SMALL.to_vec(),
+            big: Vec::new(),
+        }
+    }
+
+    /// The "Fish 'N' Chips" font, with the big font of Octo.
+    pub fn fish_n_chips() -> Font {
+        Font {
+            small: FISH_SMALL.to_vec(),
+            big: XOCHIP_BIG.to_vec(),
+        }
+    }
+
+    /// The fonts of SUPER-CHIP 1.1.
+    pub fn schip() -> Font {
+        Font {
+            small: FONT_SET.to_vec(),
+            big: SCHIP_BIG.to_vec(),
+        }
+    }
+
+    /// Return the font with the given name ("modern", "vip", "dream6800",
+    /// "eti660", "fish", "schip" or "xochip"), if any.
+    pub fn from_name(name: &str) -> Option<Font> {
+        match &name.to_lowercase()[..] {
+            "modern" | "xochip" | "octo" => Some(Font::modern()),
+            "vip" | "chip8" => Some(Font::vip()),
+            "dream6800" | "dream" => Some(Font::dream6800()),
+            "eti660" | "eti-660" => Some(Font::eti660()),
+            "fish" | "fishnchips" => Some(Font::fish_n_chips()),
+            "schip" | "superchip" => Some(Font::schip()),
+            _ => None,
+        }
+    }
+
+    /// Read a custom font : the 80 bytes of the small glyphs, optionally
+    /// followed by the 100 bytes of the 10 big decimal digits or the 160
+    /// bytes of the 16 big hexadecimal digits.
+    /// If the operation fails, return a String explaining why.
+    pub fn from_bytes(data: &[u8]) -> Result<Font, String> {
+        match data.len().checked_sub(SMALL_FONT_SIZE) {
+            Some(0) | Some(100) | Some(160) => Ok(Font {
+                small: data[..SMALL_FONT_SIZE].to_vec(),
+                big: data[SMALL_FONT_SIZE..].to_vec(),
+            }),
+            _ => Err(format!(
+                "invalid font size ({} bytes) : expected 80 bytes of small \
+                 glyphs, optionally followed by 100 or 160 bytes of big glyphs",
+                data.len()
+            )),
+        }
+    }
+
+    /// Read a custom font file, see 'Font::from_bytes'.
+    /// If the operation fails, return a String explaining why.
+    pub fn from_file(filepath: &Path) -> Result<Font, String> {
+        let data = fs::read(filepath)
+            .map_err(|why| format!("cannot read \"{}\" : {}", filepath.display(), why))?;
+        Font::from_bytes(&data)
+    }
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        Font::modern()
+    }
+}
+
+/// The font of the COSMAC VIP interpreter.
+static VIP_SMALL: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The font of CHIPOS, on the DREAM 6800 : 3 pixels wide.
+static DREAM6800_SMALL: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The font of the ETI-660 interpreter : 3 pixels wide.
+static ETI660_SMALL: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The "Fish 'N' Chips" font, with rounder glyphs.
+static FISH_SMALL: [u8; 80] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The big font of SUPER-CHIP 1.1 : 8x10 pixels, digits only.
+static SCHIP_BIG: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// The big font of Octo, for XO-CHIP : 8x10 pixels, all the hexadecimal
+/// digits.
+static XOCHIP_BIG: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
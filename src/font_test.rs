@@ -0,0 +1,65 @@
+use super::font::{Font, BIG_GLYPH_SIZE, SMALL_GLYPH_SIZE};
+use super::platform::{MemoryMap, Platform};
+use super::vm::Chip8;
+
+#[test]
+fn fonts() {
+    for name in &["modern", "vip", "dream6800", "eti660", "fish", "schip"] {
+        let font = Font::from_name(name).unwrap();
+        assert_eq!(font.small.len(), 16 * SMALL_GLYPH_SIZE);
+        assert!([0, 10, 16].contains(&(font.big.len() / BIG_GLYPH_SIZE)));
+    }
+    assert!(Font::from_name("comic").is_none());
+
+    assert!(Font::from_bytes(&[0xFF; 80]).is_ok());
+    assert_eq!(Font::from_bytes(&[0xFF; 180]).unwrap().big.len(), 100);
+    assert_eq!(Font::from_bytes(&[0xFF; 240]).unwrap().big.len(), 160);
+    assert!(Font::from_bytes(&[0xFF; 79]).is_err());
+    assert!(Font::from_bytes(&[0xFF; 200]).is_err());
+}
+
+#[test]
+fn font_addresses() {
+    // default layout : the big font follows the small one
+    let mut vm = Chip8::new();
+    vm.set_register(0, 0x3);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), 3 * SMALL_GLYPH_SIZE);
+    vm.execute_opcode(0xF030);
+    assert_eq!(vm.index(), 0x050 + 3 * BIG_GLYPH_SIZE);
+
+    // modern layout : the font moves to 0x050
+    assert!(vm.set_memory_map(MemoryMap::modern()).is_ok());
+    vm.set_register(0, 0x3);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), 0x050 + 3 * SMALL_GLYPH_SIZE);
+    vm.execute_opcode(0xF030);
+    assert_eq!(vm.index(), 0x0A0 + 3 * BIG_GLYPH_SIZE);
+    let big = &Font::modern().big[3 * BIG_GLYPH_SIZE..4 * BIG_GLYPH_SIZE];
+    assert_eq!(&vm.memory()[vm.index()..vm.index() + BIG_GLYPH_SIZE], big);
+
+    // the platform font is stored at the addresses of its memory map
+    let mut vm = Chip8::new();
+    assert!(vm.set_memory_map(MemoryMap::vip()).is_ok());
+    assert!(vm.set_font(Platform::Chip8.font()).is_ok());
+    assert_eq!(&vm.memory()[..80], &Font::vip().small[..]);
+    vm.set_register(0, 0x1);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), SMALL_GLYPH_SIZE);
+    assert_eq!(&vm.memory()[5..10], &[0x60, 0x20, 0x20, 0x20, 0x70]);
+
+    // a custom font survives a reset, and must fit in memory
+    let custom = Font::from_bytes(&[0xAA; 180]).unwrap();
+    assert!(vm.set_font(custom.clone()).is_ok());
+    vm.reset();
+    assert_eq!(vm.memory()[99], 0xAA);
+    assert_eq!(vm.font(), &custom);
+    let tiny = MemoryMap {
+        memory_size: 0x100,
+        big_font_address: 0xC0,
+        entry_point: 0x80,
+        reserved: Vec::new(),
+        ..MemoryMap::vip()
+    };
+    assert!(vm.set_memory_map(tiny).is_err());
+}
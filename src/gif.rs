@@ -0,0 +1,361 @@
+/// Minimal GIF89a support, used to record animations of the display and to
+/// read Octo cartridges without depending on an image processing crate.
+/// Reference : https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::palette::Palette;
+
+/// The minimum LZW code size : 2 bits are enough for the 4 palette entries.
+const MIN_CODE_SIZE: u8 = 2;
+/// The maximum LZW code size allowed by the specification.
+const MAX_CODE_SIZE: u8 = 12;
+
+/// Animated GIF encoder writing frames of palette indices.
+pub struct GifEncoder<W: Write> {
+    writer: W,
+    width: u16,
+    height: u16,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Write the GIF header, global palette and looping extension for an
+    /// animation of the given dimensions, and return the encoder.
+    pub fn new(mut writer: W, width: u16, height: u16, palette: &Palette) -> io::Result<Self> {
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        // global colour table of 2^(1+1) entries, 8 bits per primary colour
+        writer.write_all(&[0xF1, 0x00, 0x00])?;
+        for color in palette.colors.iter() {
+            writer.write_all(&[color.r, color.g, color.b])?;
+        }
+        // NETSCAPE2.0 application extension : loop forever
+        writer.write_all(&[0x21, 0xFF, 0x0B])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+        Ok(GifEncoder {
+            writer,
+            width,
+            height,
+        })
+    }
+
+    /// Write a full-size frame of palette indices (one byte per pixel, row by
+    /// row), to be displayed for the given delay in hundredths of a second.
+    pub fn write_frame(&mut self, pixels: &[u8], delay: u16) -> io::Result<()> {
+        debug_assert_eq!(pixels.len(), self.width as usize * self.height as usize);
+        // graphic control extension : no disposal, no transparency
+        self.writer.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        self.writer.write_all(&delay.to_le_bytes())?;
+        self.writer.write_all(&[0x00, 0x00])?;
+        // image descriptor, without local colour table
+        self.writer.write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00])?;
+        self.writer.write_all(&self.width.to_le_bytes())?;
+        self.writer.write_all(&self.height.to_le_bytes())?;
+        self.writer.write_all(&[0x00, MIN_CODE_SIZE])?;
+        let data = lzw_encode(pixels, MIN_CODE_SIZE);
+        for block in data.chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0x00])
+    }
+
+    /// Write the GIF trailer and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// LSB-first variable-width code writer, as required by GIF.
+struct CodeWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += width;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Compress the given palette indices with the GIF flavour of LZW.
+pub fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter {
+        out: Vec::new(),
+        buffer: 0,
+        count: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = min_code_size + 1;
+
+    writer.write(clear, width);
+    let mut prefix: Option<u16> = None;
+    for &pixel in pixels {
+        let pixel = pixel & (clear - 1) as u8;
+        let current = match prefix {
+            None => {
+                prefix = Some(pixel as u16);
+                continue;
+            }
+            Some(current) => current,
+        };
+        if let Some(&code) = table.get(&(current, pixel)) {
+            prefix = Some(code);
+            continue;
+        }
+        writer.write(current, width);
+        if next_code == 1 << MAX_CODE_SIZE {
+            // the table is full : start over
+            writer.write(clear, width);
+            table.clear();
+            next_code = end + 1;
+            width = min_code_size + 1;
+        } else {
+            table.insert((current, pixel), next_code);
+            if next_code == 1 << width {
+                width += 1;
+            }
+            next_code += 1;
+        }
+        prefix = Some(pixel as u16);
+    }
+    if let Some(current) = prefix {
+        writer.write(current, width);
+    }
+    writer.write(end, width);
+    writer.finish()
+}
+
+/// An image of a GIF file : a frame of an animation.
+pub struct GifFrame {
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    /// The palette indices, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+/// A decoded GIF file.
+pub struct GifImage {
+    pub width: u16,
+    pub height: u16,
+    /// The images of the file, in order.
+    pub frames: Vec<GifFrame>,
+}
+
+/// Bounds checked reader over the bytes of a GIF file.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or_else(|| "truncated GIF file".to_owned())?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read a sequence of data sub-blocks, up to the block terminator.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        loop {
+            let size = self.byte()? as usize;
+            if size == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.bytes(size)?);
+        }
+    }
+
+    /// Skip a colour table if the given flags announce one.
+    fn skip_color_table(&mut self, flags: u8) -> Result<(), String> {
+        if flags & 0x80 != 0 {
+            self.bytes(3 << ((flags & 0x07) + 1))?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the images of a GIF file (GIF87a or GIF89a), as palette indices.
+/// If the operation fails, return a String explaining why.
+pub fn decode(data: &[u8]) -> Result<GifImage, String> {
+    let mut reader = ByteReader { data, position: 0 };
+    match reader.bytes(6)? {
+        b"GIF87a" | b"GIF89a" => {}
+        _ => return Err("not a GIF file".to_owned()),
+    }
+    let width = reader.u16()?;
+    let height = reader.u16()?;
+    let flags = reader.byte()?;
+    reader.bytes(2)?; // background colour and aspect ratio
+    reader.skip_color_table(flags)?;
+
+    let mut frames = Vec::new();
+    loop {
+        match reader.byte()? {
+            // extension : ignored
+            0x21 => {
+                reader.byte()?;
+                reader.sub_blocks()?;
+            }
+            // image descriptor
+            0x2C => {
+                let left = reader.u16()?;
+                let top = reader.u16()?;
+                let frame_width = reader.u16()?;
+                let frame_height = reader.u16()?;
+                let flags = reader.byte()?;
+                reader.skip_color_table(flags)?;
+                let min_code_size = reader.byte()?;
+                if !(2..MAX_CODE_SIZE).contains(&min_code_size) {
+                    return Err(format!("invalid LZW code size {}", min_code_size));
+                }
+                let size = frame_width as usize * frame_height as usize;
+                let mut pixels = lzw_decode(&reader.sub_blocks()?, min_code_size, size)?;
+                pixels.resize(size, 0);
+                if flags & 0x40 != 0 {
+                    pixels = deinterlace(&pixels, frame_width as usize);
+                }
+                frames.push(GifFrame {
+                    left,
+                    top,
+                    width: frame_width,
+                    height: frame_height,
+                    pixels,
+                });
+            }
+            // trailer
+            0x3B => break,
+            byte => return Err(format!("unexpected GIF block {:0>2X}", byte)),
+        }
+    }
+    Ok(GifImage {
+        width,
+        height,
+        frames,
+    })
+}
+
+/// Reorder the rows of an interlaced image : the rows are stored in four
+/// passes (every 8th row from 0, every 8th from 4, every 4th from 2 then
+/// every 2nd from 1).
+fn deinterlace(pixels: &[u8], width: usize) -> Vec<u8> {
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    let rows = [(0, 8), (4, 8), (2, 4), (1, 2)]
+        .iter()
+        .flat_map(|&(start, step)| (start..height).step_by(step));
+    let mut out = vec![0; pixels.len()];
+    for (stored, row) in rows.enumerate() {
+        out[row * width..(row + 1) * width]
+            .copy_from_slice(&pixels[stored * width..(stored + 1) * width]);
+    }
+    out
+}
+
+/// Decompress GIF flavoured LZW data to palette indices, stopping after
+/// 'limit' indices.
+/// If the operation fails, return a String explaining why.
+pub fn lzw_decode(data: &[u8], min_code_size: u8, limit: usize) -> Result<Vec<u8>, String> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    // the table entries, as (prefix code, last index) pairs
+    let mut table: Vec<(Option<u16>, u8)> = (0..clear).map(|i| (None, i as u8)).collect();
+    table.push((None, 0));
+    table.push((None, 0));
+    let mut width = min_code_size + 1;
+    let mut previous: Option<u16> = None;
+    let mut out = Vec::new();
+    let mut string = Vec::new();
+
+    let (mut buffer, mut count, mut position) = (0u32, 0u8, 0);
+    while out.len() < limit {
+        while count < width {
+            match data.get(position) {
+                Some(&byte) => buffer |= (byte as u32) << count,
+                None => return Ok(out), // missing end code : keep what was decoded
+            }
+            position += 1;
+            count += 8;
+        }
+        let code = (buffer & ((1 << width) - 1)) as u16;
+        buffer >>= width;
+        count -= width;
+
+        if code == clear {
+            table.truncate(end as usize + 1);
+            width = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+
+        // the string of the code, built backwards
+        let known = (code as usize) < table.len();
+        let start = match (known, previous) {
+            (true, _) => code,
+            (false, Some(previous)) if code as usize == table.len() => previous,
+            _ => return Err(format!("invalid LZW code {}", code)),
+        };
+        string.clear();
+        let mut current = Some(start);
+        while let Some(c) = current {
+            let (prefix, index) = table[c as usize];
+            string.push(index);
+            current = prefix;
+        }
+        string.reverse();
+        let first = string[0];
+        if !known {
+            string.push(first);
+        }
+        if let Some(previous) = previous {
+            if table.len() < 1 << MAX_CODE_SIZE {
+                table.push((Some(previous), first));
+            }
+        }
+        out.extend_from_slice(&string);
+        previous = Some(code);
+        if table.len() == 1 << width && width < MAX_CODE_SIZE {
+            width += 1;
+        }
+    }
+    out.truncate(limit);
+    Ok(out)
+}
@@ -1,21 +1,21 @@
-/// Stores the state of the virtual keypad used for input.
-///
-/// The Chip8 virtual keypad has the following layout :
-///
-/// Virtual Keypad       Keyboard (QWERTY)
-/// +-+-+-+-+                +-+-+-+-+
-/// |1|2|3|C|                |1|2|3|4|
-/// +-+-+-+-+                +-+-+-+-+
-/// |4|5|6|D|                |Q|W|E|R|
-/// +-+-+-+-+       =>       +-+-+-+-+
-/// |7|8|9|E|                |A|S|D|F|
-/// +-+-+-+-+                +-+-+-+-+
-/// |A|0|B|F|                |Z|X|C|V|
-/// +-+-+-+-+                +-+-+-+-+
-///
-/// source :
-/// http://www.multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
-///
+//! Stores the state of the virtual keypad used for input.
+//!
+//! The Chip8 virtual keypad has the following layout :
+//!
+//! Virtual Keypad       Keyboard (QWERTY)
+//! +-+-+-+-+                +-+-+-+-+
+//! |1|2|3|C|                |1|2|3|4|
+//! +-+-+-+-+                +-+-+-+-+
+//! |4|5|6|D|                |Q|W|E|R|
+//! +-+-+-+-+       =>       +-+-+-+-+
+//! |7|8|9|E|                |A|S|D|F|
+//! +-+-+-+-+                +-+-+-+-+
+//! |A|0|B|F|                |Z|X|C|V|
+//! +-+-+-+-+                +-+-+-+-+
+//!
+//! source :
+//! http://www.multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
+//!
 
 /// The possible status for a key of the virtual keypad.
 #[derive(Copy, Clone, Debug)]
@@ -27,6 +27,9 @@ pub enum Keystate {
 pub struct Keypad {
     /// The state of the 16 keys (true = currently pressed).
     keys: [Keystate; 16],
+    /// The keys pressed since the last 'take_pressed', one bit per key, so
+    /// that the short presses between two instructions are not missed.
+    pressed: u16,
 }
 
 impl Keypad {
@@ -34,6 +37,7 @@ impl Keypad {
     pub fn new() -> Keypad {
         Keypad {
             keys: [Keystate::Released; 16],
+            pressed: 0,
         }
     }
 
@@ -48,6 +52,33 @@ impl Keypad {
     pub fn set_key_state(&mut self, index: usize, state: Keystate) {
         //println!("set_key_state({:X}, {:?})", index, state); // DEBUG
         debug_assert!(index < 16);
+        if let (Keystate::Released, Keystate::Pressed) = (self.keys[index], state) {
+            self.pressed |= 1 << index;
+        }
         self.keys[index] = state;
     }
+
+    /// Return the keys pressed since the last 'take_pressed', one bit per
+    /// key.
+    pub fn pressed(&self) -> u16 {
+        self.pressed
+    }
+
+    /// Replace the keys pressed since the last 'take_pressed', one bit per
+    /// key, e.g. to restore a saved state.
+    pub fn set_pressed(&mut self, pressed: u16) {
+        self.pressed = pressed;
+    }
+
+    /// Return the lowest key pressed since the last call, if any, and forget
+    /// the presses.
+    pub fn take_pressed(&mut self) -> Option<usize> {
+        let pressed = self.pressed;
+        self.pressed = 0;
+        if pressed == 0 {
+            None
+        } else {
+            Some(pressed.trailing_zeros() as usize)
+        }
+    }
 }
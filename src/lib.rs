@@ -1,8 +1,67 @@
 #[macro_use]
 extern crate log;
 
+pub mod analyzer;
+pub mod bus;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cosmac;
+pub mod database;
+pub mod diagnostics;
 pub mod display;
+pub mod font;
+pub mod gif;
+pub mod json;
 pub mod keypad;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+pub mod loader;
+pub mod machine;
+pub mod megachip;
+pub mod observer;
+pub mod palette;
+pub mod persistence;
+pub mod platform;
+pub mod png;
+pub mod quirks;
+pub mod record;
+pub mod sha1;
+mod state;
+pub mod symbols;
+pub mod syscall;
 pub mod vm;
+
+#[cfg(test)]
+mod analyzer_test;
+#[cfg(test)]
+mod bus_test;
+#[cfg(test)]
+mod cosmac_test;
+#[cfg(test)]
+mod database_test;
+#[cfg(test)]
+mod diagnostics_test;
+#[cfg(test)]
+mod font_test;
+#[cfg(test)]
+mod loader_test;
+#[cfg(test)]
+mod machine_test;
+#[cfg(test)]
+mod megachip_test;
+#[cfg(test)]
+mod observer_test;
+#[cfg(test)]
+mod palette_test;
+#[cfg(test)]
+mod persistence_test;
+#[cfg(test)]
+mod png_test;
+#[cfg(test)]
+mod record_test;
+#[cfg(test)]
+mod symbols_test;
+#[cfg(test)]
+mod syscall_test;
 #[cfg(test)]
 mod vm_test;
@@ -0,0 +1,641 @@
+/// libretro core, allowing to run the virtual machine inside RetroArch and
+/// the other libretro frontends.
+/// Built with the "libretro" feature, see the README.
+/// Reference : https://github.com/libretro/libretro-common (libretro.h)
+///
+/// The RetroPad is mapped on the virtual keypad like the default game
+/// controller layout of 'chip8ui' : D-pad on 2/4/6/8 and A on 5.
+use std::ffi::{c_void, CStr};
+use std::os::raw::{c_char, c_uint};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::keypad::Keystate;
+use crate::palette::{Palette, Theme};
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::vm::{Chip8, CPU_CLOCK, MEMORY_SIZE, PROGRAM_START, TIMERS_CLOCK};
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_REGION_NTSC: c_uint = 0;
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+/// The audio sample rate, in Hz.
+const SAMPLE_RATE: usize = 44100;
+/// The frequency of the beep, in Hz.
+const BEEP_FREQUENCY: usize = 440;
+/// The amplitude of the beep square wave.
+const BEEP_AMPLITUDE: i16 = 0x1000;
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    geometry: RetroGameGeometry,
+    timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct RetroVariable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+#[repr(C)]
+struct RetroInputDescriptor {
+    port: c_uint,
+    device: c_uint,
+    index: c_uint,
+    id: c_uint,
+    description: *const c_char,
+}
+
+pub type RetroEnvironment = extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh =
+    extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = extern "C" fn();
+pub type RetroInputState =
+    extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+/// The core options, as (key, "description; default|other values").
+static VARIABLES: [(&[u8], &[u8]); 9] = [
+    (
+        b"chip8vm_clock\0",
+        b"CPU clock (Hz); 600|300|400|500|700|800|1000|1200|1500|2000|3000\0",
+    ),
+    (
+        b"chip8vm_palette\0",
+        b"Palette; classic|green|amber|lcd|contrast|colorblind\0",
+    ),
+    (
+        b"chip8vm_quirks\0",
+        b"Quirks preset; modern|chip8|schip|custom\0",
+    ),
+    (
+        b"chip8vm_quirk_shift\0",
+        b"Custom quirks : 8XY6/8XYE shift VX in place; enabled|disabled\0",
+    ),
+    (
+        b"chip8vm_quirk_memory\0",
+        b"Custom quirks : FX55/FX65 increment I by; x+1|x|0\0",
+    ),
+    (
+        b"chip8vm_quirk_jump\0",
+        b"Custom quirks : BNNN jumps to NNN + VX; disabled|enabled\0",
+    ),
+    (
+        b"chip8vm_quirk_logic\0",
+        b"Custom quirks : logical opcodes reset VF; disabled|enabled\0",
+    ),
+    (
+        b"chip8vm_quirk_wrap\0",
+        b"Custom quirks : sprites wrap around the screen; enabled|disabled\0",
+    ),
+    (
+        b"chip8vm_quirk_vblank\0",
+        b"Custom quirks : wait for the next frame to draw; disabled|enabled\0",
+    ),
+];
+
+/// The RetroPad buttons (libretro ids) and the keypad keys they press.
+static RETROPAD_LAYOUT: [(c_uint, usize, &[u8]); 16] = [
+    (4, 0x2, b"Key 2 (up)\0"),
+    (6, 0x4, b"Key 4 (left)\0"),
+    (7, 0x6, b"Key 6 (right)\0"),
+    (5, 0x8, b"Key 8 (down)\0"),
+    (8, 0x5, b"Key 5\0"),
+    (0, 0x0, b"Key 0\0"),
+    (9, 0x7, b"Key 7\0"),
+    (1, 0x9, b"Key 9\0"),
+    (10, 0x1, b"Key 1\0"),
+    (11, 0x3, b"Key 3\0"),
+    (12, 0xC, b"Key C\0"),
+    (13, 0xD, b"Key D\0"),
+    (14, 0xA, b"Key A\0"),
+    (15, 0xB, b"Key B\0"),
+    (2, 0xE, b"Key E\0"),
+    (3, 0xF, b"Key F\0"),
+];
+
+/// The callbacks given by the frontend.
+#[derive(Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+/// The state of the core.
+struct Core {
+    vm: Chip8,
+    /// The loaded ROM, kept to reset the virtual machine.
+    rom: Vec<u8>,
+    cycles_per_frame: u32,
+    palette: Palette,
+    /// The XRGB8888 framebuffer sent to the frontend.
+    framebuffer: Vec<u32>,
+    /// The audio samples of a frame, interleaved stereo.
+    samples: Vec<i16>,
+    /// The position in the beep square wave, in samples.
+    phase: usize,
+}
+
+impl Core {
+    fn new(rom: Vec<u8>) -> Core {
+        Core {
+            vm: Chip8::new(),
+            rom,
+            cycles_per_frame: CPU_CLOCK / TIMERS_CLOCK,
+            palette: Palette::default(),
+            framebuffer: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            samples: Vec::new(),
+            phase: 0,
+        }
+    }
+
+    /// Reset the virtual machine and load the ROM, keeping the options.
+    fn reset(&mut self) {
+        let quirks = self.vm.quirks();
+        self.vm = Chip8::new();
+        self.vm.set_quirks(quirks);
+        if let Err(why) = self.vm.load_bytes(&self.rom) {
+            error!("cannot load the ROM : {}", why);
+        }
+    }
+
+    /// Read the core options from the frontend.
+    fn update_options(&mut self, callbacks: &Callbacks) {
+        if let Some(clock) = variable(callbacks, b"chip8vm_clock\0") {
+            if let Ok(clock) = clock.parse::<u32>() {
+                self.cycles_per_frame = (clock / TIMERS_CLOCK).max(1);
+            }
+        }
+        if let Some(name) = variable(callbacks, b"chip8vm_palette\0") {
+            if let Some(theme) = Theme::from_name(&name) {
+                self.palette = theme.palette();
+            }
+        }
+        let quirks = match variable(callbacks, b"chip8vm_quirks\0") {
+            Some(ref name) if name == "custom" => {
+                let enabled = |key: &[u8], default: bool| match variable(callbacks, key) {
+                    Some(value) => value == "enabled",
+                    None => default,
+                };
+                let default = Quirks::default();
+                Quirks {
+                    shift: enabled(b"chip8vm_quirk_shift\0", default.shift),
+                    memory_increment: match variable(callbacks, b"chip8vm_quirk_memory\0") {
+                        Some(ref value) if value == "x" => MemoryIncrement::ByX,
+                        Some(ref value) if value == "0" => MemoryIncrement::Unchanged,
+                        _ => MemoryIncrement::ByXPlusOne,
+                    },
+                    jump: enabled(b"chip8vm_quirk_jump\0", default.jump),
+                    logic: enabled(b"chip8vm_quirk_logic\0", default.logic),
+                    wrap: enabled(b"chip8vm_quirk_wrap\0", default.wrap),
+                    vblank: enabled(b"chip8vm_quirk_vblank\0", default.vblank),
+                }
+            }
+            Some(name) => Quirks::from_name(&name).unwrap_or_default(),
+            None => Quirks::default(),
+        };
+        self.vm.set_quirks(quirks);
+    }
+
+    /// Read the RetroPad state and update the virtual keypad.
+    fn poll_input(&mut self, callbacks: &Callbacks) {
+        let (poll, state) = match (callbacks.input_poll, callbacks.input_state) {
+            (Some(poll), Some(state)) => (poll, state),
+            _ => return,
+        };
+        poll();
+        for &(id, key, _) in RETROPAD_LAYOUT.iter() {
+            let pressed = state(0, RETRO_DEVICE_JOYPAD, 0, id) != 0;
+            match (pressed, self.vm.keypad.get_key_state(key)) {
+                (true, Keystate::Released) => self.vm.keypad.set_key_state(key, Keystate::Pressed),
+                (false, Keystate::Pressed) => self.vm.keypad.set_key_state(key, Keystate::Released),
+                _ => {}
+            }
+        }
+    }
+
+    /// Emulate a 60 Hz frame.
+    fn run_frame(&mut self) {
+        for _ in 0..self.cycles_per_frame {
+            if self.vm.emulate_cycle() {
+                break;
+            }
+        }
+        self.vm.update_timers();
+    }
+
+    /// Render the display to the XRGB8888 framebuffer, resized to the
+    /// display.
+    fn render(&mut self) {
+        let (width, height) = self.vm.display.size();
+        self.framebuffer.resize(width * height, 0);
+        let (display, palette) = (&self.vm.display, &self.palette);
+        for (y, pixels) in self.framebuffer.chunks_mut(width).enumerate() {
+            for (x, pixel) in pixels.iter_mut().enumerate() {
+                let color = display.color(x, y, palette);
+                *pixel = (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32;
+            }
+        }
+    }
+
+    /// Generate the audio samples of a frame : a square wave while the sound
+    /// timer is active, silence otherwise.
+    fn generate_audio(&mut self) {
+        let frames = SAMPLE_RATE / TIMERS_CLOCK as usize;
+        let period = SAMPLE_RATE / BEEP_FREQUENCY;
+        self.samples.clear();
+        for _ in 0..frames {
+            let sample = if self.vm.sound_timer > 0 {
+                self.phase = (self.phase + 1) % period;
+                if self.phase < period / 2 {
+                    BEEP_AMPLITUDE
+                } else {
+                    -BEEP_AMPLITUDE
+                }
+            } else {
+                0
+            };
+            self.samples.push(sample);
+            self.samples.push(sample);
+        }
+    }
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+/// Ask the frontend for the value of the core option with the given
+/// (nul-terminated) key.
+fn variable(callbacks: &Callbacks, key: &[u8]) -> Option<String> {
+    let environment = callbacks.environment?;
+    let mut var = RetroVariable {
+        key: key.as_ptr() as *const c_char,
+        value: ptr::null(),
+    };
+    if !environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE,
+        &mut var as *mut RetroVariable as *mut c_void,
+    ) || var.value.is_null()
+    {
+        return None;
+    }
+    let value = unsafe { CStr::from_ptr(var.value) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+/// Run the given closure on the loaded core, if any.
+fn with_core<T, F: FnOnce(&mut Core, &Callbacks) -> T>(f: F) -> Option<T> {
+    let callbacks = CALLBACKS.lock().ok()?;
+    let mut core = CORE.lock().ok()?;
+    core.as_mut().map(|core| f(core, &callbacks))
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(cb: RetroEnvironment) {
+    let mut variables: Vec<RetroVariable> = VARIABLES
+        .iter()
+        .map(|&(key, value)| RetroVariable {
+            key: key.as_ptr() as *const c_char,
+            value: value.as_ptr() as *const c_char,
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    cb(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+    if let Ok(mut callbacks) = CALLBACKS.lock() {
+        callbacks.environment = Some(cb);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(cb: RetroVideoRefresh) {
+    if let Ok(mut callbacks) = CALLBACKS.lock() {
+        callbacks.video_refresh = Some(cb);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_cb: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(cb: RetroAudioSampleBatch) {
+    if let Ok(mut callbacks) = CALLBACKS.lock() {
+        callbacks.audio_sample_batch = Some(cb);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(cb: RetroInputPoll) {
+    if let Ok(mut callbacks) = CALLBACKS.lock() {
+        callbacks.input_poll = Some(cb);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(cb: RetroInputState) {
+    if let Ok(mut callbacks) = CALLBACKS.lock() {
+        callbacks.input_state = Some(cb);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    if let Ok(mut core) = CORE.lock() {
+        *core = None;
+    }
+}
+
+/// # Safety
+/// 'info' must point to a writable 'retro_system_info'.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    if info.is_null() {
+        return;
+    }
+    *info = RetroSystemInfo {
+        library_name: b"chip8vm\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8|rom\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// 'info' must point to a writable 'retro_system_av_info'.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    if info.is_null() {
+        return;
+    }
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: DISPLAY_WIDTH as c_uint,
+            base_height: DISPLAY_HEIGHT as c_uint,
+            // the two-page hi-res interpreter doubles the height
+            max_width: DISPLAY_WIDTH as c_uint,
+            max_height: 2 * DISPLAY_HEIGHT as c_uint,
+            aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: TIMERS_CLOCK as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(|core, _| core.reset());
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    with_core(|core, callbacks| {
+        if let Some(environment) = callbacks.environment {
+            let mut updated = false;
+            if environment(
+                RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+                &mut updated as *mut bool as *mut c_void,
+            ) && updated
+            {
+                core.update_options(callbacks);
+            }
+        }
+
+        core.poll_input(callbacks);
+        core.run_frame();
+
+        core.render();
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let (width, height) = core.vm.display.size();
+            video_refresh(
+                core.framebuffer.as_ptr() as *const c_void,
+                width as c_uint,
+                height as c_uint,
+                width * 4,
+            );
+        }
+
+        core.generate_audio();
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            audio_sample_batch(core.samples.as_ptr(), core.samples.len() / 2);
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_core(|core, _| core.vm.save_state().len()).unwrap_or(0)
+}
+
+/// # Safety
+/// 'data' must point to 'size' writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let out = slice::from_raw_parts_mut(data as *mut u8, size);
+    with_core(|core, _| {
+        let state = core.vm.save_state();
+        if state.len() > out.len() {
+            return false;
+        }
+        out[..state.len()].copy_from_slice(&state);
+        true
+    })
+    .unwrap_or(false)
+}
+
+/// # Safety
+/// 'data' must point to 'size' readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = slice::from_raw_parts(data as *const u8, size);
+    with_core(|core, _| match core.vm.load_state(state) {
+        Ok(()) => true,
+        Err(why) => {
+            warn!("cannot restore the state : {}", why);
+            false
+        }
+    })
+    .unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// 'game' must be null or point to a valid 'retro_game_info'.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size);
+    if rom.is_empty() || rom.len() > MEMORY_SIZE - PROGRAM_START {
+        error!("invalid ROM size : {} bytes", rom.len());
+        return false;
+    }
+
+    let callbacks = match CALLBACKS.lock() {
+        Ok(callbacks) => callbacks,
+        Err(_) => return false,
+    };
+    if let Some(environment) = callbacks.environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        ) {
+            error!("the frontend does not support the XRGB8888 pixel format");
+            return false;
+        }
+        let mut descriptors: Vec<RetroInputDescriptor> = RETROPAD_LAYOUT
+            .iter()
+            .map(|&(id, _, description)| RetroInputDescriptor {
+                port: 0,
+                device: RETRO_DEVICE_JOYPAD,
+                index: 0,
+                id,
+                description: description.as_ptr() as *const c_char,
+            })
+            .collect();
+        descriptors.push(RetroInputDescriptor {
+            port: 0,
+            device: 0,
+            index: 0,
+            id: 0,
+            description: ptr::null(),
+        });
+        environment(
+            RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr() as *mut c_void,
+        );
+    }
+
+    let mut core = Core::new(rom.to_vec());
+    core.update_options(&callbacks);
+    core.reset();
+    match CORE.lock() {
+        Ok(mut slot) => {
+            *slot = Some(core);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    if let Ok(mut core) = CORE.lock() {
+        *core = None;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return ptr::null_mut();
+    }
+    with_core(|core, _| core.vm.memory_mut().as_mut_ptr() as *mut c_void).unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return 0;
+    }
+    with_core(|core, _| core.vm.memory().len()).unwrap_or(0)
+}
@@ -0,0 +1,207 @@
+/// Loader layer over 'Chip8::load', recognising the ROM containers and their
+/// load conventions :
+/// - '.ch8' and unknown extensions : raw CHIP 8 binaries loaded at 0x200 ;
+/// - '.c8x' : CHIP-8X binaries, loaded at 0x300 after the larger interpreter ;
+/// - '.sc8' : SUPER-CHIP binaries ;
+/// - '.xo8' : XO-CHIP binaries ;
+/// - Octo cartridges : GIF images with the program and its options embedded
+///   in the pixel data, recognised by their content.
+use std::fs;
+use std::path::Path;
+
+use crate::database::{platform_quirks, RomInfo};
+use crate::gif;
+use crate::json::Json;
+use crate::palette::Palette;
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::vm::{Chip8, LoadError};
+
+/// The ROM containers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Container {
+    Binary,
+    Chip8X,
+    SuperChip,
+    XoChip,
+    OctoCartridge,
+}
+
+impl Container {
+    /// Recognise the container of the given file contents, from their magic
+    /// bytes then from the file extension.
+    pub fn detect(data: &[u8], filepath: Option<&Path>) -> Container {
+        if data.starts_with(b"GIF8") {
+            return Container::OctoCartridge;
+        }
+        let extension = filepath
+            .and_then(|filepath| filepath.extension())
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_ref().map(|extension| &extension[..]) {
+            Some("c8x") => Container::Chip8X,
+            Some("sc8") => Container::SuperChip,
+            Some("xo8") => Container::XoChip,
+            _ => Container::Binary,
+        }
+    }
+
+    /// Return the load conventions of the binaries in this container : their
+    /// platform, quirks and load address.
+    pub fn conventions(self) -> RomInfo {
+        let (platform, start_address) = match self {
+            Container::Binary | Container::OctoCartridge => return RomInfo::default(),
+            Container::Chip8X => ("chip8x", 0x300),
+            Container::SuperChip => ("superchip", 0x200),
+            Container::XoChip => ("xochip", 0x200),
+        };
+        RomInfo {
+            platform: Some(platform.to_owned()),
+            quirks: platform_quirks(platform),
+            start_address: Some(start_address),
+            ..RomInfo::default()
+        }
+    }
+}
+
+/// A program ready to be loaded.
+pub struct Program {
+    pub container: Container,
+    /// The binary to load in memory.
+    pub rom: Vec<u8>,
+    /// The settings implied by the container.
+    pub info: RomInfo,
+}
+
+/// Recognise the container of the given file contents and extract the
+/// program.
+/// The Octo cartridges embed Octo source code rather than a binary : they
+/// are rejected with an explanation, see 'decode_cartridge' to read their
+/// options.
+pub fn open(data: Vec<u8>, filepath: Option<&Path>) -> Result<Program, LoadError> {
+    let container = Container::detect(&data, filepath);
+    if container == Container::OctoCartridge {
+        decode_cartridge(&data).map_err(LoadError::Format)?;
+        return Err(LoadError::Format(
+            "Octo cartridges contain Octo source code : assemble it with Octo \
+             first, then load the binary along with the cartridge options"
+                .to_owned(),
+        ));
+    }
+    Ok(Program {
+        container,
+        rom: data,
+        info: container.conventions(),
+    })
+}
+
+/// Load the ROM file at the given path in the virtual machine, applying the
+/// load address and quirks of its container.
+/// Return the settings implied by the container, for the other components
+/// (e.g. the CPU clock or the palette).
+pub fn load(vm: &mut Chip8, filepath: &Path) -> Result<RomInfo, LoadError> {
+    let program = open(fs::read(filepath)?, Some(filepath))?;
+    if let Some(address) = program.info.start_address {
+        vm.set_load_address(address);
+    }
+    if let Some(quirks) = program.info.quirks {
+        vm.set_quirks(quirks);
+    }
+    vm.load_bytes(&program.rom)?;
+    Ok(program.info)
+}
+
+/// The contents of an Octo cartridge.
+pub struct Cartridge {
+    /// The Octo source code of the program.
+    pub source: String,
+    /// The options of the program : quirks, tickrate and colours.
+    pub info: RomInfo,
+}
+
+/// Extract the program and options embedded in an Octo cartridge.
+/// The payload is stored in the low nibbles of the palette indices of all
+/// the frames, two pixels per byte (high nibble first) : a 32 bits big
+/// endian length, then a JSON object with the "program" source code and the
+/// "options" of the Octo IDE.
+/// If the operation fails, return a String explaining why.
+pub fn decode_cartridge(data: &[u8]) -> Result<Cartridge, String> {
+    let image = gif::decode(data)?;
+    let nibbles: Vec<u8> = image
+        .frames
+        .iter()
+        .flat_map(|frame| frame.pixels.iter())
+        .map(|pixel| pixel & 0x0F)
+        .collect();
+    let bytes: Vec<u8> = nibbles
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    if bytes.len() < 4 {
+        return Err("not an Octo cartridge : no payload".to_owned());
+    }
+    let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let payload = match bytes.get(4..4 + size) {
+        Some(payload) => payload,
+        None => return Err("not an Octo cartridge : truncated payload".to_owned()),
+    };
+    // Octo stores one byte per character of the JSON string
+    let text: String = payload.iter().map(|&byte| byte as char).collect();
+    let json = Json::parse(&text).map_err(|why| format!("not an Octo cartridge : {}", why))?;
+
+    let source = match json.get("program").and_then(Json::as_str) {
+        Some(source) => source.to_owned(),
+        None => return Err("not an Octo cartridge : no program".to_owned()),
+    };
+    let info = json
+        .get("options")
+        .map(parse_octo_options)
+        .unwrap_or_default();
+    Ok(Cartridge { source, info })
+}
+
+/// Parse the options of the Octo IDE.
+fn parse_octo_options(options: &Json) -> RomInfo {
+    let flag = |name: &str| options.get(name).and_then(Json::as_bool).unwrap_or(false);
+    let memory_increment = if flag("loadStoreQuirks") {
+        MemoryIncrement::Unchanged
+    } else {
+        MemoryIncrement::ByXPlusOne
+    };
+    let quirks = Quirks {
+        shift: flag("shiftQuirks"),
+        memory_increment,
+        jump: flag("jumpQuirks"),
+        logic: flag("logicQuirks"),
+        wrap: !flag("clipQuirks"),
+        vblank: flag("vBlankQuirks"),
+    };
+
+    let tickrate = options
+        .get("tickrate")
+        .and_then(Json::as_f64)
+        .filter(|&tickrate| tickrate >= 1.0)
+        .map(|tickrate| tickrate as u32);
+
+    let colors: Vec<&str> = ["backgroundColor", "fillColor", "fillColor2", "blendColor"]
+        .iter()
+        .filter_map(|name| options.get(name).and_then(Json::as_str))
+        .collect();
+    let palette = match colors.len() {
+        4 | 2 => match Palette::from_hex_list(&colors.join(",")) {
+            Ok(palette) => Some(palette),
+            Err(why) => {
+                warn!("ignoring the colours of the cartridge : {}", why);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    RomInfo {
+        quirks: Some(quirks),
+        tickrate,
+        palette,
+        ..RomInfo::default()
+    }
+}
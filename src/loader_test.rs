@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use super::gif::{self, lzw_encode, GifEncoder};
+use super::loader::{decode_cartridge, open, Container};
+use super::palette::{Palette, Rgb};
+use super::quirks::MemoryIncrement;
+use super::vm::LoadError;
+
+#[test]
+fn gif_round_trip() {
+    let pixels: Vec<u8> = (0..64 * 32).map(|i| ((i * 7) % 13 % 4) as u8).collect();
+    let mut encoder = GifEncoder::new(Vec::new(), 64, 32, &Palette::default()).unwrap();
+    encoder.write_frame(&pixels, 2).unwrap();
+    encoder.write_frame(&[1; 64 * 32], 2).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let image = gif::decode(&data).unwrap();
+    assert_eq!((image.width, image.height), (64, 32));
+    assert_eq!(image.frames.len(), 2);
+    assert_eq!(image.frames[0].pixels, pixels);
+    assert_eq!(image.frames[1].pixels, vec![1; 64 * 32]);
+    assert!(gif::decode(&data[..data.len() / 2]).is_err());
+    assert!(gif::decode(b"\x89PNG").is_err());
+}
+
+/// Build a GIF carrying the given payload in the low nibbles, as Octo does.
+fn build_cartridge(json: &str) -> Vec<u8> {
+    let mut bytes = (json.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(json.as_bytes());
+    let mut pixels: Vec<u8> = bytes
+        .iter()
+        .flat_map(|&byte| vec![0x30 | byte >> 4, 0x30 | byte & 0x0F])
+        .collect();
+    pixels.resize(pixels.len().div_ceil(128) * 128, 0);
+
+    let mut data = b"GIF89a".to_vec();
+    data.extend_from_slice(&128u16.to_le_bytes());
+    data.extend_from_slice(&((pixels.len() / 128) as u16).to_le_bytes());
+    // global colour table of 256 entries
+    data.extend_from_slice(&[0xF7, 0x00, 0x00]);
+    data.extend_from_slice(&[0x80; 256 * 3]);
+    data.extend_from_slice(&[0x2C, 0x00, 0x00, 0x00, 0x00]);
+    data.extend_from_within(6..10);
+    data.extend_from_slice(&[0x00, 0x08]);
+    for block in lzw_encode(&pixels, 8).chunks(255) {
+        data.push(block.len() as u8);
+        data.extend_from_slice(block);
+    }
+    data.extend_from_slice(&[0x00, 0x3B]);
+    data
+}
+
+#[test]
+fn octo_cartridge() {
+    let data = build_cartridge(
+        r##"{"program": ": main\n  loop again", "options": {"tickrate": 20,
+        "shiftQuirks": true, "loadStoreQuirks": true, "clipQuirks": true,
+        "backgroundColor": "#000000", "fillColor": "#FF0000",
+        "fillColor2": "#00FF00", "blendColor": "#0000FF"}}"##,
+    );
+    let cartridge = decode_cartridge(&data).unwrap();
+    assert_eq!(cartridge.source, ": main\n  loop again");
+    assert_eq!(cartridge.info.tickrate, Some(20));
+    let quirks = cartridge.info.quirks.unwrap();
+    assert!(quirks.shift && !quirks.wrap && !quirks.jump);
+    assert_eq!(quirks.memory_increment, MemoryIncrement::Unchanged);
+    let palette = cartridge.info.palette.unwrap();
+    assert_eq!(palette.colors[1], Rgb::new(0xFF, 0x00, 0x00));
+    assert_eq!(palette.colors[3], Rgb::new(0x00, 0x00, 0xFF));
+
+    match open(data, Some(Path::new("game.gif"))) {
+        Err(LoadError::Format(_)) => {}
+        _ => panic!("an Octo cartridge cannot be run without Octo"),
+    }
+    assert!(decode_cartridge(&build_cartridge("[1, 2]")).is_err());
+}
+
+#[test]
+fn containers() {
+    let rom = vec![0x12, 0x00];
+    let program = open(rom.clone(), Some(Path::new("GAME.C8X"))).unwrap();
+    assert_eq!(program.container, Container::Chip8X);
+    assert_eq!(program.info.start_address, Some(0x300));
+    assert_eq!(program.rom, rom);
+    let program = open(rom.clone(), Some(Path::new("game.sc8"))).unwrap();
+    assert_eq!(program.container, Container::SuperChip);
+    assert!(program.info.quirks.unwrap().shift);
+    assert_eq!(
+        Container::detect(&rom, Some(Path::new("game.xo8"))),
+        Container::XoChip
+    );
+    let program = open(rom, None).unwrap();
+    assert_eq!(program.container, Container::Binary);
+    assert_eq!(program.info.start_address, None);
+}
@@ -0,0 +1,85 @@
+/// The interface between the CHIP 8 engines and the frontends : the
+/// high-level 'Chip8' virtual machine, which hosts the CHIP 8 variants, and
+/// the low-level 'Vip' running the original interpreter.
+/// The frontends only drive a 'Machine', so that adding an engine or a
+/// variant does not touch them.
+use std::fmt;
+
+use crate::diagnostics::Diagnostic;
+use crate::display::Display;
+use crate::keypad::Keypad;
+use crate::megachip::Sound;
+use crate::vm::LoadError;
+
+/// A machine running CHIP 8 programs.
+pub trait Machine {
+    /// An instruction of the machine, as decoded at the program counter.
+    type Instruction: Copy + fmt::Debug;
+
+    /// Load the given program in memory at the entry point.
+    fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError>;
+
+    /// Reset the machine, keeping the loaded program.
+    fn reset(&mut self);
+
+    /// Decode the instruction at the program counter, without executing it.
+    /// Return None if there is none, e.g. past the end of the memory.
+    fn decode(&self) -> Option<Self::Instruction>;
+
+    /// Execute the given instruction, as if fetched at the program counter.
+    fn execute(&mut self, instruction: Self::Instruction);
+
+    /// Run the machine for a 60 Hz frame, then update the timers. The
+    /// machines without a clock of their own execute the given number of
+    /// instructions.
+    /// Return true if the program is done.
+    fn run_frame(&mut self, cycles: u32) -> bool;
+
+    fn display(&self) -> &Display;
+
+    fn display_mut(&mut self) -> &mut Display;
+
+    fn keypad(&self) -> &Keypad;
+
+    fn keypad_mut(&mut self) -> &mut Keypad;
+
+    /// Get the second keypad of the machine, if it has one (CHIP-8X). None
+    /// by default.
+    fn second_keypad_mut(&mut self) -> Option<&mut Keypad> {
+        None
+    }
+
+    /// Is the machine waiting for a key ? The machine follows the state of
+    /// its keypad by itself.
+    fn is_waiting_for_key(&self) -> bool {
+        false
+    }
+
+    fn delay_timer(&self) -> u8;
+
+    fn sound_timer(&self) -> u8;
+
+    /// Should the machine beep ? While the sound timer is active by default.
+    fn is_beeping(&self) -> bool {
+        self.sound_timer() > 0
+    }
+
+    /// Get the digitised sound playing, if any, with a serial number changed
+    /// whenever a sound is started (MEGA-CHIP). None by default.
+    fn digitised_sound(&self) -> Option<(&Sound, u32)> {
+        None
+    }
+
+    /// Serialize the whole state of the machine, see 'load_state'.
+    fn save_state(&self) -> Vec<u8>;
+
+    /// Restore a state serialized by 'save_state'.
+    /// If the operation fails, return a String explaining why and leave the
+    /// machine untouched.
+    fn load_state(&mut self, data: &[u8]) -> Result<(), String>;
+
+    /// Get the findings of the strict mode, none by default.
+    fn diagnostics(&self) -> &[Diagnostic] {
+        &[]
+    }
+}
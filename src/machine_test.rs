@@ -0,0 +1,60 @@
+use super::cosmac::Vip;
+use super::keypad::Keystate::*;
+use super::machine::Machine;
+use super::vm::Chip8;
+
+/// Run the given number of frames, then check that a state saved before
+/// them restores the timers and the display.
+fn state_round_trip<M: Machine>(vm: &mut M, frames: u32) {
+    let state = vm.save_state();
+    let (delay_timer, gfx) = (vm.delay_timer(), vm.display().gfx.clone());
+    for _ in 0..frames {
+        vm.run_frame(10);
+    }
+    assert_ne!(vm.delay_timer(), delay_timer);
+    assert!(vm.load_state(&state).is_ok());
+    assert_eq!(vm.delay_timer(), delay_timer);
+    assert_eq!(vm.display().gfx, gfx);
+    assert!(vm.load_state(&state[..state.len() - 1]).is_err());
+}
+
+#[test]
+fn chip8_machine() {
+    let mut vm = Chip8::new();
+    // delay timer = 0x20, draw the glyph 0 of the font at (0, 0), then loop
+    let rom = [0x60, 0x20, 0xF0, 0x15, 0xA0, 0x50, 0xD1, 0x15, 0x12, 0x08];
+    assert!(Machine::load_bytes(&mut vm, &rom).is_ok());
+    assert_eq!(vm.decode(), Some(0x6020));
+    vm.execute(0x6F07);
+    assert_eq!(vm.register(0xF), 0x07);
+    Machine::reset(&mut vm);
+
+    assert!(!vm.run_frame(10));
+    assert_eq!(Machine::delay_timer(&vm), 0x1F);
+    assert_eq!(vm.decode(), Some(0x1208));
+    assert!(vm.is_beeping() == (vm.sound_timer > 0));
+    assert_eq!(vm.display().gfx[0][..4], [1, 1, 1, 1]);
+    vm.keypad_mut().set_key_state(0x3, Pressed);
+    state_round_trip(&mut vm, 5);
+}
+
+#[test]
+fn vip_machine() {
+    let mut vip = Vip::new();
+    // R3 = 0x06, SEP R3, then increment R8.1 in a loop
+    let interpreter = [
+        0xF8, 0x06, 0xA3, 0xD3, 0x00, 0x00, 0x98, 0xFC, 0x01, 0xB8, 0x30, 0x06,
+    ];
+    assert!(vip.load_interpreter(&interpreter).is_ok());
+    assert!(Machine::load_bytes(&mut vip, &[0x12, 0x00]).is_ok());
+    assert_eq!(vip.decode(), Some(0xF8));
+    vip.execute(0xC4);
+    assert_eq!(vip.cpu.r[0], 0x0001);
+    assert_eq!(vip.cycles(), 3);
+    Machine::reset(&mut vip);
+
+    assert!(!Machine::run_frame(&mut vip, 10));
+    assert!(Machine::delay_timer(&vip) > 0);
+    assert!(!Machine::is_beeping(&vip));
+    state_round_trip(&mut vip, 1);
+}
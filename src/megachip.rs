@@ -0,0 +1,410 @@
+/// The MEGA-CHIP 8 extension of SUPER-CHIP : a 256x192 display with a
+/// palette of 255 colours loaded from memory, sprites of palette indices
+/// with a configurable size, blend modes and digitised sound.
+/// The sprites are drawn to a back buffer, presented to the display and
+/// cleared by 00E0.
+use crate::display::Display;
+use crate::palette::Rgb;
+use crate::state::{StateReader, StateWriter};
+
+/// The width of the MEGA-CHIP display, in pixels.
+pub const MEGA_WIDTH: usize = 256;
+/// The height of the MEGA-CHIP display, in pixels.
+pub const MEGA_HEIGHT: usize = 192;
+/// The number of entries in the palette, entry 0 being transparent.
+pub const MEGA_PALETTE_SIZE: usize = 256;
+/// The maximum width and height of the sprites, in pixels.
+pub const MAX_SPRITE_SIZE: usize = 256;
+/// The palette index of the monochrome sprites, e.g. the font glyphs.
+pub const GLYPH_COLOR: u8 = 0xFF;
+
+/// How the colour of a sprite pixel is combined with the colour already on
+/// screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    /// 25 % of the sprite colour.
+    Alpha25,
+    /// 50 % of the sprite colour.
+    Alpha50,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    /// Return the blend mode of the given index, as given to 080N.
+    pub fn from_index(index: u8) -> Option<BlendMode> {
+        match index {
+            0 => Some(BlendMode::Normal),
+            1 => Some(BlendMode::Alpha25),
+            2 => Some(BlendMode::Alpha50),
+            3 => Some(BlendMode::Add),
+            4 => Some(BlendMode::Multiply),
+            _ => None,
+        }
+    }
+
+    /// Return the index of the blend mode, as given to 080N.
+    pub fn index(self) -> u8 {
+        match self {
+            BlendMode::Normal => 0,
+            BlendMode::Alpha25 => 1,
+            BlendMode::Alpha50 => 2,
+            BlendMode::Add => 3,
+            BlendMode::Multiply => 4,
+        }
+    }
+
+    /// Combine the colour of a sprite pixel with the colour on screen.
+    pub fn blend(self, src: Rgb, dst: Rgb) -> Rgb {
+        let mix = |f: &dyn Fn(u32, u32) -> u32| {
+            Rgb::new(
+                f(src.r as u32, dst.r as u32).min(255) as u8,
+                f(src.g as u32, dst.g as u32).min(255) as u8,
+                f(src.b as u32, dst.b as u32).min(255) as u8,
+            )
+        };
+        match self {
+            BlendMode::Normal => src,
+            BlendMode::Alpha25 => mix(&|s, d| (s + 3 * d) / 4),
+            BlendMode::Alpha50 => mix(&|s, d| (s + d) / 2),
+            BlendMode::Add => mix(&|s, d| s + d),
+            BlendMode::Multiply => mix(&|s, d| s * d / 255),
+        }
+    }
+}
+
+/// A true colour framebuffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    /// The colours of the pixels, row by row.
+    pub pixels: Vec<Rgb>,
+    /// The palette indices of the last sprite pixels drawn, row by row,
+    /// checked against the collision colour.
+    pub indices: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// Create a black framebuffer of the given size, in pixels.
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Rgb::new(0, 0, 0); width * height],
+            indices: vec![0; width * height],
+        }
+    }
+
+    /// Return the colour of the pixel at the given position.
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Set every pixel to black.
+    pub fn clear(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = Rgb::new(0, 0, 0);
+        }
+        for index in self.indices.iter_mut() {
+            *index = 0;
+        }
+    }
+
+    /// Scroll the pixels down by the given number of rows, the rows scrolled
+    /// in at the top being black.
+    pub fn scroll_down(&mut self, rows: usize) {
+        let count = rows.min(self.height) * self.width;
+        self.pixels.rotate_right(count);
+        self.indices.rotate_right(count);
+        self.clear_range(0, count);
+    }
+
+    /// Scroll the pixels right by the given number of columns, the columns
+    /// scrolled in on the left being black.
+    pub fn scroll_right(&mut self, columns: usize) {
+        let columns = columns.min(self.width);
+        for start in (0..self.pixels.len()).step_by(self.width) {
+            let end = start + self.width;
+            self.pixels[start..end].rotate_right(columns);
+            self.indices[start..end].rotate_right(columns);
+            self.clear_range(start, start + columns);
+        }
+    }
+
+    /// Scroll the pixels left by the given number of columns, the columns
+    /// scrolled in on the right being black.
+    pub fn scroll_left(&mut self, columns: usize) {
+        let columns = columns.min(self.width);
+        for start in (0..self.pixels.len()).step_by(self.width) {
+            let end = start + self.width;
+            self.pixels[start..end].rotate_left(columns);
+            self.indices[start..end].rotate_left(columns);
+            self.clear_range(end - columns, end);
+        }
+    }
+
+    /// Set the pixels in the given range of positions to black.
+    fn clear_range(&mut self, start: usize, end: usize) {
+        for pixel in self.pixels[start..end].iter_mut() {
+            *pixel = Rgb::new(0, 0, 0);
+        }
+        for index in self.indices[start..end].iter_mut() {
+            *index = 0;
+        }
+    }
+
+    /// Serialize the pixels, see 'Chip8::save_state'.
+    pub(crate) fn save(&self, state: &mut StateWriter) {
+        for color in self.pixels.iter() {
+            state.write_bytes(&[color.r, color.g, color.b]);
+        }
+        state.write_bytes(&self.indices);
+    }
+
+    /// Restore the pixels serialized by 'Framebuffer::save' in a framebuffer
+    /// of the given size.
+    /// If the operation fails, return a String explaining why.
+    pub(crate) fn load(
+        state: &mut StateReader,
+        width: usize,
+        height: usize,
+    ) -> Result<Framebuffer, String> {
+        let mut framebuffer = Framebuffer::new(width, height);
+        for color in framebuffer.pixels.iter_mut() {
+            let rgb = state.read_bytes(3)?;
+            *color = Rgb::new(rgb[0], rgb[1], rgb[2]);
+        }
+        let len = framebuffer.indices.len();
+        framebuffer.indices.copy_from_slice(state.read_bytes(len)?);
+        Ok(framebuffer)
+    }
+}
+
+/// A digitised sound : 8 bits unsigned samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sound {
+    /// The sample rate, in Hz.
+    pub rate: u32,
+    pub samples: Vec<u8>,
+    /// Is the sound repeated until stopped ?
+    pub looping: bool,
+}
+
+impl Sound {
+    /// Read the sound stored in memory at the given address : a 6 bytes
+    /// header with the sample rate (16 bits), the number of samples (24
+    /// bits) and a reserved byte, followed by the samples.
+    /// If the operation fails, return a String explaining why.
+    pub fn from_memory(memory: &[u8], address: usize, looping: bool) -> Result<Sound, String> {
+        let header = match memory.get(address..address + 6) {
+            Some(header) => header,
+            None => return Err(format!("no sound header at {:0>6X}", address)),
+        };
+        let rate = (header[0] as u32) << 8 | header[1] as u32;
+        let length = (header[2] as usize) << 16 | (header[3] as usize) << 8 | header[4] as usize;
+        if rate == 0 {
+            return Err(format!(
+                "invalid sample rate for the sound at {:0>6X}",
+                address
+            ));
+        }
+        match memory.get(address + 6..address + 6 + length) {
+            Some(samples) => Ok(Sound {
+                rate,
+                samples: samples.to_vec(),
+                looping,
+            }),
+            None => Err(format!(
+                "the sound at {:0>6X} ({} samples) does not fit in memory",
+                address, length
+            )),
+        }
+    }
+}
+
+/// The state of the MEGA-CHIP mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MegaChip {
+    /// The colours of the palette indices used by the sprites.
+    pub palette: Vec<Rgb>,
+    /// The size of the sprites drawn by DXYN, in pixels.
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    /// The opacity of the whole screen, used for fades.
+    pub screen_alpha: u8,
+    pub blend_mode: BlendMode,
+    /// Drawing over a pixel of this palette index sets VF. 0 never
+    /// collides, being transparent.
+    pub collision_color: u8,
+    /// The buffer the sprites are drawn to, presented by 00E0.
+    pub back: Framebuffer,
+    /// The digitised sound playing, if any.
+    pub sound: Option<Sound>,
+    /// Incremented whenever a sound is started, so that the players notice
+    /// a sound restarted.
+    pub sound_serial: u32,
+}
+
+impl MegaChip {
+    /// Create the MEGA-CHIP state as set by 0011 : a black screen and a
+    /// white palette.
+    pub fn new() -> MegaChip {
+        let mut palette = vec![Rgb::new(0xFF, 0xFF, 0xFF); MEGA_PALETTE_SIZE];
+        palette[0] = Rgb::new(0, 0, 0);
+        MegaChip {
+            palette,
+            sprite_width: MAX_SPRITE_SIZE,
+            sprite_height: MAX_SPRITE_SIZE,
+            screen_alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            back: Framebuffer::new(MEGA_WIDTH, MEGA_HEIGHT),
+            sound: None,
+            sound_serial: 0,
+        }
+    }
+
+    /// Load the given colours, stored as ARGB 4 bytes each, in the palette
+    /// from the index 1.
+    pub fn load_palette(&mut self, data: &[u8]) {
+        for (entry, argb) in self.palette[1..].iter_mut().zip(data.chunks_exact(4)) {
+            *entry = Rgb::new(argb[1], argb[2], argb[3]);
+        }
+    }
+
+    /// Draw the given sprite of palette indices, 'sprite_width' per row, at
+    /// the given position. The index 0 is transparent and the pixels off
+    /// screen are clipped.
+    /// Return true if a pixel of the collision colour was drawn over.
+    pub fn draw_sprite(&mut self, xpos: usize, ypos: usize, sprite: &[u8]) -> bool {
+        let width = self.sprite_width;
+        let mut collision = false;
+        for (j, row) in sprite.chunks(width).enumerate() {
+            for (i, &index) in row.iter().enumerate() {
+                collision |= self.plot(xpos + i, ypos + j, index);
+            }
+        }
+        collision
+    }
+
+    /// Draw the given monochrome sprite, 8 pixels per row, at the given
+    /// position in the 'GLYPH_COLOR' of the palette.
+    /// Return true if a pixel of the collision colour was drawn over.
+    pub fn draw_glyph(&mut self, xpos: usize, ypos: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        for (j, &row) in sprite.iter().enumerate() {
+            for i in 0..8 {
+                if row & (0x80 >> i) != 0 {
+                    collision |= self.plot(xpos + i, ypos + j, GLYPH_COLOR);
+                }
+            }
+        }
+        collision
+    }
+
+    /// Draw a pixel of the given palette index in the back buffer, unless
+    /// transparent or off screen.
+    /// Return true if a pixel of the collision colour was drawn over.
+    fn plot(&mut self, x: usize, y: usize, index: u8) -> bool {
+        if index == 0 || x >= self.back.width || y >= self.back.height {
+            return false;
+        }
+        let position = y * self.back.width + x;
+        let under = self.back.indices[position];
+        let collision = under != 0 && under == self.collision_color;
+        let color = self.palette[index as usize];
+        self.back.pixels[position] = self.blend_mode.blend(color, self.back.pixels[position]);
+        self.back.indices[position] = index;
+        collision
+    }
+
+    /// Present the back buffer on the given display, faded by the screen
+    /// alpha, then clear it. The pixels of the display are lit where the
+    /// back buffer is not transparent, for the outputs using the palette
+    /// (e.g. screenshots and recordings).
+    pub fn present(&mut self, display: &mut Display) {
+        let alpha = self.screen_alpha as u32;
+        let mut frame = self.back.clone();
+        for pixel in frame.pixels.iter_mut() {
+            *pixel = Rgb::new(
+                (pixel.r as u32 * alpha / 255) as u8,
+                (pixel.g as u32 * alpha / 255) as u8,
+                (pixel.b as u32 * alpha / 255) as u8,
+            );
+        }
+        for (row, indices) in display
+            .gfx
+            .iter_mut()
+            .zip(frame.indices.chunks(frame.width))
+        {
+            for (pixel, &index) in row.iter_mut().zip(indices.iter()) {
+                *pixel = (index != 0) as u8;
+            }
+        }
+        display.framebuffer = Some(frame);
+        display.dirty = true;
+        self.back.clear();
+    }
+
+    /// Serialize the state, see 'Chip8::save_state'.
+    pub(crate) fn save(&self, state: &mut StateWriter) {
+        for color in self.palette.iter() {
+            state.write_bytes(&[color.r, color.g, color.b]);
+        }
+        state.write_u16(self.sprite_width as u16);
+        state.write_u16(self.sprite_height as u16);
+        state.write_u8(self.screen_alpha);
+        state.write_u8(self.blend_mode.index());
+        state.write_u8(self.collision_color);
+        self.back.save(state);
+        state.write_bool(self.sound.is_some());
+        if let Some(ref sound) = self.sound {
+            state.write_u32(sound.rate);
+            state.write_bool(sound.looping);
+            state.write_u32(sound.samples.len() as u32);
+            state.write_bytes(&sound.samples);
+        }
+    }
+
+    /// Restore a state serialized by 'MegaChip::save'.
+    /// If the operation fails, return a String explaining why.
+    pub(crate) fn load(state: &mut StateReader) -> Result<MegaChip, String> {
+        let mut megachip = MegaChip::new();
+        for color in megachip.palette.iter_mut() {
+            let rgb = state.read_bytes(3)?;
+            *color = Rgb::new(rgb[0], rgb[1], rgb[2]);
+        }
+        megachip.sprite_width = (state.read_u16()? as usize).clamp(1, MAX_SPRITE_SIZE);
+        megachip.sprite_height = (state.read_u16()? as usize).clamp(1, MAX_SPRITE_SIZE);
+        megachip.screen_alpha = state.read_u8()?;
+        megachip.blend_mode = match BlendMode::from_index(state.read_u8()?) {
+            Some(mode) => mode,
+            None => return Err("invalid state : unknown blend mode".to_owned()),
+        };
+        megachip.collision_color = state.read_u8()?;
+        megachip.back = Framebuffer::load(state, MEGA_WIDTH, MEGA_HEIGHT)?;
+        if state.read_bool()? {
+            let rate = state.read_u32()?;
+            let looping = state.read_bool()?;
+            let len = state.read_u32()? as usize;
+            let samples = state.read_bytes(len)?.to_vec();
+            if rate == 0 {
+                return Err("invalid state : null sample rate".to_owned());
+            }
+            megachip.sound = Some(Sound {
+                rate,
+                samples,
+                looping,
+            });
+        }
+        Ok(megachip)
+    }
+}
+
+impl Default for MegaChip {
+    fn default() -> MegaChip {
+        MegaChip::new()
+    }
+}
@@ -0,0 +1,73 @@
+use super::display::Display;
+use super::megachip::{Framebuffer, MegaChip, MEGA_HEIGHT, MEGA_WIDTH};
+use super::palette::Rgb;
+
+#[test]
+fn faded_present() {
+    let mut megachip = MegaChip::new();
+    megachip.palette[1] = Rgb::new(0xFF, 0x80, 0x10);
+    megachip.palette[2] = Rgb::new(0x40, 0x40, 0x40);
+    megachip.sprite_width = 2;
+    assert!(!megachip.draw_sprite(3, 4, &[1, 0, 2, 1]));
+    megachip.screen_alpha = 0x40;
+
+    let mut display = Display::with_size(MEGA_WIDTH, MEGA_HEIGHT);
+    display.dirty = false;
+    megachip.present(&mut display);
+    assert!(display.dirty);
+    let frame = display.framebuffer.as_ref().unwrap();
+    assert_eq!(frame.pixel(3, 4), Rgb::new(0x40, 0x20, 0x04));
+    assert_eq!(frame.pixel(3, 5), Rgb::new(0x10, 0x10, 0x10));
+    assert_eq!(frame.pixel(4, 4), Rgb::new(0, 0, 0));
+    // the transparent pixels are not lit
+    assert_eq!(display.gfx[4][3..5], [1, 0]);
+    assert_eq!(display.gfx[5][3..5], [1, 1]);
+
+    // the back buffer is cleared, not faded
+    assert!(megachip.back.indices.iter().all(|&index| index == 0));
+    assert_eq!(megachip.back.pixel(3, 4), Rgb::new(0, 0, 0));
+    megachip.screen_alpha = 0;
+    megachip.draw_sprite(3, 4, &[1]);
+    megachip.present(&mut display);
+    let frame = display.framebuffer.as_ref().unwrap();
+    assert_eq!(frame.pixel(3, 4), Rgb::new(0, 0, 0));
+    assert_eq!(display.gfx[4][3..5], [1, 0]);
+    assert_eq!(display.gfx[5][3..5], [0, 0]);
+}
+
+#[test]
+fn framebuffer_scrolling() {
+    let red = Rgb::new(0xFF, 0, 0);
+    let black = Rgb::new(0, 0, 0);
+    let mut framebuffer = Framebuffer::new(8, 4);
+    framebuffer.pixels[0] = red;
+    framebuffer.indices[0] = 1;
+
+    framebuffer.scroll_down(2);
+    assert_eq!(
+        (framebuffer.pixel(0, 0), framebuffer.pixel(0, 2)),
+        (black, red)
+    );
+    assert_eq!(framebuffer.indices[2 * 8], 1);
+    framebuffer.scroll_right(4);
+    assert_eq!(
+        (framebuffer.pixel(0, 2), framebuffer.pixel(4, 2)),
+        (black, red)
+    );
+    assert_eq!(framebuffer.indices[2 * 8 + 4], 1);
+    framebuffer.scroll_left(3);
+    assert_eq!(framebuffer.pixel(1, 2), red);
+    assert_eq!(
+        framebuffer
+            .indices
+            .iter()
+            .filter(|&&index| index != 0)
+            .count(),
+        1
+    );
+
+    // the pixels scrolled out are lost
+    framebuffer.scroll_left(4);
+    framebuffer.scroll_down(10);
+    assert_eq!(framebuffer, Framebuffer::new(8, 4));
+}
@@ -0,0 +1,45 @@
+//! Hooks called from inside the CHIP 8 virtual machine, for the tools
+//! following the execution without modifying it : profilers, achievement
+//! systems, trainers...
+//! The observer is a type parameter of 'Chip8' : the hooks are statically
+//! dispatched, and cost nothing with the default 'NoObserver'.
+
+/// The hooks called by the virtual machine, doing nothing by default.
+/// The addresses are the addresses in memory, the program counters those of
+/// the instructions being executed.
+pub trait Observer {
+    /// Called before executing the given opcode, fetched at the given program
+    /// counter.
+    fn before_instruction(&mut self, _pc: usize, _opcode: u16) {}
+
+    /// Called after executing the given opcode, fetched at the given program
+    /// counter.
+    fn after_instruction(&mut self, _pc: usize, _opcode: u16) {}
+
+    /// Called when an instruction writes the given byte at the given address,
+    /// before the write, with the byte in the RAM.
+    fn on_memory_write(&mut self, _address: usize, _old: u8, _new: u8) {}
+
+    /// Called after drawing the given sprite at the given position, with the
+    /// collision flag set by the drawing.
+    fn on_draw(&mut self, _x: usize, _y: usize, _sprite: &[u8], _collision: bool) {}
+
+    /// Called when FX0A starts waiting for a key press, to store it in VX.
+    fn on_key_wait(&mut self, _x: u8) {}
+
+    /// Called when the sound timer starts.
+    fn on_sound_start(&mut self) {}
+
+    /// Called when the sound timer stops, either expired or cleared.
+    fn on_sound_stop(&mut self) {}
+
+    /// Called when the instruction at the given program counter fails, e.g.
+    /// an invalid opcode, with the message logged.
+    fn on_error(&mut self, _pc: usize, _message: &str) {}
+}
+
+/// The default observer, which observes nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
@@ -0,0 +1,96 @@
+use super::keypad::Keystate::*;
+use super::machine::Machine;
+use super::observer::Observer;
+use super::vm::Chip8;
+
+/// An observer recording the events.
+#[derive(Default)]
+struct Recorder {
+    instructions: Vec<(usize, u16)>,
+    executed: usize,
+    writes: Vec<(usize, u8, u8)>,
+    draws: Vec<(usize, usize, Vec<u8>, bool)>,
+    key_waits: Vec<u8>,
+    sounds: Vec<bool>,
+    errors: Vec<usize>,
+}
+
+impl Observer for Recorder {
+    fn before_instruction(&mut self, pc: usize, opcode: u16) {
+        self.instructions.push((pc, opcode));
+    }
+
+    fn after_instruction(&mut self, _pc: usize, _opcode: u16) {
+        self.executed += 1;
+    }
+
+    fn on_memory_write(&mut self, address: usize, old: u8, new: u8) {
+        self.writes.push((address, old, new));
+    }
+
+    fn on_draw(&mut self, x: usize, y: usize, sprite: &[u8], collision: bool) {
+        self.draws.push((x, y, sprite.to_vec(), collision));
+    }
+
+    fn on_key_wait(&mut self, x: u8) {
+        self.key_waits.push(x);
+    }
+
+    fn on_sound_start(&mut self) {
+        self.sounds.push(true);
+    }
+
+    fn on_sound_stop(&mut self) {
+        self.sounds.push(false);
+    }
+
+    fn on_error(&mut self, pc: usize, _message: &str) {
+        self.errors.push(pc);
+    }
+}
+
+#[test]
+fn observer_events() {
+    let mut vm = Chip8::with_observer(Recorder::default());
+    let rom = [
+        0x60, 0x02, 0xF0, 0x18, 0xA3, 0x00, 0xF0, 0x33, 0xA3, 0x02, 0xD0, 0x01, 0xD0, 0x01, 0xF1,
+        0x0A, 0xFF, 0xFF,
+    ];
+    assert!(vm.load_bytes(&rom).is_ok());
+    vm.memory_mut()[0x302] = 0x02;
+    assert!(!vm.run_frame(10));
+
+    let observer = vm.observer();
+    assert_eq!(observer.instructions.len(), 8);
+    assert_eq!(observer.instructions[3], (0x206, 0xF033));
+    assert_eq!(observer.executed, 8);
+    assert_eq!(
+        observer.writes,
+        vec![(0x300, 0, 0), (0x301, 0, 0), (0x302, 0x02, 0x02)]
+    );
+    assert_eq!(observer.draws[0], (2, 2, vec![0x02], false));
+    assert_eq!(observer.draws[1], (2, 2, vec![0x02], true));
+    assert_eq!(observer.key_waits, vec![1]);
+    assert_eq!(observer.sounds, vec![true]);
+
+    // the sound timer of 2 stops at the next frame
+    vm.update_timers();
+    assert_eq!(vm.observer().sounds, vec![true, false]);
+
+    vm.keypad.set_key_state(0x3, Pressed);
+    vm.emulate_cycle();
+    vm.keypad.set_key_state(0x3, Released);
+    vm.emulate_cycle();
+    assert_eq!(vm.register(1), 0x3);
+    vm.emulate_cycle();
+    assert_eq!(vm.observer().errors, vec![0x210]);
+
+    // the writes of the stack kept in memory
+    let mut vm = Chip8::with_observer(Recorder::default());
+    vm.set_stack_in_memory(true);
+    vm.execute_opcode(0x2BBB);
+    assert_eq!(
+        vm.observer().writes,
+        vec![(0xECE, 0, 0x02), (0xECF, 0, 0x00)]
+    );
+}
@@ -0,0 +1,182 @@
+//! The colours used to render the display of the CHIP 8 virtual machine,
+//! independently of the emulator's backend.
+
+/// A 24-bit RGB colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Create and return a new colour from its components.
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    /// Parse a colour from an hexadecimal string of the form "#RRGGBB"
+    /// (the leading '#' is optional).
+    pub fn from_hex(string: &str) -> Option<Rgb> {
+        let hex = string.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Some(Rgb::new(component(0), component(2), component(4)))
+    }
+
+    /// Return the "#RRGGBB" representation of the colour.
+    pub fn to_hex(&self) -> String {
+        format!("#{:0>2X}{:0>2X}{:0>2X}", self.r, self.g, self.b)
+    }
+}
+
+/// The number of entries in a palette.
+pub const PALETTE_SIZE: usize = 4;
+
+/// A display palette, indexed by the value of a pixel in 'Display::gfx'.
+/// Entry 0 is the background and entry 1 the foreground ; entries 2 and 3
+/// are only used by multi-plane modes (second plane only, both planes).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [Rgb; PALETTE_SIZE],
+}
+
+impl Palette {
+    /// Create a palette from a background and a foreground colour.
+    /// The multi-plane entries default to the foreground colour.
+    pub fn two_colors(off: Rgb, on: Rgb) -> Palette {
+        Palette {
+            colors: [off, on, on, on],
+        }
+    }
+
+    /// Parse a palette from a comma-separated list of 2 or 4 hexadecimal
+    /// colours, e.g. "#000000,#FFFFFF".
+    /// If the operation fails, return a String explaining why.
+    pub fn from_hex_list(string: &str) -> Result<Palette, String> {
+        let mut colors = Vec::new();
+        for hex in string.split(',') {
+            match Rgb::from_hex(hex) {
+                Some(color) => colors.push(color),
+                None => return Err(format!("invalid colour \"{}\"", hex.trim())),
+            }
+        }
+        match colors.len() {
+            2 => Ok(Palette::two_colors(colors[0], colors[1])),
+            PALETTE_SIZE => Ok(Palette {
+                colors: [colors[0], colors[1], colors[2], colors[3]],
+            }),
+            n => Err(format!("expected 2 or {} colours, got {}", PALETTE_SIZE, n)),
+        }
+    }
+
+    /// Return the colour to use for the given pixel value.
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[pixel as usize % PALETTE_SIZE]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Theme::Classic.palette()
+    }
+}
+
+/// Enumerates the built-in palettes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Theme {
+    /// White on black.
+    Classic,
+    /// Green phosphor monitor.
+    GreenPhosphor,
+    /// Amber phosphor monitor.
+    Amber,
+    /// Greenish liquid crystal display.
+    Lcd,
+    /// Yellow on black, for maximum legibility.
+    HighContrast,
+    /// Colours distinguishable with the common colour vision deficiencies.
+    ColorBlindSafe,
+}
+
+/// All the built-in themes, in cycling order.
+pub static THEMES: [Theme; 6] = [
+    Theme::Classic,
+    Theme::GreenPhosphor,
+    Theme::Amber,
+    Theme::Lcd,
+    Theme::HighContrast,
+    Theme::ColorBlindSafe,
+];
+
+impl Theme {
+    /// Return the theme with the given (case-insensitive) name, if any.
+    pub fn from_name(name: &str) -> Option<Theme> {
+        let name = name.to_lowercase();
+        THEMES.iter().cloned().find(|theme| theme.name() == name)
+    }
+
+    /// Return the name of the theme, as accepted by 'Theme::from_name'.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Theme::Classic => "classic",
+            Theme::GreenPhosphor => "green",
+            Theme::Amber => "amber",
+            Theme::Lcd => "lcd",
+            Theme::HighContrast => "contrast",
+            Theme::ColorBlindSafe => "colorblind",
+        }
+    }
+
+    /// Return the theme following this one in the cycling order.
+    pub fn next(&self) -> Theme {
+        let index = THEMES.iter().position(|theme| theme == self).unwrap();
+        THEMES[(index + 1) % THEMES.len()]
+    }
+
+    /// Return the palette of the theme.
+    pub fn palette(&self) -> Palette {
+        let colors = match *self {
+            Theme::Classic => [
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xFF, 0xFF, 0xFF),
+                Rgb::new(0xAA, 0xAA, 0xAA),
+                Rgb::new(0x55, 0x55, 0x55),
+            ],
+            Theme::GreenPhosphor => [
+                Rgb::new(0x0A, 0x14, 0x0A),
+                Rgb::new(0x33, 0xFF, 0x66),
+                Rgb::new(0x1E, 0x99, 0x3D),
+                Rgb::new(0x99, 0xFF, 0xB2),
+            ],
+            Theme::Amber => [
+                Rgb::new(0x14, 0x0C, 0x00),
+                Rgb::new(0xFF, 0xB0, 0x00),
+                Rgb::new(0x99, 0x69, 0x00),
+                Rgb::new(0xFF, 0xD8, 0x80),
+            ],
+            Theme::Lcd => [
+                Rgb::new(0x9B, 0xBC, 0x0F),
+                Rgb::new(0x0F, 0x38, 0x0F),
+                Rgb::new(0x30, 0x62, 0x30),
+                Rgb::new(0x8B, 0xAC, 0x0F),
+            ],
+            Theme::HighContrast => [
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xFF, 0xFF, 0x00),
+                Rgb::new(0x00, 0xFF, 0xFF),
+                Rgb::new(0xFF, 0xFF, 0xFF),
+            ],
+            // Okabe-Ito colours
+            Theme::ColorBlindSafe => [
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xE6, 0x9F, 0x00),
+                Rgb::new(0x56, 0xB4, 0xE9),
+                Rgb::new(0xF0, 0xE4, 0x42),
+            ],
+        };
+        Palette { colors }
+    }
+}
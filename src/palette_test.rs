@@ -0,0 +1,44 @@
+use super::palette::{Palette, Rgb, Theme, THEMES};
+
+#[test]
+fn hex_colors() {
+    assert_eq!(Rgb::from_hex("#12aBFf"), Some(Rgb::new(0x12, 0xAB, 0xFF)));
+    assert_eq!(Rgb::from_hex(" 000000 "), Some(Rgb::new(0, 0, 0)));
+    assert_eq!(Rgb::new(0x0A, 0xB0, 0x00).to_hex(), "#0AB000");
+    for bad in ["", "#12345", "#1234567", "#12345G", "#+1+2+3", "#éé12"].iter() {
+        assert_eq!(Rgb::from_hex(bad), None, "{}", bad);
+    }
+}
+
+#[test]
+fn hex_palettes() {
+    let (black, white) = (Rgb::new(0, 0, 0), Rgb::new(0xFF, 0xFF, 0xFF));
+    assert_eq!(
+        Palette::from_hex_list("#000000, #FFFFFF"),
+        Ok(Palette::two_colors(black, white))
+    );
+    let palette = Palette::from_hex_list("000000,FFFFFF,FF0000,00FF00").unwrap();
+    assert_eq!(palette.color(2), Rgb::new(0xFF, 0, 0));
+    assert_eq!(palette.color(7), Rgb::new(0, 0xFF, 0));
+
+    assert!(Palette::from_hex_list("#000000,#XYZXYZ").is_err());
+    assert!(Palette::from_hex_list("#000000").is_err());
+    assert!(Palette::from_hex_list("#000000,#FFFFFF,#FF0000").is_err());
+    assert!(Palette::from_hex_list("#000000,#FFFFFF,").is_err());
+}
+
+#[test]
+fn themes() {
+    assert_eq!(Theme::Classic.next(), Theme::GreenPhosphor);
+    assert_eq!(THEMES[THEMES.len() - 1].next(), THEMES[0]);
+    let mut theme = Theme::Amber;
+    for _ in 0..THEMES.len() {
+        theme = theme.next();
+    }
+    assert_eq!(theme, Theme::Amber);
+    for theme in THEMES.iter() {
+        assert_eq!(Theme::from_name(&theme.name().to_uppercase()), Some(*theme));
+    }
+    assert_eq!(Theme::from_name("sepia"), None);
+    assert_eq!(Palette::default(), Theme::Classic.palette());
+}
@@ -0,0 +1,141 @@
+/// Phosphor persistence model, reducing the flicker caused by the CHIP 8
+/// games constantly erasing and redrawing their sprites.
+/// It is computed from the stream of 'Display' snapshots taken at every 60 Hz
+/// frame, so that any backend can use it when rendering.
+use crate::display::Display;
+use crate::palette::{Palette, Rgb};
+
+/// The supported persistence models.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PersistenceMode {
+    /// Pixels flip instantly, as on the original hardware.
+    Off,
+    /// Lit pixels are at full intensity, unlit pixels lose the given fraction
+    /// (in ]0, 1]) of their intensity at every frame.
+    Decay(f32),
+    /// A pixel is lit if it was lit in any of the last two frames.
+    Blend,
+}
+
+impl PersistenceMode {
+    /// Parse a persistence mode : "off", "blend", or "decay" optionally
+    /// followed by the decay rate (e.g. "decay:0.25").
+    /// If the operation fails, return a String explaining why.
+    pub fn from_name(string: &str) -> Result<PersistenceMode, String> {
+        let mut parts = string.splitn(2, ':');
+        match (&parts.next().unwrap().to_lowercase()[..], parts.next()) {
+            ("off", None) => Ok(PersistenceMode::Off),
+            ("blend", None) | ("or", None) => Ok(PersistenceMode::Blend),
+            ("decay", None) => Ok(PersistenceMode::Decay(DEFAULT_DECAY_RATE)),
+            ("decay", Some(rate)) => match rate.parse::<f32>() {
+                Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(PersistenceMode::Decay(rate)),
+                _ => Err(format!("invalid decay rate \"{}\"", rate)),
+            },
+            _ => Err(format!("unrecognized persistence mode \"{}\"", string)),
+        }
+    }
+}
+
+/// The default fraction of intensity lost by an unlit pixel at every frame.
+pub const DEFAULT_DECAY_RATE: f32 = 0.35;
+
+/// The persistence state of every pixel of the display.
+pub struct Persistence {
+    mode: PersistenceMode,
+    width: usize,
+    height: usize,
+    /// The intensity of every pixel, in [0, 1], row by row.
+    intensity: Vec<f32>,
+    /// The value of every pixel when it was last lit, for its colour.
+    value: Vec<u8>,
+    /// The pixels lit in the previous frame, for the blending mode.
+    previous: Vec<bool>,
+}
+
+impl Persistence {
+    /// Create and return a new persistence state with the given model.
+    pub fn new(mode: PersistenceMode) -> Persistence {
+        Persistence {
+            mode,
+            width: 0,
+            height: 0,
+            intensity: Vec::new(),
+            value: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Return the persistence model.
+    pub fn mode(&self) -> PersistenceMode {
+        self.mode
+    }
+
+    /// Update the state with the display snapshot of the next frame.
+    pub fn push_frame(&mut self, display: &Display) {
+        let height = display.gfx.len();
+        let width = if height > 0 { display.gfx[0].len() } else { 0 };
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.intensity = vec![0.0; width * height];
+            self.value = vec![0; width * height];
+            self.previous = vec![false; width * height];
+        }
+
+        for (y, row) in display.gfx.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let i = y * width + x;
+                let lit = pixel != 0;
+                if lit {
+                    self.value[i] = pixel;
+                }
+                self.intensity[i] = match self.mode {
+                    PersistenceMode::Off => {
+                        if lit {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    }
+                    PersistenceMode::Decay(rate) => {
+                        if lit {
+                            1.0
+                        } else {
+                            self.intensity[i] * (1.0 - rate)
+                        }
+                    }
+                    PersistenceMode::Blend => {
+                        if lit || self.previous[i] {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    }
+                };
+                self.previous[i] = lit;
+            }
+        }
+    }
+
+    /// Return the intensity, in [0, 1], of the pixel at the given position.
+    pub fn intensity(&self, x: usize, y: usize) -> f32 {
+        if x < self.width && y < self.height {
+            self.intensity[y * self.width + x]
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the colour of the pixel at the given position, blending the
+    /// background colour of the palette with the pixel's last lit colour.
+    pub fn color(&self, x: usize, y: usize, palette: &Palette) -> Rgb {
+        let off = palette.color(0);
+        let intensity = self.intensity(x, y);
+        if intensity <= 0.0 {
+            return off;
+        }
+        let on = palette.color(self.value[y * self.width + x]);
+        let blend = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * intensity).round() as u8;
+        Rgb::new(blend(off.r, on.r), blend(off.g, on.g), blend(off.b, on.b))
+    }
+}
@@ -0,0 +1,52 @@
+use super::display::Display;
+use super::palette::{Rgb, Theme};
+use super::persistence::{Persistence, PersistenceMode};
+
+#[test]
+fn persistence_decay() {
+    let mut persistence = Persistence::new(PersistenceMode::Decay(0.5));
+    let mut display = Display::new();
+    display.draw(3, 4, &[0x80]);
+    persistence.push_frame(&display);
+    assert_eq!(persistence.intensity(3, 4), 1.0);
+
+    display.clear();
+    persistence.push_frame(&display);
+    assert_eq!(persistence.intensity(3, 4), 0.5);
+    persistence.push_frame(&display);
+    assert_eq!(persistence.intensity(3, 4), 0.25);
+    let palette = Theme::Classic.palette();
+    assert_eq!(
+        persistence.color(3, 4, &palette),
+        Rgb::new(0x40, 0x40, 0x40)
+    );
+    assert_eq!(persistence.color(0, 0, &palette), palette.color(0));
+}
+
+#[test]
+fn persistence_blend() {
+    let mut persistence = Persistence::new(PersistenceMode::Blend);
+    let mut display = Display::new();
+    display.draw(10, 10, &[0x80]);
+    persistence.push_frame(&display);
+    display.clear();
+    persistence.push_frame(&display);
+    assert_eq!(persistence.intensity(10, 10), 1.0);
+    persistence.push_frame(&display);
+    assert_eq!(persistence.intensity(10, 10), 0.0);
+}
+
+#[test]
+fn persistence_mode_parsing() {
+    assert_eq!(PersistenceMode::from_name("off"), Ok(PersistenceMode::Off));
+    assert_eq!(
+        PersistenceMode::from_name("blend"),
+        Ok(PersistenceMode::Blend)
+    );
+    assert_eq!(
+        PersistenceMode::from_name("decay:0.5"),
+        Ok(PersistenceMode::Decay(0.5))
+    );
+    assert!(PersistenceMode::from_name("decay:2").is_err());
+    assert!(PersistenceMode::from_name("fade").is_err());
+}
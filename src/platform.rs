@@ -0,0 +1,273 @@
+/// The CHIP 8 platforms, i.e. the interpreters and their extensions, which
+/// the programs are written for.
+use std::fmt;
+
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::font::Font;
+use crate::quirks::Quirks;
+use crate::vm::{MEMORY_SIZE, PROGRAM_START};
+
+/// The address of the first entry of the call stack of the COSMAC VIP
+/// interpreter, right below its variables at 0xED0.
+pub const VIP_STACK_ADDRESS: usize = 0xECE;
+
+/// The layout of the memory of an interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryMap {
+    /// The size of the memory, in bytes.
+    pub memory_size: usize,
+    /// The address of the small hexadecimal font used by FX29.
+    pub font_address: usize,
+    /// The address of the big font used by FX30.
+    pub big_font_address: usize,
+    /// The address where the programs are loaded and started.
+    pub entry_point: usize,
+    /// The areas used by the interpreter itself (code, variables, stack or
+    /// display buffer), which the programs cannot be loaded over, as
+    /// (start, end) pairs with the end excluded.
+    pub reserved: Vec<(usize, usize)>,
+    /// The address of the first entry of the call stack when it is kept in
+    /// memory, the stack growing downwards by 2 bytes per entry.
+    pub stack_address: usize,
+}
+
+impl MemoryMap {
+    /// The COSMAC VIP : the interpreter takes the first 512 bytes, and its
+    /// variables, stack and display buffer the last 352 bytes.
+    pub fn vip() -> MemoryMap {
+        MemoryMap {
+            memory_size: MEMORY_SIZE,
+            font_address: 0x000,
+            big_font_address: 0x050,
+            entry_point: PROGRAM_START,
+            reserved: vec![(0x000, PROGRAM_START), (0xEA0, MEMORY_SIZE)],
+            stack_address: VIP_STACK_ADDRESS,
+        }
+    }
+
+    /// The COSMAC VIP with the two-page hi-res interpreter : its 64x64
+    /// display buffer takes two pages at 0xE00, and its variables and stack
+    /// move right before.
+    pub fn vip_hires() -> MemoryMap {
+        MemoryMap {
+            reserved: vec![(0x000, PROGRAM_START), (0xDA0, MEMORY_SIZE)],
+            stack_address: VIP_STACK_ADDRESS - 0x100,
+            ..MemoryMap::vip()
+        }
+    }
+
+    /// The COSMAC VIP with the CHIP-8X interpreter, which is larger : the
+    /// programs start at 0x300.
+    pub fn vip_chip8x() -> MemoryMap {
+        MemoryMap {
+            entry_point: 0x300,
+            reserved: vec![(0x000, 0x300), (0xEA0, MEMORY_SIZE)],
+            ..MemoryMap::vip()
+        }
+    }
+
+    /// The ETI-660 : the programs start at 0x600, after the interpreter.
+    pub fn eti660() -> MemoryMap {
+        MemoryMap {
+            memory_size: MEMORY_SIZE,
+            font_address: 0x000,
+            big_font_address: 0x050,
+            entry_point: 0x600,
+            reserved: vec![(0x000, 0x600)],
+            stack_address: VIP_STACK_ADDRESS,
+        }
+    }
+
+    /// The DREAM 6800 and its CHIPOS interpreter, whose display buffer lies
+    /// in the first 512 bytes.
+    pub fn dream6800() -> MemoryMap {
+        MemoryMap {
+            memory_size: MEMORY_SIZE,
+            font_address: 0x000,
+            big_font_address: 0x050,
+            entry_point: PROGRAM_START,
+            reserved: vec![(0x000, PROGRAM_START)],
+            stack_address: VIP_STACK_ADDRESS,
+        }
+    }
+
+    /// Modern interpreters : the font is stored at 0x050 by convention, the
+    /// big font right after it, and the programs may use all the memory
+    /// after 0x200.
+    pub fn modern() -> MemoryMap {
+        MemoryMap {
+            memory_size: MEMORY_SIZE,
+            font_address: 0x050,
+            big_font_address: 0x0A0,
+            entry_point: PROGRAM_START,
+            reserved: vec![(0x000, PROGRAM_START)],
+            stack_address: VIP_STACK_ADDRESS,
+        }
+    }
+
+    /// Return the end of the area available to a program loaded at the
+    /// given address : the start of the next reserved area, or the end of
+    /// the memory.
+    pub fn program_end(&self, address: usize) -> usize {
+        self.reserved
+            .iter()
+            .map(|&(start, _)| start)
+            .filter(|&start| start > address)
+            .min()
+            .unwrap_or(self.memory_size)
+            .min(self.memory_size)
+    }
+}
+
+impl Default for MemoryMap {
+    /// The layout of 'Chip8::new' : the font at 0x000, the big font right
+    /// after it, and the programs may use all the memory after 0x200.
+    /// Unlike the modern preset, the font stays where the earlier versions
+    /// of the virtual machine stored it.
+    fn default() -> MemoryMap {
+        MemoryMap {
+            font_address: 0x000,
+            big_font_address: 0x050,
+            ..MemoryMap::modern()
+        }
+    }
+}
+
+/// The platforms, with their quirks and memory layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Platform {
+    /// The original CHIP 8 interpreter of the COSMAC VIP.
+    Chip8,
+    /// The two-page hi-res CHIP 8 interpreter of the COSMAC VIP, with a
+    /// 64x64 display. Its programs start with the 0x1260 entry trick.
+    Chip8Hires,
+    /// CHIP-8X, the colour extension of the COSMAC VIP interpreter.
+    Chip8X,
+    /// CHIP 8 on the ETI-660 computer.
+    Eti660,
+    /// CHIPOS, the CHIP 8 interpreter of the DREAM 6800 computer.
+    Dream6800,
+    /// CHIP 8 as implemented by most modern interpreters.
+    Modern,
+    /// SUPER-CHIP 1.1, on the HP 48 calculators.
+    SuperChip,
+    /// XO-CHIP, the extension of SUPER-CHIP introduced by Octo, with 64 KB
+    /// of memory. Only the SUPER-CHIP instructions are implemented.
+    XoChip,
+    /// MEGA-CHIP 8, the colour extension of SUPER-CHIP.
+    MegaChip,
+}
+
+impl Platform {
+    /// Return the platform with the given name ("chip8", "chip8hires",
+    /// "chip8x", "eti660", "dream6800", "modern", "schip", "xochip" or
+    /// "megachip"), if any.
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match &name.to_lowercase()[..] {
+            "chip8" | "chip-8" | "vip" => Some(Platform::Chip8),
+            "chip8hires" | "chip8-hires" | "hires" => Some(Platform::Chip8Hires),
+            "chip8x" | "chip-8x" => Some(Platform::Chip8X),
+            "eti660" | "eti-660" => Some(Platform::Eti660),
+            "dream6800" | "dream" => Some(Platform::Dream6800),
+            "modern" => Some(Platform::Modern),
+            "schip" | "superchip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            "megachip" | "megachip8" | "mega-chip" => Some(Platform::MegaChip),
+            _ => None,
+        }
+    }
+
+    /// Return the name of the platform, as accepted by 'Platform::from_name'.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::Chip8Hires => "chip8hires",
+            Platform::Chip8X => "chip8x",
+            Platform::Eti660 => "eti660",
+            Platform::Dream6800 => "dream6800",
+            Platform::Modern => "modern",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+            Platform::MegaChip => "megachip",
+        }
+    }
+
+    /// Return the quirks preset of the platform.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8
+            | Platform::Chip8Hires
+            | Platform::Chip8X
+            | Platform::Eti660
+            | Platform::Dream6800 => Quirks::chip8(),
+            Platform::Modern => Quirks::modern(),
+            Platform::SuperChip | Platform::MegaChip => Quirks::schip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+
+    /// Return the maximum depth of the call stack of the platform : 12 levels
+    /// on the COSMAC VIP, 16 on the later interpreters.
+    pub fn stack_depth(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Chip8Hires | Platform::Chip8X => 12,
+            _ => 16,
+        }
+    }
+
+    /// Does the platform include the SUPER-CHIP instructions ?
+    pub fn has_schip(self) -> bool {
+        matches!(
+            self,
+            Platform::SuperChip | Platform::XoChip | Platform::MegaChip
+        )
+    }
+
+    /// Return the font of the platform.
+    pub fn font(self) -> Font {
+        match self {
+            Platform::Chip8 | Platform::Chip8Hires | Platform::Chip8X => Font::vip(),
+            Platform::Eti660 => Font::eti660(),
+            Platform::Dream6800 => Font::dream6800(),
+            Platform::Modern | Platform::XoChip => Font::modern(),
+            Platform::SuperChip | Platform::MegaChip => Font::schip(),
+        }
+    }
+
+    /// Return the memory layout of the platform.
+    pub fn memory_map(self) -> MemoryMap {
+        match self {
+            Platform::Chip8 => MemoryMap::vip(),
+            Platform::Chip8Hires => MemoryMap::vip_hires(),
+            Platform::Chip8X => MemoryMap::vip_chip8x(),
+            Platform::Eti660 => MemoryMap::eti660(),
+            Platform::Dream6800 => MemoryMap::dream6800(),
+            Platform::Modern | Platform::SuperChip => MemoryMap::modern(),
+            Platform::XoChip => MemoryMap {
+                memory_size: 0x10000,
+                ..MemoryMap::modern()
+            },
+            // 16 MB, reached with the long loads 01NN NNNN
+            Platform::MegaChip => MemoryMap {
+                memory_size: 0x1000000,
+                ..MemoryMap::modern()
+            },
+        }
+    }
+
+    /// Return the size of the display of the platform, as (width, height) in
+    /// pixels. The SUPER-CHIP programs switch to a 128x64 display with 00FF,
+    /// and the MEGA-CHIP programs to a 256x192 display with 0011.
+    pub fn display_size(self) -> (usize, usize) {
+        match self {
+            Platform::Chip8Hires => (DISPLAY_WIDTH, 2 * DISPLAY_HEIGHT),
+            _ => (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,278 @@
+/// Minimal PNG encoder, allowing to take screenshots of the display without
+/// depending on an image processing crate.
+/// Reference : https://www.w3.org/TR/PNG/ and RFC 1950/1951 for the zlib
+/// and DEFLATE formats.
+use crate::display::Display;
+use crate::palette::{Palette, PALETTE_SIZE};
+
+/// The PNG file signature.
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// The maximum length of a stored DEFLATE block.
+const STORED_BLOCK_MAX: usize = 0xFFFF;
+
+/// The DEFLATE compression strategies supported by the encoder.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compression {
+    /// Uncompressed (stored) blocks.
+    Store,
+    /// A single block using the fixed Huffman codes, with back-references to
+    /// the previous byte and to the previous scanline only. This is enough
+    /// to shrink the blocky CHIP 8 screenshots considerably.
+    Fixed,
+}
+
+/// Encode the given display as an indexed-colour PNG image, each CHIP 8
+/// pixel being drawn as a 'scale' x 'scale' square.
+pub fn encode(display: &Display, palette: &Palette, scale: usize) -> Vec<u8> {
+    encode_with(display, palette, scale, Compression::Fixed)
+}
+
+/// Same as 'encode' but with the given compression strategy.
+pub fn encode_with(
+    display: &Display,
+    palette: &Palette,
+    scale: usize,
+    compression: Compression,
+) -> Vec<u8> {
+    let scale = scale.max(1);
+    let height = display.gfx.len();
+    let width = if height > 0 { display.gfx[0].len() } else { 0 };
+    let (w, h) = (width * scale, height * scale);
+
+    // filtered scanlines : a filter type byte (0 = none) followed by one
+    // palette index per pixel
+    let stride = w + 1;
+    let mut raw = Vec::with_capacity(stride * h);
+    for row in display.gfx.iter() {
+        let mut line = Vec::with_capacity(stride);
+        line.push(0u8);
+        for &pixel in row.iter() {
+            for _ in 0..scale {
+                line.push(pixel % PALETTE_SIZE as u8);
+            }
+        }
+        for _ in 0..scale {
+            raw.extend_from_slice(&line);
+        }
+    }
+
+    let mut png = Vec::new();
+    png.extend_from_slice(&SIGNATURE);
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(w as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(h as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 3, 0, 0, 0]); // 8 bits, indexed, no interlace
+    write_chunk(&mut png, b"IHDR", &ihdr);
+
+    let mut plte = Vec::with_capacity(3 * PALETTE_SIZE);
+    for color in palette.colors.iter() {
+        plte.extend_from_slice(&[color.r, color.g, color.b]);
+    }
+    write_chunk(&mut png, b"PLTE", &plte);
+
+    write_chunk(&mut png, b"IDAT", &zlib(&raw, stride, compression));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+/// Append a PNG chunk with the given type and data.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wrap the given data in a zlib stream.
+/// 'stride' is the distance to try for back-references besides 1.
+fn zlib(data: &[u8], stride: usize, compression: Compression) -> Vec<u8> {
+    // deflate, 32K window, no preset dictionary, fastest compression
+    let mut out = vec![0x78, 0x01];
+    match compression {
+        Compression::Store => deflate_store(data, &mut out),
+        Compression::Fixed => deflate_fixed(data, stride, &mut out),
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// DEFLATE the data using stored blocks only.
+fn deflate_store(data: &[u8], out: &mut Vec<u8>) {
+    let mut chunks = data.chunks(STORED_BLOCK_MAX).peekable();
+    if chunks.peek().is_none() {
+        // an empty stream still needs a final block
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+        return;
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        out.push(if last { 0x01 } else { 0x00 });
+        let len = chunk.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Base match lengths for the length codes 257..285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Extra bits for the length codes 257..285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances for the distance codes 0..29.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits for the distance codes 0..29.
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The maximum match length and distance allowed by DEFLATE.
+const MAX_MATCH: usize = 258;
+const MAX_DISTANCE: usize = 32768;
+
+/// LSB-first bit writer, as required by DEFLATE.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> BitWriter<'a> {
+        BitWriter {
+            out,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Write the 'n' lowest bits of 'bits', least significant first.
+    fn write(&mut self, bits: u32, n: u8) {
+        self.buffer |= bits << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first.
+    fn write_code(&mut self, code: u32, n: u8) {
+        let mut reversed = 0;
+        for i in 0..n {
+            reversed |= ((code >> i) & 1) << (n - 1 - i);
+        }
+        self.write(reversed, n);
+    }
+
+    /// Write a literal/length symbol with the fixed Huffman code.
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    /// Write a <length, distance> back-reference.
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let l = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .unwrap();
+        self.write_symbol(257 + l as u16);
+        self.write((length - LENGTH_BASE[l] as usize) as u32, LENGTH_EXTRA[l]);
+        let d = DIST_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap();
+        self.write_code(d as u32, 5);
+        self.write((distance - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d]);
+    }
+
+    /// Flush the remaining bits, padding the last byte with zeros.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+/// DEFLATE the data in a single block with the fixed Huffman codes.
+fn deflate_fixed(data: &[u8], stride: usize, out: &mut Vec<u8>) {
+    let mut writer = BitWriter::new(out);
+    writer.write(1, 1); // final block
+    writer.write(1, 2); // fixed Huffman codes
+
+    let match_length = |pos: usize, distance: usize| -> usize {
+        if distance == 0 || distance > pos || distance > MAX_DISTANCE {
+            return 0;
+        }
+        let max = MAX_MATCH.min(data.len() - pos);
+        (0..max)
+            .take_while(|&k| data[pos + k] == data[pos + k - distance])
+            .count()
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = [1, stride]
+            .iter()
+            .map(|&distance| (match_length(pos, distance), distance))
+            .max_by_key(|&(length, _)| length)
+            .unwrap();
+        if length >= 3 {
+            writer.write_match(length, distance);
+            pos += length;
+        } else {
+            writer.write_symbol(data[pos] as u16);
+            pos += 1;
+        }
+    }
+
+    writer.write_symbol(256); // end of block
+    writer.flush();
+}
+
+/// Compute the CRC-32 (ISO 3309) of the given data, as used by PNG chunks.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Compute the Adler-32 checksum of the given data, as used by zlib.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
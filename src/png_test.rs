@@ -0,0 +1,191 @@
+use super::display::Display;
+use super::palette::Theme;
+use super::png::{adler32, crc32, encode, encode_with, Compression};
+
+#[test]
+fn checksums() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    assert_eq!(adler32(&[]), 1);
+}
+
+#[test]
+fn png_structure() {
+    let mut display = Display::new();
+    display.draw(10, 5, &[0xFF, 0x81, 0xFF]);
+    let png = encode(&display, &Theme::Amber.palette(), 3);
+
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    // IHDR is always the first chunk
+    assert_eq!(&png[8..16], &[0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+    assert_eq!(&png[16..20], &(64u32 * 3).to_be_bytes());
+    assert_eq!(&png[20..24], &(32u32 * 3).to_be_bytes());
+    // empty IEND chunk with its constant CRC
+    assert_eq!(
+        &png[png.len() - 12..],
+        &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+    );
+}
+
+/// LSB-first bit reader over a DEFLATE stream.
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// The position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bit(&mut self) -> usize {
+        let bit = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        bit as usize
+    }
+
+    /// Read an 'n' bits number, least significant bit first.
+    fn bits(&mut self, n: u8) -> usize {
+        (0..n).fold(0, |value, i| value | self.bit() << i)
+    }
+
+    /// Skip to the next byte and return its index.
+    fn align(&mut self) -> usize {
+        self.pos = self.pos.div_ceil(8) * 8;
+        self.pos / 8
+    }
+
+    /// Read a literal/length symbol with the fixed Huffman code, most
+    /// significant bit first.
+    fn fixed_symbol(&mut self) -> usize {
+        let mut code = 0;
+        for len in 1..=9 {
+            code = code << 1 | self.bit();
+            match (len, code) {
+                (7, 0x00..=0x17) => return 256 + code,
+                (8, 0x30..=0xBF) => return code - 0x30,
+                (8, 0xC0..=0xC7) => return 280 + code - 0xC0,
+                (9, 0x190..=0x1FF) => return 144 + code - 0x190,
+                _ => {}
+            }
+        }
+        panic!("invalid fixed Huffman code {:b}", code);
+    }
+}
+
+/// Decode a zlib stream made of stored and fixed Huffman blocks, checking
+/// its header and Adler-32 checksum.
+fn inflate(zlib: &[u8]) -> Vec<u8> {
+    const LENGTH_BASE: [usize; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const DIST_BASE: [usize; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    assert_eq!(zlib[0] & 0x0F, 8, "not deflate");
+    assert_eq!(((zlib[0] as u16) << 8 | zlib[1] as u16) % 31, 0);
+
+    let mut out = Vec::new();
+    let mut reader = BitReader {
+        data: &zlib[2..],
+        pos: 0,
+    };
+    loop {
+        let last = reader.bit() == 1;
+        match reader.bits(2) {
+            0 => {
+                let start = reader.align();
+                let data = reader.data;
+                let len = data[start] as usize | (data[start + 1] as usize) << 8;
+                let nlen = data[start + 2] as usize | (data[start + 3] as usize) << 8;
+                assert_eq!(len ^ 0xFFFF, nlen);
+                out.extend_from_slice(&data[start + 4..start + 4 + len]);
+                reader.pos = (start + 4 + len) * 8;
+            }
+            1 => loop {
+                let symbol = reader.fixed_symbol();
+                if symbol < 256 {
+                    out.push(symbol as u8);
+                    continue;
+                } else if symbol == 256 {
+                    break;
+                }
+                let l = symbol - 257;
+                let extra = if l < 8 || l == 28 { 0 } else { (l - 4) / 4 };
+                let length = LENGTH_BASE[l] + reader.bits(extra as u8);
+                let d = (0..5).fold(0, |code, _| code << 1 | reader.bit());
+                let extra = if d < 4 { 0 } else { (d - 2) / 2 };
+                let distance = DIST_BASE[d] + reader.bits(extra as u8);
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            },
+            kind => panic!("unexpected block type {}", kind),
+        }
+        if last {
+            break;
+        }
+    }
+
+    let end = 2 + reader.align();
+    assert_eq!(end + 4, zlib.len());
+    assert_eq!(&zlib[end..], &adler32(&out).to_be_bytes());
+    out
+}
+
+/// Split the given PNG image in (type, data) chunks, checking their CRC.
+fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < png.len() {
+        let mut len = [0; 4];
+        len.copy_from_slice(&png[pos..pos + 4]);
+        let len = u32::from_be_bytes(len) as usize;
+        let mut kind = [0; 4];
+        kind.copy_from_slice(&png[pos + 4..pos + 8]);
+        let crc = crc32(&png[pos + 4..pos + 8 + len]);
+        assert_eq!(&png[pos + 8 + len..pos + 12 + len], &crc.to_be_bytes());
+        chunks.push((kind, &png[pos + 8..pos + 8 + len]));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+#[test]
+fn png_compression() {
+    let mut display = Display::new();
+    display.draw(0, 0, &[0xAA, 0x55, 0xAA, 0x55]);
+    display.draw(60, 30, &[0xFF, 0x81]);
+    let palette = Theme::Classic.palette();
+    let stored = encode_with(&display, &palette, 4, Compression::Store);
+    let fixed = encode_with(&display, &palette, 4, Compression::Fixed);
+
+    // the raw scanlines are 64 * 4 + 1 bytes long : 128 rows of 257 bytes
+    // split in stored blocks of at most 65535 bytes
+    let raw_len = 128 * 257;
+    assert!(stored.len() > raw_len);
+    assert!(fixed.len() < raw_len / 10);
+
+    // both decode to the filtered scanlines of the scaled display
+    let mut raw = Vec::new();
+    for row in display.gfx.iter() {
+        let mut scanline = vec![0];
+        for &pixel in row.iter() {
+            scanline.extend_from_slice(&[pixel; 4]);
+        }
+        for _ in 0..4 {
+            raw.extend_from_slice(&scanline);
+        }
+    }
+    assert_eq!(raw.len(), raw_len);
+    for png in [stored, fixed].iter() {
+        let chunks = chunks(png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, vec![b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+        assert_eq!(
+            chunks[1].1,
+            &[0, 0, 0, 0xFF, 0xFF, 0xFF, 0xAA, 0xAA, 0xAA, 0x55, 0x55, 0x55]
+        );
+        assert!(inflate(chunks[2].1) == raw);
+    }
+}
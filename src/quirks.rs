@@ -0,0 +1,103 @@
+//! The behaviours which differ between the CHIP 8 interpreters, and on which
+//! the programs written for one of them may rely.
+//! Reference : https://github.com/chip-8/chip-8-database (quirks.json)
+
+/// How the load and store opcodes FX55 and FX65 modify the register I.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryIncrement {
+    /// I is set to I + X + 1 (original COSMAC VIP interpreter).
+    ByXPlusOne,
+    /// I is set to I + X (CHIP-48).
+    ByX,
+    /// I is left unchanged (SUPER-CHIP).
+    Unchanged,
+}
+
+/// The implementation options of the virtual machine.
+/// The default value is the behaviour expected by most modern ROMs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quirks {
+    /// Should the shifting opcodes 8XY6 and 8XYE shift VX in place ?
+    /// If false, VX is set to VY shifted, as on the COSMAC VIP.
+    pub shift: bool,
+    /// The behaviour of FX55 and FX65 regarding I.
+    pub memory_increment: MemoryIncrement,
+    /// Should BNNN jump to NNN + VX (read as BXNN) instead of NNN + V0 ?
+    pub jump: bool,
+    /// Should the logical opcodes 8XY1, 8XY2 and 8XY3 reset VF ?
+    pub logic: bool,
+    /// Should the sprites wrap around the screen edges ?
+    /// If false they are clipped. The starting position always wraps.
+    pub wrap: bool,
+    /// Should DXYN wait for the next frame before drawing, limiting the
+    /// program to one sprite per frame ?
+    pub vblank: bool,
+}
+
+impl Quirks {
+    /// The quirks of the original COSMAC VIP interpreter.
+    pub fn chip8() -> Quirks {
+        Quirks {
+            shift: false,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump: false,
+            logic: true,
+            wrap: false,
+            vblank: true,
+        }
+    }
+
+    /// The quirks of SUPER-CHIP 1.1.
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift: true,
+            memory_increment: MemoryIncrement::Unchanged,
+            jump: true,
+            logic: false,
+            wrap: false,
+            vblank: false,
+        }
+    }
+
+    /// The quirks of XO-CHIP, as implemented by Octo.
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift: false,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump: false,
+            logic: false,
+            wrap: true,
+            vblank: false,
+        }
+    }
+
+    /// The quirks expected by most modern ROMs.
+    pub fn modern() -> Quirks {
+        Quirks {
+            shift: true,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump: false,
+            logic: false,
+            wrap: true,
+            vblank: false,
+        }
+    }
+
+    /// Return the quirks preset with the given name ("chip8", "schip",
+    /// "xochip" or "modern"), if any.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match &name.to_lowercase()[..] {
+            "chip8" | "chip-8" | "vip" => Some(Quirks::chip8()),
+            "schip" | "superchip" => Some(Quirks::schip()),
+            "xochip" | "xo-chip" => Some(Quirks::xochip()),
+            "modern" => Some(Quirks::modern()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::modern()
+    }
+}
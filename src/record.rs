@@ -0,0 +1,215 @@
+/// Recording of the display as animated GIF images or uncompressed video
+/// streams, meant to be fed one 'Display' snapshot per 60 Hz frame.
+use std::io::{self, Write};
+
+use crate::display::Display;
+use crate::gif::GifEncoder;
+use crate::palette::Palette;
+use crate::vm::TIMERS_CLOCK;
+
+/// The supported recording formats.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RecordFormat {
+    /// Looping animated GIF.
+    Gif,
+    /// YUV4MPEG2 stream (4:4:4 chroma), as read by most video encoders.
+    Y4m,
+    /// Headerless stream of 24-bit RGB frames.
+    RawRgb,
+}
+
+impl RecordFormat {
+    /// Return the format with the given name ("gif", "y4m" or "rgb"), if any.
+    pub fn from_name(name: &str) -> Option<RecordFormat> {
+        match &name.to_lowercase()[..] {
+            "gif" => Some(RecordFormat::Gif),
+            "y4m" => Some(RecordFormat::Y4m),
+            "rgb" | "raw" => Some(RecordFormat::RawRgb),
+            _ => None,
+        }
+    }
+}
+
+/// The GIF-specific recording state.
+/// Consecutive identical frames are merged, and frames shorter than the
+/// minimum delay most GIF viewers honour are dropped.
+struct GifState<W: Write> {
+    encoder: GifEncoder<W>,
+    /// The frame waiting to be written, with the index of its first frame.
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+/// The minimum frame delay for GIF recordings, in hundredths of a second.
+const GIF_MIN_DELAY: u64 = 2;
+
+/// Return the time of the given 60 Hz frame, in hundredths of a second.
+fn frame_time(frame: u64) -> u64 {
+    (frame * 100 + TIMERS_CLOCK as u64 / 2) / TIMERS_CLOCK as u64
+}
+
+enum Output<W: Write> {
+    Gif(GifState<W>),
+    Stream(W),
+}
+
+/// Records display snapshots to the given writer in the given format.
+pub struct Recorder<W: Write> {
+    format: RecordFormat,
+    output: Output<W>,
+    palette: Palette,
+    scale: usize,
+    /// The width and height of the recording, in pixels.
+    size: (usize, usize),
+    /// The number of frames recorded so far.
+    frames: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording of displays of the given dimensions (in CHIP 8
+    /// pixels), each pixel being drawn as a 'scale' x 'scale' square.
+    pub fn new(
+        writer: W,
+        format: RecordFormat,
+        palette: &Palette,
+        display_size: (usize, usize),
+        scale: usize,
+    ) -> io::Result<Recorder<W>> {
+        let scale = scale.max(1);
+        let (w, h) = (display_size.0 * scale, display_size.1 * scale);
+        let output = match format {
+            RecordFormat::Gif => Output::Gif(GifState {
+                encoder: GifEncoder::new(writer, w as u16, h as u16, palette)?,
+                pending: None,
+            }),
+            RecordFormat::Y4m => {
+                let mut writer = writer;
+                writeln!(
+                    writer,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    w, h, TIMERS_CLOCK
+                )?;
+                Output::Stream(writer)
+            }
+            RecordFormat::RawRgb => Output::Stream(writer),
+        };
+        Ok(Recorder {
+            format,
+            output,
+            palette: palette.clone(),
+            scale,
+            size: (w, h),
+            frames: 0,
+        })
+    }
+
+    /// Return the number of frames recorded so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record the given display as the next 60 Hz frame.
+    pub fn record_frame(&mut self, display: &Display) -> io::Result<()> {
+        let pixels = self.scaled_pixels(display);
+        let frame = self.frames;
+        self.frames += 1;
+        let palette = &self.palette;
+        match self.output {
+            Output::Gif(ref mut state) => {
+                let flush = match state.pending {
+                    Some((ref previous, _)) if *previous == pixels => return Ok(()),
+                    Some((_, start)) => frame_time(frame) - frame_time(start) >= GIF_MIN_DELAY,
+                    None => false,
+                };
+                if flush {
+                    let (previous, start) = state.pending.take().unwrap();
+                    let delay = frame_time(frame) - frame_time(start);
+                    state.encoder.write_frame(&previous, delay as u16)?;
+                    state.pending = Some((pixels, frame));
+                } else {
+                    // keep the start of a too short frame for the new one
+                    let start = state.pending.as_ref().map_or(frame, |p| p.1);
+                    state.pending = Some((pixels, start));
+                }
+                Ok(())
+            }
+            Output::Stream(ref mut writer) => {
+                let colors = pixels.iter().map(|&pixel| palette.color(pixel));
+                match self.format {
+                    RecordFormat::Y4m => {
+                        let (mut y, mut u, mut v) = (Vec::new(), Vec::new(), Vec::new());
+                        for color in colors {
+                            let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+                            // ITU-R BT.601, studio swing
+                            y.push((16 + ((66 * r + 129 * g + 25 * b + 128) >> 8)) as u8);
+                            u.push((128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8)) as u8);
+                            v.push((128 + ((112 * r - 94 * g - 18 * b + 128) >> 8)) as u8);
+                        }
+                        writer.write_all(b"FRAME\n")?;
+                        writer.write_all(&y)?;
+                        writer.write_all(&u)?;
+                        writer.write_all(&v)
+                    }
+                    _ => {
+                        let mut rgb = Vec::with_capacity(pixels.len() * 3);
+                        for color in colors {
+                            rgb.extend_from_slice(&[color.r, color.g, color.b]);
+                        }
+                        writer.write_all(&rgb)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finish the recording and return the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.output {
+            Output::Gif(mut state) => {
+                if let Some((pixels, start)) = state.pending.take() {
+                    let delay = frame_time(self.frames) - frame_time(start);
+                    state
+                        .encoder
+                        .write_frame(&pixels, delay.max(GIF_MIN_DELAY) as u16)?;
+                }
+                state.encoder.finish()
+            }
+            Output::Stream(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+        }
+    }
+
+    /// Return the palette indices of the scaled display, row by row.
+    /// A display whose size changed since the start of the recording (e.g.
+    /// in the MEGA-CHIP mode) is resampled to the size of the recording.
+    fn scaled_pixels(&self, display: &Display) -> Vec<u8> {
+        let (width, height) = display.size();
+        let (w, h) = self.size;
+        if (width * self.scale, height * self.scale) != (w, h) {
+            let mut pixels = vec![0; w * h];
+            if width > 0 && height > 0 {
+                for (y, line) in pixels.chunks_mut(w).enumerate() {
+                    let row = &display.gfx[y * height / h];
+                    for (x, pixel) in line.iter_mut().enumerate() {
+                        *pixel = row[x * width / w];
+                    }
+                }
+            }
+            return pixels;
+        }
+        let mut pixels = Vec::with_capacity(w * h);
+        for row in display.gfx.iter() {
+            let mut line = Vec::with_capacity(w);
+            for &pixel in row.iter() {
+                for _ in 0..self.scale {
+                    line.push(pixel);
+                }
+            }
+            for _ in 0..self.scale {
+                pixels.extend_from_slice(&line);
+            }
+        }
+        pixels
+    }
+}
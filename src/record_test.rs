@@ -0,0 +1,60 @@
+use super::display::Display;
+use super::palette::Theme;
+use super::record::{RecordFormat, Recorder};
+
+#[test]
+fn record_y4m() {
+    let mut display = Display::new();
+    let mut recorder = Recorder::new(
+        Vec::new(),
+        RecordFormat::Y4m,
+        &Theme::Classic.palette(),
+        (64, 32),
+        2,
+    )
+    .unwrap();
+    for _ in 0..3 {
+        display.draw(0, 0, &[0x80]);
+        recorder.record_frame(&display).unwrap();
+    }
+    assert_eq!(recorder.frames(), 3);
+    let data = recorder.finish().unwrap();
+
+    let header = b"YUV4MPEG2 W128 H64 F60:1 Ip A1:1 C444\n";
+    assert_eq!(&data[..header.len()], &header[..]);
+    let frame_len = b"FRAME\n".len() + 128 * 64 * 3;
+    assert_eq!(data.len(), header.len() + 3 * frame_len);
+    // white then black luma at the top left corner of the first frames
+    assert_eq!(data[header.len() + 6], 235);
+    assert_eq!(data[header.len() + frame_len + 6], 16);
+}
+
+#[test]
+fn record_gif() {
+    let display = Display::new();
+    let mut recorder = Recorder::new(
+        Vec::new(),
+        RecordFormat::Gif,
+        &Theme::Classic.palette(),
+        (64, 32),
+        1,
+    )
+    .unwrap();
+    // identical frames are merged into a single GIF frame
+    for _ in 0..60 {
+        recorder.record_frame(&display).unwrap();
+    }
+    let data = recorder.finish().unwrap();
+
+    assert_eq!(&data[..6], b"GIF89a");
+    assert_eq!(&data[6..10], &[64, 0, 32, 0]);
+    assert_eq!(data.last(), Some(&0x3B));
+    let frames = data
+        .windows(4)
+        .filter(|w| w[0] == 0x21 && w[1] == 0xF9 && w[2] == 0x04)
+        .count();
+    assert_eq!(frames, 1);
+    let delay = data.windows(4).position(|w| w == [0x21, 0xF9, 0x04, 0x04]);
+    let delay = delay.unwrap() + 4;
+    assert_eq!(u16::from_le_bytes([data[delay], data[delay + 1]]), 100);
+}
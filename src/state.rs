@@ -0,0 +1,99 @@
+//! Helpers for the serialization of the virtual machine's state, used to
+//! implement save states.
+//! All the values are stored in big-endian order.
+
+/// Serializes values to a byte buffer.
+pub struct StateWriter {
+    buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Create a new writer, starting the buffer with the given magic bytes.
+    pub fn new(magic: &[u8]) -> StateWriter {
+        StateWriter {
+            buffer: magic.to_vec(),
+        }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Return the serialized state.
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+/// Deserializes values from a byte buffer written by a 'StateWriter'.
+/// Every read fails with a String explaining why if the buffer is too short.
+pub struct StateReader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// Create a new reader, checking that the buffer starts with the given
+    /// magic bytes.
+    pub fn new(buffer: &'a [u8], magic: &[u8]) -> Result<StateReader<'a>, String> {
+        if !buffer.starts_with(magic) {
+            return Err("invalid state : unrecognized format or version".to_owned());
+        }
+        Ok(StateReader {
+            buffer,
+            position: magic.len(),
+        })
+    }
+
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.buffer.len() - self.position < n {
+            return Err("invalid state : unexpected end of data".to_owned());
+        }
+        let bytes = &self.buffer[self.position..self.position + n];
+        self.position += n;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    /// Check that the whole buffer has been read.
+    pub fn finish(self) -> Result<(), String> {
+        if self.position == self.buffer.len() {
+            Ok(())
+        } else {
+            Err("invalid state : trailing data".to_owned())
+        }
+    }
+}
@@ -0,0 +1,108 @@
+/// The symbol tables of the programs, naming their subroutines in the
+/// backtraces of the call stack.
+/// A symbol file lists one symbol per line, as its hexadecimal address
+/// followed by its name, e.g. "2A4 draw_player" ; the text after '#' or ';'
+/// is a comment.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The symbols of a program, sorted by address.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SymbolTable {
+    symbols: Vec<(usize, String)>,
+}
+
+impl SymbolTable {
+    /// Create an empty symbol table.
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Read the symbols of the given symbol file contents.
+    /// If the operation fails, return a String explaining why.
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut table = SymbolTable::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split(&['#', ';'][..]).next().unwrap_or("");
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [] => {}
+                [address, name] => {
+                    let digits = address.trim_start_matches("0x").trim_start_matches('$');
+                    let address = usize::from_str_radix(digits, 16)
+                        .map_err(|_| format!("line {} : invalid address \"{}\"", n + 1, address))?;
+                    table.insert(address, name);
+                }
+                _ => {
+                    return Err(format!(
+                        "line {} : expected an address and a name, got \"{}\"",
+                        n + 1,
+                        line.trim()
+                    ))
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Read the symbol file at the given path, see 'SymbolTable::parse'.
+    /// If the operation fails, return a String explaining why.
+    pub fn from_file(filepath: &Path) -> Result<SymbolTable, String> {
+        let text = fs::read_to_string(filepath)
+            .map_err(|why| format!("cannot read \"{}\" : {}", filepath.display(), why))?;
+        SymbolTable::parse(&text).map_err(|why| format!("\"{}\" : {}", filepath.display(), why))
+    }
+
+    /// Name the given address, replacing its previous name if any.
+    pub fn insert(&mut self, address: usize, name: &str) {
+        match self.symbols.binary_search_by_key(&address, |&(a, _)| a) {
+            Ok(index) => self.symbols[index].1 = name.to_owned(),
+            Err(index) => self.symbols.insert(index, (address, name.to_owned())),
+        }
+    }
+
+    /// Get the number of symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Is the symbol table empty ?
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Return the symbol at or right before the given address, as its name
+    /// and the offset of the address from it.
+    pub fn lookup(&self, address: usize) -> Option<(&str, usize)> {
+        let index = match self.symbols.binary_search_by_key(&address, |&(a, _)| a) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let (start, ref name) = self.symbols[index];
+        Some((name, address - start))
+    }
+}
+
+/// A frame of the call stack : the address of the instruction running in it,
+/// i.e. the program counter for the innermost frame and the call
+/// instructions for the others, and its symbol if known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub address: usize,
+    /// The name of the symbol at or right before the address, and the offset
+    /// of the address from it.
+    pub symbol: Option<(String, usize)>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:0>3X}", self.address)?;
+        match self.symbol {
+            Some((ref name, 0)) => write!(f, " ({})", name),
+            Some((ref name, offset)) => write!(f, " ({}+{:X})", name, offset),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use super::symbols::SymbolTable;
+use super::vm::Chip8;
+
+#[test]
+fn symbol_table() {
+    let text = "# symbols of the test program\n\
+                200 main\n\
+                \n\
+                0x20A draw ; the player sprite\n\
+                $210 wait\n";
+    let symbols = SymbolTable::parse(text).unwrap();
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols.lookup(0x1FE), None);
+    assert_eq!(symbols.lookup(0x200), Some(("main", 0)));
+    assert_eq!(symbols.lookup(0x20E), Some(("draw", 4)));
+    assert_eq!(symbols.lookup(0xFFF), Some(("wait", 0xDEF)));
+
+    assert!(SymbolTable::parse("200 main\nmain 200\n").is_err());
+    assert!(SymbolTable::parse("200 two names\n").is_err());
+}
+
+#[test]
+fn backtrace() {
+    // main calls draw at 202, which calls wait at 20C
+    let rom = [
+        0x22, 0x0A, 0x12, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x60, 0x01, 0x22, 0x10, 0x00,
+        0xEE, 0x00, 0xEE,
+    ];
+    let mut vm = Chip8::new();
+    assert!(vm.load_bytes(&rom).is_ok());
+    for _ in 0..3 {
+        vm.emulate_cycle();
+    }
+    let addresses: Vec<usize> = vm.backtrace().iter().map(|frame| frame.address).collect();
+    assert_eq!(addresses, vec![0x210, 0x20C, 0x200]);
+    assert!(vm.backtrace().iter().all(|frame| frame.symbol.is_none()));
+
+    vm.set_symbols(Some(
+        SymbolTable::parse("200 main\n20A draw\n210 wait\n").unwrap(),
+    ));
+    let frames: Vec<String> = vm
+        .backtrace()
+        .iter()
+        .map(|frame| frame.to_string())
+        .collect();
+    assert_eq!(frames, vec!["210 (wait)", "20C (draw+2)", "200 (main)"]);
+}
@@ -0,0 +1,60 @@
+/// Host routines called by the programs with 0NNN, the calls to the machine
+/// code of the COSMAC VIP : the virtual machine runs the native handler
+/// registered on the address NNN, if any, with access to its whole state.
+/// No handler is registered by default, the classic programs being left
+/// alone ; 'install_debug_syscalls' registers the printf-style debugging
+/// routines below.
+use crate::bus::Bus;
+use crate::observer::Observer;
+use crate::vm::Chip8;
+
+/// A native handler of 0NNN.
+/// It is called with the program counter on the 0NNN instruction, then the
+/// virtual machine moves on to the next instruction unless the handler moved
+/// the program counter.
+pub type Syscall<B, O> = fn(&mut Chip8<B, O>);
+
+/// 0001 : log the value of V0.
+pub const PRINT_V0: u16 = 0x001;
+/// 0002 : log the registers.
+pub const DUMP_REGISTERS: u16 = 0x002;
+/// 0003 : log the V0 bytes at I as a string.
+pub const PRINT_STRING: u16 = 0x003;
+
+/// Register the debugging routines on their addresses.
+pub fn install_debug_syscalls<B: Bus, O: Observer>(vm: &mut Chip8<B, O>) {
+    vm.set_syscall(PRINT_V0, print_v0);
+    vm.set_syscall(DUMP_REGISTERS, dump_registers);
+    vm.set_syscall(PRINT_STRING, print_string);
+}
+
+/// Log the value of V0, in hexadecimal and decimal.
+pub fn print_v0<B: Bus, O: Observer>(vm: &mut Chip8<B, O>) {
+    let v0 = vm.register(0);
+    info!("[{:0>3X}] V0 = {:0>2X} ({})", vm.pc(), v0, v0);
+}
+
+/// Log the registers, the index register and the timers.
+pub fn dump_registers<B: Bus, O: Observer>(vm: &mut Chip8<B, O>) {
+    let registers: Vec<String> = (0..16)
+        .map(|x| format!("V{:X}={:0>2X}", x, vm.register(x)))
+        .collect();
+    info!(
+        "[{:0>3X}] {} I={:0>3X} DT={:0>2X} ST={:0>2X} SP={}",
+        vm.pc(),
+        registers.join(" "),
+        vm.index(),
+        vm.delay_timer,
+        vm.sound_timer,
+        vm.stack().len()
+    );
+}
+
+/// Log the V0 bytes stored at I as a string, the invalid UTF-8 sequences
+/// being replaced.
+pub fn print_string<B: Bus, O: Observer>(vm: &mut Chip8<B, O>) {
+    let memory = vm.memory();
+    let end = (vm.index() + vm.register(0) as usize).min(memory.len());
+    let bytes = &memory[vm.index().min(end)..end];
+    info!("[{:0>3X}] {}", vm.pc(), String::from_utf8_lossy(bytes));
+}
@@ -0,0 +1,51 @@
+use super::bus::Ram;
+use super::observer::NoObserver;
+use super::syscall::{install_debug_syscalls, DUMP_REGISTERS};
+use super::vm::Chip8;
+
+/// Set V2 to V0 + V1.
+fn add(vm: &mut Chip8<Ram, NoObserver>) {
+    let sum = vm.register(0).wrapping_add(vm.register(1));
+    vm.set_register(2, sum);
+}
+
+/// Skip the next instruction.
+fn skip(vm: &mut Chip8<Ram, NoObserver>) {
+    let pc = vm.pc();
+    vm.set_pc(pc + 4);
+}
+
+#[test]
+fn syscalls() {
+    let mut vm = Chip8::new();
+    let rom = [
+        0x60, 0x05, 0x61, 0x03, 0x01, 0x23, 0x04, 0x56, 0x60, 0xFF, 0x00, 0x01, 0x00, 0x02, 0xA2,
+        0x00, 0x00, 0x03, 0x07, 0x89,
+    ];
+    assert!(vm.load_bytes(&rom).is_ok());
+    vm.set_syscall(0x123, add);
+    vm.set_syscall(0x456, skip);
+    for _ in 0..4 {
+        vm.emulate_cycle();
+    }
+    assert_eq!(vm.register(2), 0x08);
+    assert_eq!(vm.pc(), 0x20A);
+
+    // the debugging routines are opt-in
+    vm.emulate_cycle();
+    assert_eq!(vm.pc(), 0x20A);
+    install_debug_syscalls(&mut vm);
+    for _ in 0..4 {
+        vm.emulate_cycle();
+    }
+    assert_eq!(vm.pc(), 0x212);
+    assert_eq!((vm.register(0), vm.index()), (0x05, 0x200));
+
+    // the other addresses are still not implemented
+    vm.emulate_cycle();
+    assert_eq!(vm.pc(), 0x212);
+    vm.remove_syscall(DUMP_REGISTERS);
+    vm.set_pc(0x20C);
+    vm.emulate_cycle();
+    assert_eq!(vm.pc(), 0x20C);
+}
@@ -1,34 +1,119 @@
 use rand::random;
 /// Core CPU implementation.
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
+use std::mem;
 use std::path::Path;
 
-use crate::display::{Display, FONT_SET};
+use crate::bus::{Bus, Ram};
+use crate::diagnostics::{Diagnostic, Diagnostics, StrictMode};
+use crate::display::{Display, HIRES_HEIGHT, HIRES_WIDTH};
+use crate::font::{Font, BIG_GLYPH_SIZE, SMALL_GLYPH_SIZE};
 use crate::keypad::{Keypad, Keystate};
+use crate::machine::Machine;
+use crate::megachip::{Framebuffer, MegaChip, Sound, MEGA_HEIGHT, MEGA_WIDTH};
+use crate::observer::{NoObserver, Observer};
+use crate::platform::{MemoryMap, Platform};
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::state::{StateReader, StateWriter};
+use crate::symbols::{Frame, SymbolTable};
+use crate::syscall::Syscall;
+
+mod chip8x;
+mod hires;
+mod megachip;
+mod schip;
 
 /// The default CPU clock, in Hz.
 pub const CPU_CLOCK: u32 = 600;
 /// The timers clock, in Hz.
 pub const TIMERS_CLOCK: u32 = 60;
+/// The default size of the memory, in bytes.
+pub const MEMORY_SIZE: usize = 4096;
+/// The default address where the programs are loaded and started.
+pub const PROGRAM_START: usize = 0x200;
+/// The address where the programs of the two-page hi-res interpreter really
+/// start, after the 0x1260 entry trick.
+pub const HIRES_PROGRAM_START: usize = 0x2C0;
 
 /// The index of the register used for the 'carry flag'.
 /// VF is used according to the CHIP 8 specifications.
 pub const FLAG: usize = 15;
-/// The size of the stack.
-const STACK_SIZE: usize = 16;
+/// The magic bytes starting a serialized state, including the format version.
+const STATE_MAGIC: &[u8] = b"CHIP8ST\x09";
+
+/// The errors which can occur while loading a ROM.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The ROM does not fit in memory between the load address and the next
+    /// area reserved by the interpreter.
+    TooLarge { size: usize, max: usize },
+    /// The ROM could not be read.
+    Io(io::Error),
+    /// The ROM is empty.
+    Empty,
+    /// The ROM container is invalid or not supported.
+    Format(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::TooLarge { size, max } => {
+                write!(f, "the ROM is too large ({} bytes, {} at most)", size, max)
+            }
+            LoadError::Io(ref why) => write!(f, "error while reading the ROM : {}", why),
+            LoadError::Empty => write!(f, "the ROM is empty"),
+            LoadError::Format(ref why) => write!(f, "invalid ROM file : {}", why),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io(ref why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(why: io::Error) -> LoadError {
+        LoadError::Io(why)
+    }
+}
+
+/// The progress of FX0A.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum KeyWait {
+    /// Not waiting.
+    Idle,
+    /// Waiting for a key press, to store the key in VX.
+    Press(u8),
+    /// Waiting for the release of the key pressed, to store it in VX, as
+    /// (X, key).
+    Release(u8, u8),
+}
 
 /// CHIP 8 virtual machine.
 /// The references used to implement this particular interpreter include :
 /// http://en.wikipedia.org/wiki/CHIP-8
 /// http://mattmik.com/chip8.html
 /// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
-pub struct Chip8 {
+/// The instructions access the memory through the given 'Bus', the plain RAM
+/// by default, and report their events to the given 'Observer', none by
+/// default.
+pub struct Chip8<B: Bus = Ram, O: Observer = NoObserver> {
     /// The current opcode.
     opcode: u16,
-    /// The chip's 4096 bytes of memory.
-    pub memory: [u8; 4096], // TEMPORARY pub for debug purposes
+    /// The memory bus, with the chip's memory of 4096 bytes by default.
+    bus: B,
+    /// The observer of the execution.
+    observer: O,
     /// The chip's 16 registers, from V0 to VF.
     /// VF is used for the 'carry flag'.
     v: [u8; 16],
@@ -36,11 +121,17 @@ pub struct Chip8 {
     i: usize,
     /// Program counter.
     pc: usize,
-    /// The stack, used for subroutine operations.
-    /// By default has 16 levels of nesting.
-    pub stack: [u16; STACK_SIZE],
-    /// Stack pointer.
-    pub sp: usize,
+    /// The stack, used for subroutine operations, unless kept in memory.
+    stack: Vec<u16>,
+    /// Stack pointer : the number of entries on the stack.
+    sp: usize,
+    /// The maximum depth of the stack, None if unlimited.
+    /// 16 levels of nesting by default.
+    stack_depth: Option<usize>,
+    /// Is the stack kept in memory, at the stack address of the memory map ?
+    stack_in_memory: bool,
+    /// The symbols of the program, naming the frames of the backtraces.
+    symbols: Option<SymbolTable>,
     // Timer registers, must be updated at 60 Hz by the emulator.
     pub delay_timer: u8,
     pub sound_timer: u8,
@@ -48,98 +139,618 @@ pub struct Chip8 {
     pub display: Display,
     /// Input component.
     pub keypad: Keypad,
-    /// Is the virtual machine waiting for a keypress ?
-    /// If so, when any key is pressed store its index in VX where X is
-    /// the value stored in this tuple.
-    pub wait_for_key: (bool, u8),
-    /// Implementation option.
-    /// Should the shifting opcodes 8XY6 and 8XYE use the original implementation,
-    /// i.e. set VX to VY shifted respectively right and left by one bit ?
-    /// If false, the VM will instead consider as many ROMs seem to do that Y=X.
-    /// See http://mattmik.com/chip8.html for more detail.
-    shift_op_use_vy: bool,
+    /// The second keypad of the CHIP-8X, read by EXF2 and EXF5.
+    pub second_keypad: Keypad,
+    /// The progress of FX0A, waiting for a key.
+    key_wait: KeyWait,
+    /// Should FX0A wait for the release of the key, as on the COSMAC VIP,
+    /// rather than resume as soon as it is pressed ?
+    key_wait_release: bool,
+    /// Has a frame started since the last draw ? Only used with the vblank
+    /// quirk.
+    vblank: bool,
+    /// Implementation options.
+    quirks: Quirks,
+    /// The address where the programs are loaded and started.
+    load_address: usize,
+    /// The layout of the memory.
+    memory_map: MemoryMap,
+    /// The font set, stored in memory at the addresses of the memory map.
+    font: Font,
+    /// The platform, giving the display size and the platform specific
+    /// opcodes.
+    platform: Platform,
+    /// The last byte written to the output port by FXF8 (CHIP-8X), e.g. the
+    /// pitch of the VP-595 sound board.
+    io_output: u8,
+    /// The byte read from the input port by FXFB (CHIP-8X).
+    io_input: u8,
+    /// Is the high resolution mode of SUPER-CHIP enabled by 00FF ?
+    hires: bool,
+    /// The user flags of SUPER-CHIP, saved and restored by FX75 and FX85.
+    /// They survive the resets, as the RPL flags of the HP 48 survive the
+    /// programs.
+    flags: [u8; 16],
+    /// Has the program exited with 00FD (SUPER-CHIP) ?
+    exited: bool,
+    /// The state of the MEGA-CHIP mode, if enabled by 0011.
+    megachip: Option<MegaChip>,
+    /// The native handlers of 0NNN, by address NNN.
+    syscalls: HashMap<u16, Syscall<B, O>>,
+    /// The state of the strict mode, if enabled.
+    diagnostics: Option<Diagnostics>,
+}
+
+/// Return the values (nibbles) A, B, C and D of the opcode 0xABCD, for
+/// easier matching.
+fn nibbles(op: u16) -> (u8, u8, u8, u8) {
+    (
+        ((op & 0xF000) >> 12) as u8,
+        ((op & 0x0F00) >> 8) as u8,
+        ((op & 0x00F0) >> 4) as u8,
+        (op & 0x000F) as u8,
+    )
 }
 
 /// Macro for handling invalid/unimplemented opcodes.
-/// As of now only prints a error message, could maybe panic in the future.
+/// As of now only logs a error message, could maybe panic in the future.
+/// NB : the standard output is left alone since it can carry a recording.
 macro_rules! op_not_implemented {
-    ($op: expr, $pc: expr) => {
-        println!(
+    ($vm: expr, $op: expr) => {
+        $vm.report_error(format!(
             "Not implemented opcode {:0>4X} at {:0>5X}",
-            $op as usize, $pc
-        );
+            $op as usize, $vm.pc
+        ))
     };
 }
 
 impl Chip8 {
-    /// Create and return a new, initialized Chip8 virtual machine.
+    /// Create and return a new, initialized Chip8 virtual machine, with the
+    /// memory layout of the modern interpreters.
     pub fn new() -> Chip8 {
+        Chip8::with_bus(Ram::new(MEMORY_SIZE))
+    }
+}
+
+impl<O: Observer> Chip8<Ram, O> {
+    /// Create and return a new, initialized Chip8 virtual machine reporting
+    /// to the given observer, with the memory layout of the modern
+    /// interpreters.
+    pub fn with_observer(observer: O) -> Chip8<Ram, O> {
+        Chip8::with_bus_and_observer(Ram::new(MEMORY_SIZE), observer)
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Create and return a new, initialized Chip8 virtual machine accessing
+    /// its memory through the given bus, with the memory layout of the modern
+    /// interpreters. The RAM of the bus is cleared to the size of the memory.
+    pub fn with_bus(bus: B) -> Chip8<B> {
+        Chip8::with_bus_and_observer(bus, NoObserver)
+    }
+}
+
+impl<B: Bus, O: Observer> Chip8<B, O> {
+    /// Create and return a new, initialized Chip8 virtual machine accessing
+    /// its memory through the given bus and reporting to the given observer.
+    /// See 'Chip8::with_bus'.
+    pub fn with_bus_and_observer(mut bus: B, observer: O) -> Chip8<B, O> {
+        let ram = bus.ram_mut();
+        ram.clear();
+        ram.resize(MEMORY_SIZE, 0);
         let mut chip8 = Chip8 {
             opcode: 0u16,
-            memory: [0u8; 4096],
+            bus,
+            observer,
             v: [0u8; 16],
             i: 0usize,
             pc: 0usize,
-            stack: [0u16; STACK_SIZE],
+            stack: Vec::new(),
             sp: 0usize,
+            stack_depth: Some(Platform::Modern.stack_depth()),
+            stack_in_memory: false,
+            symbols: None,
             delay_timer: 0u8,
             sound_timer: 0u8,
             display: Display::new(),
             keypad: Keypad::new(),
-            wait_for_key: (false, 0x0),
-            shift_op_use_vy: false,
+            second_keypad: Keypad::new(),
+            key_wait: KeyWait::Idle,
+            key_wait_release: true,
+            vblank: true,
+            quirks: Quirks::default(),
+            load_address: PROGRAM_START,
+            memory_map: MemoryMap::default(),
+            font: Font::default(),
+            platform: Platform::Modern,
+            io_output: 0,
+            io_input: 0,
+            hires: false,
+            flags: [0; 16],
+            exited: false,
+            megachip: None,
+            syscalls: HashMap::new(),
+            diagnostics: None,
         };
-        // load the font set in memory in the space [0x0, 0x200[ = [0, 80[
-        for i in 0..80 {
-            chip8.memory[i] = FONT_SET[i];
-        }
+        chip8.load_font();
         // the program space starts at 0x200
-        chip8.pc = 0x200;
+        chip8.pc = PROGRAM_START;
 
         chip8
     }
 
+    /// Load the font set in memory at the addresses given by the memory map.
+    fn load_font(&mut self) {
+        let memory = self.bus.ram_mut();
+        let start = self.memory_map.font_address;
+        memory[start..start + self.font.small.len()].copy_from_slice(&self.font.small);
+        let start = self.memory_map.big_font_address;
+        memory[start..start + self.font.big.len()].copy_from_slice(&self.font.big);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            let start = self.memory_map.font_address;
+            diagnostics.data(start, start + self.font.small.len());
+            let start = self.memory_map.big_font_address;
+            diagnostics.data(start, start + self.font.big.len());
+        }
+    }
+
+    /// Check that the given font fits in memory with the given memory map.
+    /// If it does not, return a String explaining why.
+    fn check_font_layout(memory_map: &MemoryMap, font: &Font) -> Result<(), String> {
+        let fonts = [
+            ("font", memory_map.font_address, font.small.len()),
+            ("big font", memory_map.big_font_address, font.big.len()),
+        ];
+        for &(name, address, size) in fonts.iter() {
+            if address + size > memory_map.memory_size {
+                return Err(format!(
+                    "the {} at {:0>3X} does not fit in memory",
+                    name, address
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the font set.
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Set the font set and store it in memory.
+    /// If the operation fails, return a String explaining why and leave the
+    /// virtual machine untouched.
+    pub fn set_font(&mut self, font: Font) -> Result<(), String> {
+        Self::check_font_layout(&self.memory_map, &font)?;
+        self.font = font;
+        self.load_font();
+        Ok(())
+    }
+
+    /// Get the layout of the memory.
+    pub fn memory_map(&self) -> &MemoryMap {
+        &self.memory_map
+    }
+
+    /// Set the layout of the memory : clear the memory to its new size, load
+    /// the font and set the load address to the entry point.
+    /// Must be called before loading the program.
+    /// If the operation fails, return a String explaining why and leave the
+    /// virtual machine untouched.
+    pub fn set_memory_map(&mut self, memory_map: MemoryMap) -> Result<(), String> {
+        Self::check_font_layout(&memory_map, &self.font)?;
+        if memory_map.entry_point >= memory_map.memory_size {
+            return Err(format!(
+                "the entry point {:0>3X} is outside the memory",
+                memory_map.entry_point
+            ));
+        }
+        let memory = self.bus.ram_mut();
+        memory.clear();
+        memory.resize(memory_map.memory_size, 0);
+        self.load_address = memory_map.entry_point;
+        self.memory_map = memory_map;
+        if let Some(mode) = self.diagnostics.as_ref().map(Diagnostics::mode) {
+            self.init_diagnostics(mode);
+        }
+        self.load_font();
+        self.reset();
+        Ok(())
+    }
+
+    /// Enable the strict mode with the given options, reporting the bugs of
+    /// the program (see the 'diagnostics' module), or disable it with None.
+    /// Must be called before loading the program, the memory being filled
+    /// with random bytes if asked.
+    pub fn set_strict_mode(&mut self, mode: Option<StrictMode>) {
+        match mode {
+            Some(mode) => {
+                self.init_diagnostics(mode);
+                self.load_font();
+            }
+            None => self.diagnostics = None,
+        }
+    }
+
+    /// Start the strict mode over the whole memory, uninitialised.
+    fn init_diagnostics(&mut self, mode: StrictMode) {
+        if mode.random_ram {
+            for byte in self.bus.ram_mut().iter_mut() {
+                *byte = random::<u8>();
+            }
+        }
+        self.diagnostics = Some(Diagnostics::new(mode, &self.memory_map));
+    }
+
+    /// Get the state of the strict mode, if enabled.
+    pub fn diagnostics(&self) -> Option<&Diagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// Get the memory, as the RAM behind the bus.
+    pub fn memory(&self) -> &[u8] {
+        self.bus.ram()
+    }
+
+    /// Get the memory, as the RAM behind the bus, to modify it without going
+    /// through the bus.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.bus.ram_mut()
+    }
+
+    /// Get the memory bus.
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Get the memory bus, e.g. to inspect the devices mapped on it.
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Get the observer.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Get the observer, e.g. to collect its results.
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Register the native handler of 0NNN on the given address NNN,
+    /// replacing the previous one if any. See the 'syscall' module.
+    pub fn set_syscall(&mut self, address: u16, handler: Syscall<B, O>) {
+        self.syscalls.insert(address & 0x0FFF, handler);
+    }
+
+    /// Unregister the native handler of 0NNN on the given address NNN.
+    pub fn remove_syscall(&mut self, address: u16) {
+        self.syscalls.remove(&(address & 0x0FFF));
+    }
+
+    /// Get the platform.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Set the platform : its memory layout (see 'set_memory_map'), font,
+    /// display size and stack depth, and the opcodes specific to it. The
+    /// quirks are left alone.
+    /// Must be called before loading the program.
+    /// If the operation fails, return a String explaining why and leave the
+    /// virtual machine untouched.
+    pub fn set_platform(&mut self, platform: Platform) -> Result<(), String> {
+        let font = mem::replace(&mut self.font, platform.font());
+        let previous = mem::replace(&mut self.platform, platform);
+        if let Err(why) = self.set_memory_map(platform.memory_map()) {
+            self.font = font;
+            self.platform = previous;
+            return Err(why);
+        }
+        self.stack_depth = Some(platform.stack_depth());
+        Ok(())
+    }
+
+    /// Get the maximum depth of the stack, None if unlimited.
+    pub fn stack_depth(&self) -> Option<usize> {
+        self.stack_depth
+    }
+
+    /// Set the maximum depth of the stack, or None for an unlimited stack,
+    /// e.g. to debug deep recursions. The calls past it are reported as
+    /// errors and not executed.
+    pub fn set_stack_depth(&mut self, depth: Option<usize>) {
+        self.stack_depth = depth;
+    }
+
+    /// Is the stack kept in memory ?
+    pub fn is_stack_in_memory(&self) -> bool {
+        self.stack_in_memory
+    }
+
+    /// Keep the stack in memory at the stack address of the memory map, as
+    /// the COSMAC VIP does, so that the programs can read and overwrite it,
+    /// or out of their reach (the default).
+    /// Must be called before loading the program : the stack is emptied.
+    pub fn set_stack_in_memory(&mut self, in_memory: bool) {
+        self.stack_in_memory = in_memory;
+        self.stack.clear();
+        self.sp = 0;
+    }
+
+    /// Get the entries of the stack, i.e. the addresses of the pending call
+    /// instructions, from the oldest to the latest.
+    pub fn stack(&self) -> Vec<u16> {
+        if !self.stack_in_memory {
+            return self.stack.clone();
+        }
+        let memory = self.memory();
+        (0..self.sp)
+            .map(|n| {
+                let address = self.stack_entry_address(n);
+                (memory[address] as u16) << 8 | memory[address + 1] as u16
+            })
+            .collect()
+    }
+
+    /// Replace the entries of the stack, from the oldest to the latest.
+    /// If the operation fails, return a String explaining why and leave the
+    /// virtual machine untouched.
+    pub fn set_stack(&mut self, stack: &[u16]) -> Result<(), String> {
+        self.check_stack_depth(stack.len())?;
+        self.write_stack(stack);
+        Ok(())
+    }
+
+    /// Replace the entries of the stack, which must fit.
+    fn write_stack(&mut self, stack: &[u16]) {
+        self.sp = stack.len();
+        if !self.stack_in_memory {
+            self.stack = stack.to_vec();
+            return;
+        }
+        for (n, &addr) in stack.iter().enumerate() {
+            let address = self.stack_entry_address(n);
+            let memory = self.bus.ram_mut();
+            memory[address] = (addr >> 8) as u8;
+            memory[address + 1] = addr as u8;
+        }
+    }
+
+    /// Return the address of the given entry of the stack kept in memory.
+    fn stack_entry_address(&self, n: usize) -> usize {
+        self.memory_map.stack_address - 2 * n
+    }
+
+    /// Check that a stack of the given depth fits within the maximum depth
+    /// and, when kept in memory, in the memory.
+    /// If it does not, return a String explaining why.
+    fn check_stack_depth(&self, depth: usize) -> Result<(), String> {
+        if let Some(max) = self.stack_depth {
+            if depth > max {
+                return Err(format!("stack overflow ({} levels at most)", max));
+            }
+        }
+        let address = self.memory_map.stack_address;
+        if self.stack_in_memory
+            && depth > 0
+            && (2 * (depth - 1) > address || address + 2 > self.memory().len())
+        {
+            return Err(format!(
+                "stack overflow (out of the memory below {:0>3X})",
+                address
+            ));
+        }
+        Ok(())
+    }
+
+    /// Push the given address on the stack, which must have room for it.
+    /// The stack kept in memory is written through the bus, see
+    /// 'write_interpreter'.
+    fn push(&mut self, addr: u16) {
+        if self.stack_in_memory {
+            let address = self.stack_entry_address(self.sp);
+            self.write_interpreter(address, (addr >> 8) as u8);
+            self.write_interpreter(address + 1, addr as u8);
+        } else {
+            self.stack.push(addr);
+        }
+        self.sp += 1;
+    }
+
+    /// Pop the latest address from the stack, or return None if it is
+    /// empty. The stack kept in memory is read through the bus.
+    fn pop(&mut self) -> Option<u16> {
+        if self.sp == 0 {
+            return None;
+        }
+        self.sp -= 1;
+        if self.stack_in_memory {
+            let address = self.stack_entry_address(self.sp);
+            Some((self.bus.read(address) as u16) << 8 | self.bus.read(address + 1) as u16)
+        } else {
+            self.stack.pop()
+        }
+    }
+
+    /// Get the symbols of the program, if any.
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        self.symbols.as_ref()
+    }
+
+    /// Set the symbols of the program naming the frames of the backtraces,
+    /// or remove them with None.
+    pub fn set_symbols(&mut self, symbols: Option<SymbolTable>) {
+        self.symbols = symbols;
+    }
+
+    /// Return the call stack, from the innermost frame, at the program
+    /// counter, to the outermost one, at the first pending call instruction.
+    /// The frames are named after the symbols of the program, if any.
+    pub fn backtrace(&self) -> Vec<Frame> {
+        let mut addresses = vec![self.pc];
+        addresses.extend(self.stack().iter().rev().map(|&addr| addr as usize));
+        addresses
+            .into_iter()
+            .map(|address| Frame {
+                address,
+                symbol: self
+                    .symbols
+                    .as_ref()
+                    .and_then(|symbols| symbols.lookup(address))
+                    .map(|(name, offset)| (name.to_owned(), offset)),
+            })
+            .collect()
+    }
+
     /// Reinitialize the virtual machine's state but keep the loaded program
-    /// in memory.
+    /// in memory. The font is restored and the program counter points to
+    /// the load address again.
     pub fn reset(&mut self) {
+        self.load_font();
         self.opcode = 0u16;
         self.v = [0u8; 16];
         self.i = 0usize;
-        self.pc = 0x200;
-        self.stack = [0u16; STACK_SIZE];
+        self.pc = self.load_address;
+        self.stack.clear();
         self.sp = 0usize;
         self.delay_timer = 0u8;
         self.sound_timer = 0u8;
-        self.display = Display::new();
+        let (width, height) = self.platform.display_size();
+        self.display = Display::with_size(width, height);
+        if self.platform == Platform::Chip8X {
+            self.display.enable_colors();
+        }
         self.keypad = Keypad::new();
-        self.wait_for_key = (false, 0x0);
+        self.second_keypad = Keypad::new();
+        self.key_wait = KeyWait::Idle;
+        self.vblank = true;
+        self.io_output = 0;
+        self.hires = false;
+        self.exited = false;
+        self.megachip = None;
+    }
+
+    /// Get the state of the MEGA-CHIP mode, if enabled.
+    pub fn megachip(&self) -> Option<&MegaChip> {
+        self.megachip.as_ref()
+    }
+
+    /// Get the last byte written to the output port by FXF8 (CHIP-8X).
+    pub fn io_output(&self) -> u8 {
+        self.io_output
+    }
+
+    /// Set the byte read from the input port by FXFB (CHIP-8X).
+    pub fn set_io_input(&mut self, value: u8) {
+        self.io_input = value;
     }
 
-    /// Set the shift_op_use_vy flag.
+    /// Get the address where the programs are loaded and started.
+    pub fn load_address(&self) -> usize {
+        self.load_address
+    }
+
+    /// Set the address where the next programs will be loaded and started.
+    /// Must be called before loading the program.
+    pub fn set_load_address(&mut self, address: usize) {
+        self.load_address = address.min(self.memory().len());
+        self.pc = self.load_address;
+    }
+
+    /// Get the implementation options.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Set the implementation options.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Should the shifting opcodes 8XY6 and 8XYE use the original implementation,
+    /// i.e. set VX to VY shifted respectively right and left by one bit ?
+    /// If false, the VM will instead consider as many ROMs seem to do that Y=X.
+    /// See http://mattmik.com/chip8.html for more detail.
     pub fn should_shift_op_use_vy(&mut self, b: bool) {
-        self.shift_op_use_vy = b;
+        self.quirks.shift = !b;
     }
 
-    /// Is the CPU waiting for a key press ?
+    /// Is the CPU waiting for a key with FX0A ?
     pub fn is_waiting_for_key(&self) -> bool {
-        self.wait_for_key.0
+        self.key_wait != KeyWait::Idle
     }
 
-    /// Called by the emulator application to inform the virtual machine
-    /// waiting for a key pressed that a key has been pressed.
-    pub fn end_wait_for_key(&mut self, key_index: usize) {
-        if !self.is_waiting_for_key() {
-            warn!(concat!(
-                "Chip8::end_wait_for_key_press called but the VM ",
-                "wasn't waiting for a key press - ignoring"
-            ));
-            return;
+    /// Does FX0A wait for the release of the key ?
+    pub fn key_wait_release(&self) -> bool {
+        self.key_wait_release
+    }
+
+    /// Should FX0A wait for the release of the key, as on the COSMAC VIP (the
+    /// default), or resume as soon as it is pressed ?
+    pub fn set_key_wait_release(&mut self, release: bool) {
+        self.key_wait_release = release;
+    }
+
+    /// Move FX0A on with the changes of the keypad state : store the key in
+    /// VX and go to the next instruction once it is pressed, then released
+    /// if asked.
+    fn update_key_wait(&mut self) {
+        let (x, key) = match self.key_wait {
+            KeyWait::Idle => return,
+            KeyWait::Press(x) => match self.keypad.take_pressed() {
+                Some(key) => (x, key as u8),
+                None => return,
+            },
+            KeyWait::Release(x, key) => (x, key),
+        };
+        if let Keystate::Pressed = self.keypad.get_key_state(key as usize) {
+            if self.key_wait_release {
+                self.key_wait = KeyWait::Release(x, key);
+                return;
+            }
         }
-        self.v[self.wait_for_key.1 as usize] = key_index as u8;
-        self.wait_for_key.0 = false;
+        self.v[x as usize] = key;
+        self.key_wait = KeyWait::Idle;
         self.pc += 2;
     }
 
+    /// Decrement the delay and sound timers if they are not zero.
+    /// Must be called by the emulation loop at 'TIMERS_CLOCK' Hz.
+    pub fn update_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.set_sound_timer(self.sound_timer - 1);
+        }
+        self.vblank = true;
+    }
+
+    /// Set the sound timer, reporting the start and the end of the sound.
+    fn set_sound_timer(&mut self, value: u8) {
+        match (self.sound_timer, value) {
+            (0, 0) => {}
+            (0, _) => self.observer.on_sound_start(),
+            (_, 0) => self.observer.on_sound_stop(),
+            _ => {}
+        }
+        self.sound_timer = value;
+    }
+
+    /// Log the given error of the current instruction and report it.
+    /// With the symbols of the program, the backtrace is logged too.
+    fn report_error(&mut self, message: String) {
+        error!("{}", message);
+        if self.symbols.is_some() {
+            for frame in self.backtrace() {
+                error!("    at {}", frame);
+            }
+        }
+        self.observer.on_error(self.pc, &message);
+    }
+
     /// Get the value stored in the register VX.
     pub fn register(&self, x: usize) -> u8 {
         self.v[x]
@@ -155,66 +766,382 @@ impl Chip8 {
         self.pc
     }
 
-    /// Load a Chip8 rom from the given filepath.
-    /// If the operation fails, return a String explaining why.
-    pub fn load(&mut self, filepath: &Path) -> Option<String> {
-        let file = match File::open(filepath) {
-            Ok(f) => f,
-            Err(ref why) => {
-                return Some(format!(
-                    "couldn't open rom file \"{}\" : {}",
-                    filepath.display(),
-                    Error::description(why)
-                ));
+    /// Set the value stored in the register VX.
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+    }
+
+    /// Set the index register.
+    pub fn set_index(&mut self, i: usize) {
+        self.i = i;
+    }
+
+    /// Set the program counter value.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    /// Serialize the whole state of the virtual machine (memory, registers,
+    /// timers, display and keypad), for instance to implement save states.
+    /// The implementation options are not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(STATE_MAGIC);
+        state.write_u16(self.opcode);
+        state.write_u32(self.memory().len() as u32);
+        state.write_bytes(self.memory());
+        state.write_bytes(&self.v);
+        state.write_u32(self.i as u32);
+        state.write_u32(self.pc as u32);
+        // padded to the maximum depth, for states of a constant size
+        let stack = self.stack();
+        let capacity = self.stack_depth.unwrap_or(0).max(stack.len());
+        state.write_u32(capacity as u32);
+        for n in 0..capacity {
+            state.write_u16(stack.get(n).cloned().unwrap_or(0));
+        }
+        state.write_u32(stack.len() as u32);
+        state.write_u8(self.delay_timer);
+        state.write_u8(self.sound_timer);
+        state.write_bool(self.hires);
+        state.write_u16(self.display.width() as u16);
+        state.write_u16(self.display.height() as u16);
+        for row in self.display.gfx.iter() {
+            state.write_bytes(row);
+        }
+        state.write_bool(self.display.colors.is_some());
+        if let Some(ref colors) = self.display.colors {
+            state.write_u8(colors.background);
+            for row in colors.zones.iter() {
+                state.write_bytes(row);
             }
+        }
+        state.write_u8(self.io_output);
+        state.write_bool(self.megachip.is_some());
+        if let Some(ref megachip) = self.megachip {
+            megachip.save(&mut state);
+            if let Some(ref framebuffer) = self.display.framebuffer {
+                framebuffer.save(&mut state);
+            }
+        }
+        for key in 0..16 {
+            state.write_bool(match self.keypad.get_key_state(key) {
+                Keystate::Pressed => true,
+                Keystate::Released => false,
+            });
+        }
+        let (wait, x, key) = match self.key_wait {
+            KeyWait::Idle => (0, 0, 0),
+            KeyWait::Press(x) => (1, x, 0),
+            KeyWait::Release(x, key) => (2, x, key),
         };
-        for (i, b) in file.bytes().enumerate() {
-            //if b.is_none() /* EOF */ { break; }
-            match b {
-                Ok(byte) => self.memory[self.pc + i] = byte,
-                Err(e) => {
-                    return Some(format!("error while reading ROM : {}", e.to_string()));
+        state.write_u8(wait);
+        state.write_u8(x);
+        state.write_u8(key);
+        state.write_u16(self.keypad.pressed());
+        state.write_bytes(&self.flags);
+        state.write_bool(self.exited);
+        state.finish()
+    }
+
+    /// Restore a state serialized by 'save_state'.
+    /// If the operation fails, return a String explaining why and leave the
+    /// virtual machine untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut state = StateReader::new(data, STATE_MAGIC)?;
+        let opcode = state.read_u16()?;
+        let memory_size = state.read_u32()? as usize;
+        if memory_size != self.memory().len() {
+            return Err(format!(
+                "invalid state : {} bytes of memory instead of {}",
+                memory_size,
+                self.memory().len()
+            ));
+        }
+        let memory = state.read_bytes(memory_size)?;
+        let v = state.read_bytes(self.v.len())?;
+        let i = state.read_u32()? as usize;
+        let pc = state.read_u32()? as usize;
+        let capacity = state.read_u32()? as usize;
+        let mut stack = Vec::new();
+        for _ in 0..capacity {
+            stack.push(state.read_u16()?);
+        }
+        let sp = state.read_u32()? as usize;
+        if sp > capacity {
+            return Err(format!(
+                "invalid state : stack pointer {} out of bounds",
+                sp
+            ));
+        }
+        stack.truncate(sp);
+        self.check_stack_depth(sp)
+            .map_err(|why| format!("invalid state : {}", why))?;
+        let delay_timer = state.read_u8()?;
+        let sound_timer = state.read_u8()?;
+        let hires = state.read_bool()?;
+        if hires && !self.platform.has_schip() {
+            return Err(format!(
+                "invalid state : SUPER-CHIP high resolution on the {} platform",
+                self.platform
+            ));
+        }
+        let display_size = (state.read_u16()? as usize, state.read_u16()? as usize);
+        let expected_size = if hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            self.platform.display_size()
+        };
+        let megachip_size = (MEGA_WIDTH, MEGA_HEIGHT);
+        if display_size != expected_size
+            && !(self.platform == Platform::MegaChip && display_size == megachip_size)
+        {
+            return Err(format!(
+                "invalid state : {}x{} display instead of {}x{}",
+                display_size.0, display_size.1, expected_size.0, expected_size.1
+            ));
+        }
+        let mut display = Display::with_size(display_size.0, display_size.1);
+        for row in display.gfx.iter_mut() {
+            let len = row.len();
+            row.copy_from_slice(state.read_bytes(len)?);
+        }
+        if state.read_bool()? {
+            display.enable_colors();
+            if let Some(ref mut colors) = display.colors {
+                colors.background = state.read_u8()? % 4;
+                for row in colors.zones.iter_mut() {
+                    let len = row.len();
+                    row.copy_from_slice(state.read_bytes(len)?);
+                    for zone in row.iter_mut() {
+                        *zone &= 0x7;
+                    }
                 }
             }
         }
-        None
+        let io_output = state.read_u8()?;
+        let megachip = if state.read_bool()? {
+            if display_size != megachip_size {
+                return Err("invalid state : MEGA-CHIP mode with a small display".to_owned());
+            }
+            let megachip = MegaChip::load(&mut state)?;
+            display.framebuffer = Some(Framebuffer::load(&mut state, MEGA_WIDTH, MEGA_HEIGHT)?);
+            Some(megachip)
+        } else {
+            None
+        };
+        let mut keypad = Keypad::new();
+        for key in 0..16 {
+            if state.read_bool()? {
+                keypad.set_key_state(key, Keystate::Pressed);
+            }
+        }
+        let key_wait = match (
+            state.read_u8()?,
+            state.read_u8()? & 0xF,
+            state.read_u8()? & 0xF,
+        ) {
+            (0, _, _) => KeyWait::Idle,
+            (1, x, _) => KeyWait::Press(x),
+            (2, x, key) => KeyWait::Release(x, key),
+            (wait, _, _) => return Err(format!("invalid state : key wait {}", wait)),
+        };
+        keypad.set_pressed(state.read_u16()?);
+        let mut flags = [0; 16];
+        flags.copy_from_slice(state.read_bytes(16)?);
+        let exited = state.read_bool()?;
+        state.finish()?;
+
+        self.opcode = opcode;
+        self.memory_mut().copy_from_slice(memory);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.loaded(0, memory_size);
+        }
+        self.v.copy_from_slice(v);
+        self.i = i;
+        self.pc = pc;
+        self.write_stack(&stack);
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.display = display;
+        self.keypad = keypad;
+        self.key_wait = key_wait;
+        self.io_output = io_output;
+        self.hires = hires;
+        self.flags = flags;
+        self.exited = exited;
+        self.megachip = megachip;
+        Ok(())
+    }
+
+    /// Return the maximum size of a program loaded at the load address :
+    /// up to the next area reserved by the interpreter or the end of the
+    /// memory.
+    pub fn max_program_size(&self) -> usize {
+        self.memory_map
+            .program_end(self.load_address)
+            .min(self.memory().len())
+            .saturating_sub(self.load_address)
+    }
+
+    /// Load the ROM file at the given path in memory at the load address.
+    pub fn load(&mut self, filepath: &Path) -> Result<(), LoadError> {
+        let file = File::open(filepath)?;
+        let size = file.metadata()?.len() as usize;
+        let max = self.max_program_size();
+        if size > max {
+            return Err(LoadError::TooLarge { size, max });
+        }
+        self.load_reader(file)
+    }
+
+    /// Read a ROM until the end of the given reader and load it in memory at
+    /// the load address. The reader is not consumed past the maximum size.
+    pub fn load_reader<R: Read>(&mut self, reader: R) -> Result<(), LoadError> {
+        let max = self.max_program_size();
+        let mut rom = Vec::new();
+        reader.take(max as u64 + 1).read_to_end(&mut rom)?;
+        if rom.len() > max {
+            // the reported size is only a lower bound
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.load_bytes(&rom)
     }
 
-    /// Emulate a Chip8 CPU cycle.
+    /// Load the given ROM in memory at the load address, and point the
+    /// program counter to it.
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        let max = self.max_program_size();
+        if rom.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        if rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        let start = self.load_address;
+        self.memory_mut()[start..start + rom.len()].copy_from_slice(rom);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.loaded(start, start + rom.len());
+        }
+        self.pc = self.load_address;
+        Ok(())
+    }
+
+    /// Emulate a Chip8 CPU cycle. While waiting for a key with FX0A, the
+    /// cycle only checks the keypad.
     /// Return true if the loaded program is done.
     pub fn emulate_cycle(&mut self) -> bool {
-        // Is the program finished ?
-        if self.pc >= 4094 {
-            return true;
+        if self.is_waiting_for_key() {
+            self.update_key_wait();
+            return false;
+        }
+        match self.fetch() {
+            Some(op) => {
+                // println!("{:0>4X} {:0>4X}", op, self.pc); // DEBUG
+                Machine::execute(self, op);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Fetch the opcode at the program counter through the bus.
+    /// Return None if the program is finished, i.e. past the end of the memory
+    /// or exited with 00FD.
+    fn fetch(&mut self) -> Option<u16> {
+        if self.exited || self.pc >= self.memory().len() - 2 {
+            return None;
         }
-        // Fetch and execute the opcode to execute ;
         // an opcode being 2 bytes long, we need to read 2 bytes from memory
-        let op = (self.memory[self.pc] as u16) << 8 | (self.memory[self.pc + 1] as u16);
+        Some((self.bus.read(self.pc) as u16) << 8 | (self.bus.read(self.pc + 1) as u16))
+    }
 
-        // println!("{:0>4X} {:0>4X}", self.opcode, self.pc); // DEBUG
-        self.opcode = op;
-        self.execute_opcode(op);
-        false
+    /// Write the given byte at the given address through the bus, reporting
+    /// the write. The addresses outside the RAM, e.g. a device mapped by the
+    /// bus, are reported as previously holding 0.
+    /// In the strict mode, the writes outside the memory are dropped.
+    fn write(&mut self, address: usize, value: u8) {
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            if !diagnostics.write(address) {
+                return;
+            }
+        }
+        let old = self.bus.ram().get(address).copied().unwrap_or(0);
+        self.observer.on_memory_write(address, old, value);
+        self.bus.write(address, value);
     }
 
-    /// Execute a single opcode.
+    /// Write the given byte at the given address through the bus on behalf
+    /// of the interpreter, e.g. the stack kept in memory, reporting the
+    /// write. The strict mode marks the byte as initialised data, without
+    /// reporting the write to the interpreter area.
+    fn write_interpreter(&mut self, address: usize, value: u8) {
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.data(address, address + 1);
+        }
+        let old = self.bus.ram().get(address).copied().unwrap_or(0);
+        self.observer.on_memory_write(address, old, value);
+        self.bus.write(address, value);
+    }
+
+    /// Read the byte at the given address through the bus.
+    /// In the strict mode, the bytes outside the memory read as 0.
+    fn read(&mut self, address: usize) -> u8 {
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            if !diagnostics.read(address) {
+                return 0;
+            }
+        }
+        self.bus.read(address)
+    }
+
+    /// Read the given number of bytes at I through the bus, stopping at the
+    /// end of the memory.
+    fn read_at_i(&mut self, size: usize) -> Vec<u8> {
+        let end = (self.i + size).min(self.memory().len());
+        (self.i.min(end)..end)
+            .map(|address| self.read(address))
+            .collect()
+    }
+
+    /// Execute a single opcode : an opcode of the extension of the platform
+    /// if it is one, see 'execute_extension', a CHIP 8 opcode otherwise.
     pub fn execute_opcode(&mut self, op: u16) {
-        // For easier matching, get the values (nibbles) A, B, C, D
-        // if the opcode is 0xABCD.
-        let opcode_tuple = (
-            ((op & 0xF000) >> 12) as u8,
-            ((op & 0x0F00) >> 8) as u8,
-            ((op & 0x00F0) >> 4) as u8,
-            (op & 0x000F) as u8,
-        );
+        if !self.execute_extension(op) {
+            self.execute_chip8(op);
+        }
+    }
 
+    /// Execute the given opcode if the extension of the platform, e.g.
+    /// CHIP-8X, defines or redefines it.
+    /// Return false if the opcode is left to CHIP 8.
+    fn execute_extension(&mut self, op: u16) -> bool {
+        match self.platform {
+            Platform::Chip8Hires => self.execute_hires(op),
+            Platform::Chip8X => self.execute_chip8x(op),
+            Platform::SuperChip | Platform::XoChip => self.execute_schip(op),
+            Platform::MegaChip => self.execute_megachip(op) || self.execute_schip(op),
+            _ => false,
+        }
+    }
+
+    /// Execute the given CHIP 8 opcode.
+    fn execute_chip8(&mut self, op: u16) {
         //println!("{:0>4X}/{:X},{:X},{:X},{:X}", self.opcode, a, b, c, d);
 
         // Opcode decoding
-        match opcode_tuple {
+        match nibbles(op) {
             (0x0, 0x0, 0xE, 0x0) => self.cls(),
             (0x0, 0x0, 0xE, 0xE) => self.ret(),
-            // 0NNN = sys addr : ignore
+            // 0NNN = sys addr : only the registered native handlers
+            (0x0, _, _, _) if self.syscalls.contains_key(&(op & 0x0FFF)) => {
+                self.sys_addr(op & 0x0FFF)
+            }
             (0x1, _, _, _) => self.jump_addr(op & 0x0FFF),
             (0x2, _, _, _) => self.call_addr(op & 0x0FFF),
             (0x3, x, _, _) => self.se_vx_nn(x, (op & 0x00FF) as u8),
@@ -233,9 +1160,10 @@ impl Chip8 {
             (0x8, x, y, 0xE) => self.shl_vx_vy(x, y),
             (0x9, x, y, 0x0) => self.sne_vx_vy(x, y),
             (0xA, _, _, _) => self.ld_i_addr(op & 0x0FFF),
-            (0xB, _, _, _) => {
-                let v0 = self.v[0] as u16; // sacrifice to the god of borrows
-                self.jump_addr(op & 0x0FFF + v0);
+            (0xB, x, _, _) => {
+                let offset = if self.quirks.jump { x } else { 0 };
+                let offset = self.v[offset as usize] as u16; // sacrifice to the god of borrows
+                self.jump_addr((op & 0x0FFF) + offset);
             }
             (0xC, x, _, _) => self.rnd_vx_nn(x, (op & 0x00FF) as u8),
             (0xD, x, y, n) => self.drw_vx_vy_n(x, y, n),
@@ -247,10 +1175,11 @@ impl Chip8 {
             (0xF, x, 0x1, 0x8) => self.ld_st_vx(x),
             (0xF, x, 0x1, 0xE) => self.add_i_vx(x),
             (0xF, x, 0x2, 0x9) => self.ld_i_font_vx(x),
+            (0xF, x, 0x3, 0x0) => self.ld_i_big_font_vx(x),
             (0xF, x, 0x3, 0x3) => self.ld_mem_i_bcd_vx(x),
             (0xF, x, 0x5, 0x5) => self.ld_mem_i_regs(x),
             (0xF, x, 0x6, 0x5) => self.ld_regs_mem_i(x),
-            _ => op_not_implemented!(op, self.pc),
+            _ => op_not_implemented!(self, op),
         }
     }
 
@@ -260,13 +1189,27 @@ impl Chip8 {
         self.pc += 2;
     }
 
+    /// Call the native handler registered on the given address, then move on
+    /// to the next instruction unless the handler moved the program counter.
+    fn sys_addr(&mut self, addr: u16) {
+        let handler = self.syscalls[&addr];
+        let pc = self.pc;
+        handler(self);
+        if self.pc == pc {
+            self.pc += 2;
+        }
+    }
+
     /// Return from a subroutine, by setting the program counter to the address
     /// popped from the stack.
     fn ret(&mut self) {
-        self.sp -= 1;
-        let addr = self.stack[self.sp];
-        self.jump_addr(addr);
-        self.pc += 2;
+        match self.pop() {
+            Some(addr) => {
+                self.jump_addr(addr);
+                self.pc += 2;
+            }
+            None => self.report_error(format!("Stack underflow at {:0>5X}", self.pc)),
+        }
     }
 
     /// Jump to the given address of the form 0x0NNN.
@@ -277,10 +1220,17 @@ impl Chip8 {
     /// Execute the subroutine at the provided address pushing the current
     /// program counter to the stack and jumping to the given address of the
     /// form 0x0NNN.
-    /// TODO : handle stack overflow error ?
+    /// A call past the maximum depth of the stack is reported as an error and
+    /// not executed.
     fn call_addr(&mut self, addr: u16) {
-        self.stack[self.sp] = self.pc as u16;
-        self.sp += 1;
+        if let Err(why) = self.check_stack_depth(self.sp + 1) {
+            self.report_error(format!("Call at {:0>5X} : {}", self.pc, why));
+            return;
+        }
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.call(self.sp + 1);
+        }
+        self.push(self.pc as u16);
         self.jump_addr(addr);
     }
 
@@ -366,18 +1316,27 @@ impl Chip8 {
     /// Set VX to (VX OR VY).
     fn or_vx_vy(&mut self, x: u8, y: u8) {
         self.v[x as usize] |= self.v[y as usize];
+        if self.quirks.logic {
+            self.v[FLAG] = 0x0;
+        }
         self.pc += 2;
     }
 
     /// Set VX to (VX AND VY).
     fn and_vx_vy(&mut self, x: u8, y: u8) {
         self.v[x as usize] &= self.v[y as usize];
+        if self.quirks.logic {
+            self.v[FLAG] = 0x0;
+        }
         self.pc += 2;
     }
 
     /// Set VX to (VX XOR VY).
     fn xor_vx_vy(&mut self, x: u8, y: u8) {
         self.v[x as usize] ^= self.v[y as usize];
+        if self.quirks.logic {
+            self.v[FLAG] = 0x0;
+        }
         self.pc += 2;
     }
 
@@ -414,9 +1373,9 @@ impl Chip8 {
     /// and set register VF to the least significant bit prior to the shift.
     /// NB : references disagree on this opcode, we use the one defined here :
     /// http://mattmik.com/chip8.html
-    /// If shift_op_use_vy is false, will consider VX instead of VY.
+    /// With the shift quirk, will consider VX instead of VY.
     fn shr_vx_vy(&mut self, x: u8, y: u8) {
-        let shift_on = if self.shift_op_use_vy { y } else { x };
+        let shift_on = if self.quirks.shift { x } else { y };
         self.v[FLAG] = self.v[shift_on as usize] & 0x01;
         self.v[x as usize] = self.v[shift_on as usize] >> 1;
         self.pc += 2;
@@ -424,9 +1383,9 @@ impl Chip8 {
 
     /// Same as 'shr_vx_vy' but with a left shift.
     /// Set register VF to the most significant bit prior to the shift.
-    /// If shift_op_use_vy is false, will consider VX instead of VY.
+    /// With the shift quirk, will consider VX instead of VY.
     fn shl_vx_vy(&mut self, x: u8, y: u8) {
-        let shift_on = if self.shift_op_use_vy { y } else { x };
+        let shift_on = if self.quirks.shift { x } else { y };
         self.v[FLAG] = self.v[shift_on as usize] & 0x80;
         self.v[x as usize] = self.v[shift_on as usize] << 1;
         self.pc += 2;
@@ -443,22 +1402,39 @@ impl Chip8 {
     /// The drawing is implemented by 'Display' as a XOR operation.
     /// VF will act here as a collision flag, i.e. if any set pixel is erased
     /// set it to 0x1, and to 0x0 otherwise.
+    /// With the vblank quirk, the opcode is repeated until a frame starts.
     fn drw_vx_vy_n(&mut self, x: u8, y: u8, n: u8) {
+        if !self.wait_vblank() {
+            return;
+        }
         let pos_x = self.v[x as usize] as usize;
         let pos_y = self.v[y as usize] as usize;
-        let mem_start = self.i;
-        let mem_end = self.i + n as usize;
-        if self
-            .display
-            .draw(pos_x, pos_y, &self.memory[mem_start..mem_end])
-        {
-            self.v[FLAG] = 0x1;
-        } else {
-            self.v[FLAG] = 0x0;
+        let sprite = self.read_at_i(n as usize);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            if !self.quirks.wrap {
+                diagnostics.draw(pos_x, pos_y, &sprite, self.display.size());
+            }
         }
+        let collision = self
+            .display
+            .draw_with(pos_x, pos_y, &sprite, self.quirks.wrap);
+        self.v[FLAG] = collision as u8;
+        self.observer.on_draw(pos_x, pos_y, &sprite, collision);
         self.pc += 2;
     }
 
+    /// With the vblank quirk, wait for the start of a frame before drawing.
+    /// Return false if the drawing opcode must be repeated.
+    fn wait_vblank(&mut self) -> bool {
+        if self.quirks.vblank {
+            if !self.vblank {
+                return false;
+            }
+            self.vblank = false;
+        }
+        true
+    }
+
     /// Store the current value of the delay timer in register VX.
     fn ld_vx_dt(&mut self, x: u8) {
         self.v[x as usize] = self.delay_timer;
@@ -473,35 +1449,38 @@ impl Chip8 {
 
     /// Set the sound timer to the value stored in register VX.
     fn ld_st_vx(&mut self, x: u8) {
-        self.sound_timer = self.v[x as usize];
+        self.set_sound_timer(self.v[x as usize]);
         self.pc += 2;
     }
 
     /// Wait for a key press and store the result in the register VX.
-    /// Implementation : the emulation application must trigger the
-    /// 'end_wait_for_key_press' function ; this allows to achieve better
-    /// decoupling from the framerate.
+    /// The next cycles follow the keypad state (see 'update_key_wait') :
+    /// the keys held before do not count, and the timers keep running.
     fn ld_vx_key(&mut self, x: u8) {
-        self.wait_for_key = (true, x);
-        /*for i in 0..16 {
-            match self.keypad.get_key_state(i) {
-                Keystate::Pressed => {
-                    self.v[x as usize] = i as u8;
-                    self.pc += 2;
-                    break;
-                }
-                Keystate::Released => {},
-            }
-        }*/
+        self.keypad.take_pressed();
+        self.key_wait = KeyWait::Press(x);
+        self.observer.on_key_wait(x);
     }
 
     /// Set I to the memory address of the sprite data corresponding to the
     /// hexadecimal digit (0x0..0xF) stored in register VX.
     /// Will use the internal fontset stored in memory.
     fn ld_i_font_vx(&mut self, x: u8) {
-        // the font set is in the memory range 0x0..0x80
-        // and each character is represented by 5 bytes
-        self.i = (self.v[x as usize] * 5) as usize;
+        // each character is represented by 5 bytes
+        let digit = (self.v[x as usize] & 0xF) as usize;
+        self.i = self.memory_map.font_address + digit * SMALL_GLYPH_SIZE;
+        self.pc += 2;
+    }
+
+    /// Set I to the memory address of the big sprite (8x10 pixels) of the
+    /// digit stored in register VX (SUPER-CHIP).
+    /// Will use the big font stored in memory, if any.
+    fn ld_i_big_font_vx(&mut self, x: u8) {
+        let digit = (self.v[x as usize] & 0xF) as usize;
+        if digit * BIG_GLYPH_SIZE >= self.font.big.len() {
+            warn!("no big glyph for the digit {:X} in the font", digit);
+        }
+        self.i = self.memory_map.big_font_address + digit * BIG_GLYPH_SIZE;
         self.pc += 2;
     }
 
@@ -510,31 +1489,143 @@ impl Chip8 {
     fn ld_mem_i_bcd_vx(&mut self, x: u8) {
         // VX is a byte : its decimal value is in 0..256
         let vx = self.v[x as usize];
-        self.memory[self.i] = vx / 100;
-        self.memory[self.i + 1] = (vx / 10) % 10;
-        self.memory[self.i + 2] = (vx % 100) % 10;
+        self.write(self.i, vx / 100);
+        self.write(self.i + 1, (vx / 10) % 10);
+        self.write(self.i + 2, (vx % 100) % 10);
         self.pc += 2;
     }
 
     /// Store the values of registers V0 to VX inclusive in memory starting at
-    /// the address I, and set I to I + X + 1 after operation.
+    /// the address I, and by default set I to I + X + 1 after operation.
     fn ld_mem_i_regs(&mut self, x: u8) {
         let x_usize = x as usize;
         for j in 0..(x_usize + 1) {
-            self.memory[self.i + j] = self.v[j];
+            self.write(self.i + j, self.v[j]);
         }
-        self.i += x_usize + 1;
+        self.increment_i_after_load_store(x_usize);
         self.pc += 2;
     }
 
     /// Fill registers V0 to VX inclusive with the values stored in memory
-    /// starting at the address I.
+    /// starting at the address I, and by default set I to I + X + 1 after
+    /// operation.
     fn ld_regs_mem_i(&mut self, x: u8) {
         let x_usize = x as usize;
         for j in 0..(x_usize + 1) {
-            self.v[j] = self.memory[self.i + j];
+            self.v[j] = self.read(self.i + j);
         }
-        self.i += x_usize + 1;
+        self.increment_i_after_load_store(x_usize);
         self.pc += 2;
     }
+
+    /// Update I after FX55 or FX65 according to the memory increment quirk.
+    fn increment_i_after_load_store(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::ByXPlusOne => self.i += x + 1,
+            MemoryIncrement::ByX => self.i += x,
+            MemoryIncrement::Unchanged => {}
+        }
+    }
+}
+
+impl<B: Bus, O: Observer> Machine for Chip8<B, O> {
+    /// The opcode, i.e. the first 2 bytes of the instruction : the 4 bytes
+    /// long instructions read the next 2 bytes when executed.
+    type Instruction = u16;
+
+    fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        Chip8::load_bytes(self, rom)
+    }
+
+    fn reset(&mut self) {
+        Chip8::reset(self)
+    }
+
+    fn decode(&self) -> Option<u16> {
+        // Is the program finished ?
+        // decoding has no side effect : the RAM is read without the bus
+        let memory = self.memory();
+        if self.exited || self.pc >= memory.len() - 2 {
+            return None;
+        }
+        Some((memory[self.pc] as u16) << 8 | (memory[self.pc + 1] as u16))
+    }
+
+    fn execute(&mut self, op: u16) {
+        let pc = self.pc;
+        self.observer.before_instruction(pc, op);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.before_instruction(pc, op, &self.quirks);
+        }
+        self.opcode = op;
+        self.execute_opcode(op);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.after_instruction(self.i);
+        }
+        self.observer.after_instruction(pc, op);
+    }
+
+    fn run_frame(&mut self, cycles: u32) -> bool {
+        for _ in 0..cycles {
+            if self.emulate_cycle() {
+                return true;
+            }
+        }
+        self.update_timers();
+        false
+    }
+
+    fn display(&self) -> &Display {
+        &self.display
+    }
+
+    fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn second_keypad_mut(&mut self) -> Option<&mut Keypad> {
+        if self.platform == Platform::Chip8X {
+            Some(&mut self.second_keypad)
+        } else {
+            None
+        }
+    }
+
+    fn is_waiting_for_key(&self) -> bool {
+        Chip8::is_waiting_for_key(self)
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    fn digitised_sound(&self) -> Option<(&Sound, u32)> {
+        let megachip = self.megachip.as_ref()?;
+        let sound = megachip.sound.as_ref()?;
+        Some((sound, megachip.sound_serial))
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        Chip8::save_state(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        Chip8::load_state(self, data)
+    }
+
+    fn diagnostics(&self) -> &[Diagnostic] {
+        self.diagnostics.as_ref().map_or(&[], Diagnostics::report)
+    }
 }
@@ -0,0 +1,121 @@
+/// The opcodes of CHIP-8X, the colour extension of the COSMAC VIP
+/// interpreter.
+use super::{nibbles, Chip8};
+use crate::bus::Bus;
+use crate::display::COLOR_ZONE_HEIGHT;
+use crate::keypad::Keystate;
+use crate::observer::Observer;
+
+impl<B: Bus, O: Observer> Chip8<B, O> {
+    /// Execute the given opcode if it is a CHIP-8X one.
+    /// Return false if the opcode is left to CHIP 8.
+    pub(super) fn execute_chip8x(&mut self, op: u16) -> bool {
+        match nibbles(op) {
+            (0x0, 0x2, 0xA, 0x0) => self.cycle_background(),
+            (0x5, x, y, 0x1) => self.add_vx_vy_nibbles(x, y),
+            (0xB, x, y, 0x0) => self.col_zones_vx_vy(x, y),
+            (0xB, x, y, n) => self.col_rows_vx_vy_n(x, y, n),
+            (0xE, x, 0xF, 0x2) => self.skp2_vx(x),
+            (0xE, x, 0xF, 0x5) => self.sknp2_vx(x),
+            (0xF, x, 0xF, 0x8) => self.out_vx(x),
+            (0xF, x, 0xF, 0xB) => self.in_vx(x),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Switch to the next background colour (CHIP-8X).
+    fn cycle_background(&mut self) {
+        if let Some(ref mut colors) = self.display.colors {
+            colors.cycle_background();
+        }
+        self.display.dirty = true;
+        self.pc += 2;
+    }
+
+    /// Add VY to VX nibble by nibble, each sum wrapping around on 3 bits
+    /// (CHIP-8X). Moves the colour zones given to BXY0.
+    fn add_vx_vy_nibbles(&mut self, x: u8, y: u8) {
+        let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+        self.v[x as usize] =
+            ((vx & 0x70) + (vy & 0x70)) & 0x70 | ((vx & 0x07) + (vy & 0x07)) & 0x07;
+        self.pc += 2;
+    }
+
+    /// Return the columns of colour zones given by VX, as (first, count) :
+    /// the low nibble is the first column and the high nibble the number of
+    /// additional columns to the right.
+    fn color_columns(&self, x: u8) -> (usize, usize) {
+        let vx = self.v[x as usize] as usize;
+        (vx & 0xF, (vx >> 4) + 1)
+    }
+
+    /// Set the foreground colour VY of zones of 8x4 pixels (CHIP-8X).
+    /// VX gives the columns (see 'color_columns') and VX+1 the rows of
+    /// zones in the same way.
+    fn col_zones_vx_vy(&mut self, x: u8, y: u8) {
+        let columns = self.color_columns(x);
+        let vx1 = self.v[(x as usize + 1) & 0xF] as usize;
+        let rows = (
+            (vx1 & 0xF) * COLOR_ZONE_HEIGHT,
+            ((vx1 >> 4) + 1) * COLOR_ZONE_HEIGHT,
+        );
+        let color = self.v[y as usize];
+        if let Some(ref mut colors) = self.display.colors {
+            colors.fill(columns, rows, color);
+        }
+        self.display.dirty = true;
+        self.pc += 2;
+    }
+
+    /// Set the foreground colour VY of N rows of zones of 8x1 pixels,
+    /// starting at the pixel row VX+1 (CHIP-8X). VX gives the columns as
+    /// with BXY0.
+    fn col_rows_vx_vy_n(&mut self, x: u8, y: u8, n: u8) {
+        let columns = self.color_columns(x);
+        let rows = (self.v[(x as usize + 1) & 0xF] as usize, n as usize);
+        let color = self.v[y as usize];
+        if let Some(ref mut colors) = self.display.colors {
+            colors.fill(columns, rows, color);
+        }
+        self.display.dirty = true;
+        self.pc += 2;
+    }
+
+    /// Skip the next instruction if the key of index VX is currently pressed
+    /// on the second keypad (CHIP-8X).
+    fn skp2_vx(&mut self, x: u8) {
+        self.pc += match self
+            .second_keypad
+            .get_key_state(self.v[x as usize] as usize)
+        {
+            Keystate::Pressed => 4,
+            Keystate::Released => 2,
+        };
+    }
+
+    /// Skip the next instruction if the key of index VX is currently released
+    /// on the second keypad (CHIP-8X).
+    fn sknp2_vx(&mut self, x: u8) {
+        self.pc += match self
+            .second_keypad
+            .get_key_state(self.v[x as usize] as usize)
+        {
+            Keystate::Pressed => 2,
+            Keystate::Released => 4,
+        };
+    }
+
+    /// Write VX to the output port (CHIP-8X).
+    fn out_vx(&mut self, x: u8) {
+        self.io_output = self.v[x as usize];
+        self.pc += 2;
+    }
+
+    /// Store the byte of the input port in VX (CHIP-8X). The input is latched
+    /// by the emulator application, so the instruction never waits.
+    fn in_vx(&mut self, x: u8) {
+        self.v[x as usize] = self.io_input;
+        self.pc += 2;
+    }
+}
@@ -0,0 +1,21 @@
+/// The opcodes of the two-page hi-res interpreter of the COSMAC VIP.
+use super::{nibbles, Chip8, HIRES_PROGRAM_START};
+use crate::bus::Bus;
+use crate::observer::Observer;
+
+impl<B: Bus, O: Observer> Chip8<B, O> {
+    /// Execute the given opcode if it is specific to the hi-res interpreter.
+    /// Return false if the opcode is left to CHIP 8.
+    pub(super) fn execute_hires(&mut self, op: u16) -> bool {
+        match nibbles(op) {
+            // hi-res clear screen
+            (0x0, 0x2, 0x3, 0x0) => self.cls(),
+            // hi-res entry trick : skip the patch of the interpreter
+            (0x1, 0x2, 0x6, 0x0) if self.pc == self.load_address => {
+                self.jump_addr(HIRES_PROGRAM_START as u16)
+            }
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,216 @@
+/// The opcodes of MEGA-CHIP 8, enabled by 0011 : the MEGA-CHIP mode
+/// redefines 00E0 and DXYN. MEGA-CHIP extends SUPER-CHIP, whose opcodes are
+/// left to vm/schip.rs ; the scrolling also moves the true colour pixels in
+/// the MEGA-CHIP mode.
+use super::schip::HORIZONTAL_SCROLL;
+use super::{nibbles, Chip8, FLAG};
+use crate::bus::Bus;
+use crate::display::Display;
+use crate::megachip::{
+    BlendMode, Framebuffer, MegaChip, Sound, MAX_SPRITE_SIZE, MEGA_HEIGHT, MEGA_WIDTH,
+};
+use crate::observer::Observer;
+
+impl<B: Bus, O: Observer> Chip8<B, O> {
+    /// Execute the given opcode if it is a MEGA-CHIP one : 0010 and 0011,
+    /// and the other MEGA-CHIP opcodes in the MEGA-CHIP mode.
+    /// Return false if the opcode is left to CHIP 8.
+    pub(super) fn execute_megachip(&mut self, op: u16) -> bool {
+        let nn = (op & 0x00FF) as u8;
+        match nibbles(op) {
+            (0x0, 0x0, 0x1, 0x0) => self.megachip_off(),
+            (0x0, 0x0, 0x1, 0x1) => self.megachip_on(),
+            _ if self.megachip.is_none() => return false,
+            (0x0, 0x0, 0xE, 0x0) => self.present(),
+            (0x0, 0x0, 0xC, _) | (0x0, 0x0, 0xF, 0xB) | (0x0, 0x0, 0xF, 0xC) => {
+                return self.scroll_megachip(op)
+            }
+            (0x0, 0x1, _, _) => self.ld_i_long(nn),
+            (0x0, 0x2, _, _) => self.ld_palette(nn),
+            (0x0, 0x3, _, _) => self.sprite_width(nn),
+            (0x0, 0x4, _, _) => self.sprite_height(nn),
+            (0x0, 0x5, _, _) => self.screen_alpha(nn),
+            (0x0, 0x6, 0x0, n) => self.play_sound(n),
+            (0x0, 0x7, 0x0, 0x0) => self.stop_sound(),
+            (0x0, 0x8, 0x0, n) => self.blend_mode(n),
+            (0x0, 0x9, _, _) => self.collision_color(nn),
+            (0xD, x, y, n) => self.drw_megachip(x, y, n),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Disable the MEGA-CHIP mode, back to the SUPER-CHIP display in its
+    /// current resolution.
+    fn megachip_off(&mut self) {
+        let (width, height) = self.schip_display_size();
+        self.display = Display::with_size(width, height);
+        self.megachip = None;
+        self.pc += 2;
+    }
+
+    /// Enable the MEGA-CHIP mode : 256x192 display and palette.
+    fn megachip_on(&mut self) {
+        self.display = Display::with_size(MEGA_WIDTH, MEGA_HEIGHT);
+        self.display.framebuffer = Some(Framebuffer::new(MEGA_WIDTH, MEGA_HEIGHT));
+        self.megachip = Some(MegaChip::new());
+        self.pc += 2;
+    }
+
+    /// Set I to the 24 bits address formed by NN and the next 2 bytes of the
+    /// 4 bytes long instruction 01NN NNNN (MEGA-CHIP).
+    fn ld_i_long(&mut self, nn: u8) {
+        let low = if self.pc + 4 <= self.memory().len() {
+            (self.bus.read(self.pc + 2) as usize) << 8 | self.bus.read(self.pc + 3) as usize
+        } else {
+            0
+        };
+        self.i = (nn as usize) << 16 | low;
+        self.pc += 4;
+    }
+
+    /// Load NN colours, stored in memory at I as ARGB 4 bytes each, in the
+    /// palette from the index 1 (MEGA-CHIP).
+    fn ld_palette(&mut self, nn: u8) {
+        let colors = self.read_at_i(4 * nn as usize);
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.load_palette(&colors);
+        }
+        self.pc += 2;
+    }
+
+    /// Set the width of the sprites drawn by DXYN, 0 meaning 256 pixels
+    /// (MEGA-CHIP).
+    fn sprite_width(&mut self, nn: u8) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.sprite_width = if nn == 0 {
+                MAX_SPRITE_SIZE
+            } else {
+                nn as usize
+            };
+        }
+        self.pc += 2;
+    }
+
+    /// Set the height of the sprites drawn by DXYN, 0 meaning 256 pixels
+    /// (MEGA-CHIP).
+    fn sprite_height(&mut self, nn: u8) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.sprite_height = if nn == 0 {
+                MAX_SPRITE_SIZE
+            } else {
+                nn as usize
+            };
+        }
+        self.pc += 2;
+    }
+
+    /// Set the opacity of the whole screen (MEGA-CHIP).
+    fn screen_alpha(&mut self, nn: u8) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.screen_alpha = nn;
+        }
+        self.pc += 2;
+    }
+
+    /// Play the digitised sound stored in memory at I, repeated if N is 0
+    /// (MEGA-CHIP).
+    fn play_sound(&mut self, n: u8) {
+        match Sound::from_memory(self.bus.ram(), self.i, n == 0) {
+            Ok(sound) => {
+                if let Some(ref mut megachip) = self.megachip {
+                    megachip.sound = Some(sound);
+                    megachip.sound_serial = megachip.sound_serial.wrapping_add(1);
+                }
+            }
+            Err(why) => self.report_error(format!(
+                "cannot play the sound at {:0>5X} : {}",
+                self.pc, why
+            )),
+        }
+        self.pc += 2;
+    }
+
+    /// Stop the digitised sound (MEGA-CHIP).
+    fn stop_sound(&mut self) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.sound = None;
+        }
+        self.pc += 2;
+    }
+
+    /// Set the blend mode of the sprites (MEGA-CHIP).
+    fn blend_mode(&mut self, n: u8) {
+        match (BlendMode::from_index(n), self.megachip.as_mut()) {
+            (Some(mode), Some(megachip)) => megachip.blend_mode = mode,
+            (None, _) => {
+                let message = format!("unknown blend mode {} at {:0>5X}", n, self.pc);
+                self.report_error(message);
+            }
+            (Some(_), None) => {}
+        }
+        self.pc += 2;
+    }
+
+    /// Set the palette index whose pixels collide with the sprites
+    /// (MEGA-CHIP).
+    fn collision_color(&mut self, nn: u8) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.collision_color = nn;
+        }
+        self.pc += 2;
+    }
+
+    /// Scroll the screen shown and the back buffer, then the pixels of the
+    /// display as in SUPER-CHIP (00CN, 00FB and 00FC in the MEGA-CHIP mode).
+    fn scroll_megachip(&mut self, op: u16) -> bool {
+        let scroll = |framebuffer: &mut Framebuffer| match nibbles(op) {
+            (_, _, 0xC, n) => framebuffer.scroll_down(n as usize),
+            (_, _, _, 0xB) => framebuffer.scroll_right(HORIZONTAL_SCROLL),
+            _ => framebuffer.scroll_left(HORIZONTAL_SCROLL),
+        };
+        if let Some(ref mut megachip) = self.megachip {
+            scroll(&mut megachip.back);
+        }
+        if let Some(ref mut framebuffer) = self.display.framebuffer {
+            scroll(framebuffer);
+        }
+        self.execute_schip(op)
+    }
+
+    /// Present the back buffer, then clear it (00E0 in the MEGA-CHIP mode).
+    fn present(&mut self) {
+        if let Some(ref mut megachip) = self.megachip {
+            megachip.present(&mut self.display);
+        }
+        self.pc += 2;
+    }
+
+    /// Draw the sprite of the size set by 0300 and 0400 at I, at position
+    /// VX, VY (DXYN in the MEGA-CHIP mode). The font glyphs stay monochrome
+    /// and N rows high.
+    /// VF is set to 0x1 if a pixel of the collision colour is hit, and to 0x0
+    /// otherwise.
+    fn drw_megachip(&mut self, x: u8, y: u8, n: u8) {
+        if !self.wait_vblank() {
+            return;
+        }
+        let pos_x = self.v[x as usize] as usize;
+        let pos_y = self.v[y as usize] as usize;
+        let size = match self.megachip {
+            Some(ref megachip) => megachip.sprite_width * megachip.sprite_height,
+            None => return,
+        };
+        let glyph = self.i < self.load_address;
+        let bytes = self.read_at_i(if glyph { n as usize } else { size });
+        let megachip = self.megachip.as_mut().unwrap();
+        let collision = if glyph {
+            megachip.draw_glyph(pos_x, pos_y, &bytes)
+        } else {
+            megachip.draw_sprite(pos_x, pos_y, &bytes)
+        };
+        self.v[FLAG] = collision as u8;
+        self.observer.on_draw(pos_x, pos_y, &bytes, collision);
+        self.pc += 2;
+    }
+}
@@ -0,0 +1,119 @@
+/// The opcodes of SUPER-CHIP 1.1 : scrolling, the 128x64 high resolution
+/// mode, the 16x16 sprites, the exit and the user flags. FX30, which reads
+/// the big font, is shared with the modern interpreters.
+/// As the modern SUPER-CHIP interpreters do, the scrolling opcodes move the
+/// pixels of the current resolution, and DXY0 draws 16x16 sprites in both
+/// resolutions, setting VF to 0x1 on a collision.
+use super::{nibbles, Chip8, FLAG};
+use crate::bus::Bus;
+use crate::display::{Display, HIRES_HEIGHT, HIRES_WIDTH};
+use crate::observer::Observer;
+
+/// The number of pixels scrolled by 00FB and 00FC.
+pub(super) const HORIZONTAL_SCROLL: usize = 4;
+
+impl<B: Bus, O: Observer> Chip8<B, O> {
+    /// Execute the given opcode if it is a SUPER-CHIP one.
+    /// Return false if the opcode is left to CHIP 8.
+    pub(super) fn execute_schip(&mut self, op: u16) -> bool {
+        match nibbles(op) {
+            (0x0, 0x0, 0xC, n) => self.scd_n(n),
+            (0x0, 0x0, 0xF, 0xB) => self.scr(),
+            (0x0, 0x0, 0xF, 0xC) => self.scl(),
+            (0x0, 0x0, 0xF, 0xD) => self.exit(),
+            (0x0, 0x0, 0xF, 0xE) => self.set_hires(false),
+            (0x0, 0x0, 0xF, 0xF) => self.set_hires(true),
+            (0xD, x, y, 0x0) => self.drw_vx_vy_wide(x, y),
+            (0xF, x, 0x7, 0x5) => self.ld_flags_vx(x),
+            (0xF, x, 0x8, 0x5) => self.ld_vx_flags(x),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Scroll the display down by N pixels.
+    fn scd_n(&mut self, n: u8) {
+        self.display.scroll_down(n as usize);
+        self.pc += 2;
+    }
+
+    /// Scroll the display right by 4 pixels.
+    fn scr(&mut self) {
+        self.display.scroll_right(HORIZONTAL_SCROLL);
+        self.pc += 2;
+    }
+
+    /// Scroll the display left by 4 pixels.
+    fn scl(&mut self) {
+        self.display.scroll_left(HORIZONTAL_SCROLL);
+        self.pc += 2;
+    }
+
+    /// Exit the interpreter : the program is done.
+    fn exit(&mut self) {
+        self.exited = true;
+    }
+
+    /// Enable the 128x64 high resolution mode, or go back to the display of
+    /// the platform. The display is cleared, unless in the MEGA-CHIP mode
+    /// which applies the resolution when disabled.
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        if self.megachip.is_none() {
+            let (width, height) = self.schip_display_size();
+            self.display = Display::with_size(width, height);
+        }
+        self.pc += 2;
+    }
+
+    /// Return the size of the display in the current resolution, as
+    /// (width, height) in pixels.
+    pub(super) fn schip_display_size(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            self.platform.display_size()
+        }
+    }
+
+    /// Draw the 16x16 sprite stored in memory at I, 2 bytes per row, at
+    /// position VX, VY. See 'drw_vx_vy_n'.
+    fn drw_vx_vy_wide(&mut self, x: u8, y: u8) {
+        if !self.wait_vblank() {
+            return;
+        }
+        let pos_x = self.v[x as usize] as usize;
+        let pos_y = self.v[y as usize] as usize;
+        let sprite = self.read_at_i(32);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            if !self.quirks.wrap {
+                // as two sprites of 8x16 pixels
+                let size = self.display.size();
+                for half in 0..2 {
+                    let rows: Vec<u8> = sprite.iter().skip(half).step_by(2).cloned().collect();
+                    diagnostics.draw(pos_x + 8 * half, pos_y, &rows, size);
+                }
+            }
+        }
+        let collision = self
+            .display
+            .draw_wide_with(pos_x, pos_y, &sprite, self.quirks.wrap);
+        self.v[FLAG] = collision as u8;
+        self.observer.on_draw(pos_x, pos_y, &sprite, collision);
+        self.pc += 2;
+    }
+
+    /// Save the registers V0 to VX inclusive in the user flags.
+    fn ld_flags_vx(&mut self, x: u8) {
+        let count = x as usize + 1;
+        self.flags[..count].copy_from_slice(&self.v[..count]);
+        self.pc += 2;
+    }
+
+    /// Restore the registers V0 to VX inclusive from the user flags.
+    fn ld_vx_flags(&mut self, x: u8) {
+        let count = x as usize + 1;
+        self.v[..count].copy_from_slice(&self.flags[..count]);
+        self.pc += 2;
+    }
+}
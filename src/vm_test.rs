@@ -1,5 +1,9 @@
 use super::keypad::Keystate::*;
-use super::vm::{Chip8, FLAG};
+use super::machine::Machine;
+use super::palette::{Palette, Rgb};
+use super::platform::{MemoryMap, Platform};
+use super::quirks::{MemoryIncrement, Quirks};
+use super::vm::{Chip8, LoadError, FLAG, MEMORY_SIZE};
 
 #[test]
 fn jump_addr() {
@@ -13,18 +17,61 @@ fn subroutines_and_reset() {
     let mut vm = Chip8::new();
     vm.execute_opcode(0x2BBB);
     assert_eq!(vm.pc(), 0x0BBB);
-    assert_eq!(vm.stack[0], 0x200);
+    assert_eq!(vm.stack(), vec![0x200]);
     vm.execute_opcode(0x00EE);
-    assert_eq!(vm.sp, 0);
+    assert!(vm.stack().is_empty());
 
+    vm.execute_opcode(0x2BBB);
     vm.reset();
-    assert_eq!(vm.stack[0], 0);
-    vm.stack[0] = 0x0AAA;
-    vm.stack[1] = 0x0BBB;
-    vm.sp = 0x2;
+    assert!(vm.stack().is_empty());
+    assert!(vm.set_stack(&[0x0AAA, 0x0BBB]).is_ok());
+    vm.execute_opcode(0x00EE);
+    assert_eq!(vm.stack(), vec![0x0AAA]);
+    assert_eq!(vm.pc(), 0x0BBB + 2);
+
+    // underflow : reported and not executed
+    vm.execute_opcode(0x00EE);
+    vm.execute_opcode(0x00EE);
+    assert_eq!(vm.pc(), 0x0AAA + 2);
+}
+
+#[test]
+fn stack_depth() {
+    let mut vm = Chip8::new();
+    assert_eq!(vm.stack_depth(), Some(16));
+    assert!(vm.set_platform(Platform::Chip8).is_ok());
+    assert_eq!(vm.stack_depth(), Some(12));
+    assert!(vm.set_stack(&[0x200; 13]).is_err());
+
+    // overflow : reported and not executed
+    assert!(vm.set_stack(&[0x200; 12]).is_ok());
+    vm.execute_opcode(0x2BBB);
+    assert_eq!((vm.pc(), vm.stack().len()), (0x200, 12));
+    vm.set_stack_depth(None);
+    vm.execute_opcode(0x2BBB);
+    assert_eq!((vm.pc(), vm.stack().len()), (0xBBB, 13));
+
+    // in memory, at the location of the VIP interpreter
+    vm.set_stack_in_memory(true);
+    assert!(vm.stack().is_empty());
+    vm.set_pc(0x200);
+    vm.execute_opcode(0x2BBB);
+    vm.execute_opcode(0x2CCC);
+    assert_eq!(&vm.memory()[0xECC..0xED0], &[0x0B, 0xBB, 0x02, 0x00]);
+    vm.memory_mut()[0xECD] = 0xAA;
+    assert_eq!(vm.stack(), vec![0x200, 0xBAA]);
     vm.execute_opcode(0x00EE);
-    assert_eq!(vm.sp, 1);
-    assert_eq!(vm.pc(), (vm.stack[1] + 2) as usize);
+    assert_eq!(vm.pc(), 0xBAC);
+    assert!(vm.set_stack(&[0x200; 0x768]).is_ok());
+    assert!(vm.set_stack(&[0x200; 0x769]).is_err());
+
+    let state = vm.save_state();
+    vm.reset();
+    assert!(vm.load_state(&state).is_ok());
+    assert_eq!(vm.stack().len(), 0x768);
+    vm.set_stack_in_memory(false);
+    assert!(vm.load_state(&state).is_ok());
+    assert_eq!(vm.stack().len(), 0x768);
 }
 
 #[test]
@@ -58,15 +105,15 @@ fn mem_regs_load() {
     vm.execute_opcode(0x6321);
     vm.execute_opcode(0xA500);
     vm.execute_opcode(0xF355); // ld_mem_i_regs
-    assert_eq!(vm.memory[0x500 + 0], 0x11);
-    assert_eq!(vm.memory[0x500 + 1], 0x22);
-    assert_eq!(vm.memory[0x500 + 2], 0x33);
-    assert_eq!(vm.memory[0x500 + 3], 0x21);
+    assert_eq!(vm.memory()[0x500 + 0], 0x11);
+    assert_eq!(vm.memory()[0x500 + 1], 0x22);
+    assert_eq!(vm.memory()[0x500 + 2], 0x33);
+    assert_eq!(vm.memory()[0x500 + 3], 0x21);
     assert_eq!(vm.index(), 0x500 + 4);
 
-    vm.memory[0x500 + 0] = 0x12;
-    vm.memory[0x500 + 1] = 0x24;
-    vm.memory[0x500 + 2] = 0x56;
+    vm.memory_mut()[0x500 + 0] = 0x12;
+    vm.memory_mut()[0x500 + 1] = 0x24;
+    vm.memory_mut()[0x500 + 2] = 0x56;
     vm.execute_opcode(0xA500);
     vm.execute_opcode(0xF365); // ld_regs_mem_i
     assert_eq!(vm.register(0x0), 0x12);
@@ -203,9 +250,9 @@ fn bcd() {
     vm.execute_opcode(0x6095); // 149
     vm.execute_opcode(0xA400);
     vm.execute_opcode(0xF033); // ld_mem_i_bcd_vx
-    assert_eq!(vm.memory[0x400 + 0], 0b0001);
-    assert_eq!(vm.memory[0x400 + 1], 0b0100);
-    assert_eq!(vm.memory[0x400 + 2], 0b1001);
+    assert_eq!(vm.memory()[0x400 + 0], 0b0001);
+    assert_eq!(vm.memory()[0x400 + 1], 0b0100);
+    assert_eq!(vm.memory()[0x400 + 2], 0b1001);
     assert_eq!(vm.pc(), 0x515 + 2 * 3);
 }
 
@@ -224,14 +271,57 @@ fn input() {
     assert_eq!(vm.pc(), 0x999 + 4 + 4 + 6);
 }
 
+#[test]
+fn key_wait() {
+    // F30A with the delay timer running, then F40A
+    let rom = [0x60, 0x05, 0xF0, 0x15, 0xF3, 0x0A, 0xF4, 0x0A];
+    let mut vm = Chip8::new();
+    assert!(vm.load_bytes(&rom).is_ok());
+    vm.keypad.set_key_state(0x1, Pressed);
+    assert!(!vm.run_frame(10));
+    assert!(vm.is_waiting_for_key());
+    assert_eq!((vm.pc(), vm.delay_timer), (0x204, 4));
+
+    // the key held before does not count, the next one is kept until released
+    vm.keypad.set_key_state(0x1, Released);
+    vm.keypad.set_key_state(0x9, Pressed);
+    assert!(!vm.run_frame(10));
+    assert_eq!((vm.pc(), vm.delay_timer), (0x204, 3));
+    vm.keypad.set_key_state(0x9, Released);
+    vm.emulate_cycle();
+    assert_eq!((vm.pc(), vm.register(3)), (0x206, 0x9));
+
+    // a short press between two cycles, then without waiting for the release
+    vm.emulate_cycle();
+    vm.keypad.set_key_state(0xA, Pressed);
+    vm.keypad.set_key_state(0xA, Released);
+    let state = vm.save_state();
+    vm.emulate_cycle();
+    assert_eq!((vm.pc(), vm.register(4)), (0x208, 0xA));
+
+    // the pending press is part of the saved state
+    let mut other = Chip8::new();
+    assert!(other.load_state(&state).is_ok());
+    assert!(other.is_waiting_for_key());
+    other.emulate_cycle();
+    assert_eq!((other.pc(), other.register(4)), (0x208, 0xA));
+    vm.set_pc(0x206);
+    vm.set_key_wait_release(false);
+    vm.emulate_cycle();
+    vm.keypad.set_key_state(0xB, Pressed);
+    vm.emulate_cycle();
+    assert_eq!((vm.pc(), vm.register(4)), (0x208, 0xB));
+    assert!(!vm.is_waiting_for_key());
+}
+
 #[test]
 fn drawing() {
     let mut vm = Chip8::new();
     vm.execute_opcode(0x1200);
     vm.execute_opcode(0xA250);
-    vm.memory[0x250 + 0] = 0b1110_0111;
-    vm.memory[0x250 + 1] = 0b0110_0110;
-    vm.memory[0x250 + 2] = 0b0011_1100;
+    vm.memory_mut()[0x250 + 0] = 0b1110_0111;
+    vm.memory_mut()[0x250 + 1] = 0b0110_0110;
+    vm.memory_mut()[0x250 + 2] = 0b0011_1100;
 
     vm.execute_opcode(0x6A19); // x = 25
     vm.execute_opcode(0x6B07); // y =  7
@@ -260,4 +350,418 @@ fn drawing() {
     }
 
     assert_eq!(vm.pc(), 0x200 + 2 * 6);
+
+    // the sprite stops at the end of the memory
+    vm.execute_opcode(0x00E0);
+    vm.execute_opcode(0xAFFE);
+    vm.memory_mut()[0xFFE] = 0x80;
+    vm.memory_mut()[0xFFF] = 0x80;
+    vm.execute_opcode(0xDAB5);
+    assert_eq!(vm.register(FLAG), 0x0);
+    assert_eq!(vm.display.gfx[7][25], 1);
+    assert_eq!(vm.display.gfx[8][25], 1);
+    assert_eq!(vm.display.gfx[9][25], 0);
+}
+
+#[test]
+fn save_and_load_state() {
+    let mut vm = Chip8::new();
+    vm.execute_opcode(0x6A19);
+    vm.execute_opcode(0xA250);
+    vm.execute_opcode(0x2300);
+    vm.memory_mut()[0x250] = 0xFF;
+    vm.execute_opcode(0xDAA1);
+    vm.delay_timer = 42;
+    let state = vm.save_state();
+
+    let mut other = Chip8::new();
+    assert!(other.load_state(&state[..state.len() - 1]).is_err());
+    assert!(other.load_state(&state[1..]).is_err());
+    assert!(other.load_state(&state).is_ok());
+    assert_eq!(other.register(0xA), 0x19);
+    assert_eq!(other.index(), 0x250);
+    assert_eq!(other.pc(), 0x300 + 2);
+    assert_eq!(other.stack().len(), 1);
+    assert_eq!(other.delay_timer, 42);
+    assert_eq!(other.memory()[0x250], 0xFF);
+    assert_eq!(other.display.gfx[0x19][0x19], 1);
+    assert_eq!(other.save_state(), state);
+}
+
+#[test]
+fn quirks() {
+    let mut vm = Chip8::new();
+    vm.set_quirks(Quirks::schip());
+
+    // BXNN jumps to XNN + VX
+    vm.execute_opcode(0x6210);
+    vm.execute_opcode(0xB220);
+    assert_eq!(vm.pc(), 0x230);
+
+    // FX55 leaves I unchanged
+    vm.execute_opcode(0xA300);
+    vm.execute_opcode(0xF255);
+    assert_eq!(vm.index(), 0x300);
+    assert_eq!(vm.memory()[0x302], 0x10);
+
+    // clipped sprites
+    vm.execute_opcode(0x603C);
+    vm.execute_opcode(0x611E);
+    vm.memory_mut()[0x300] = 0xFF;
+    vm.memory_mut()[0x301] = 0xFF;
+    vm.memory_mut()[0x302] = 0xFF;
+    vm.execute_opcode(0xD013);
+    assert_eq!(vm.display.gfx[0x1E][0x3F], 1);
+    assert_eq!(vm.display.gfx[0x1F][0x3C], 1);
+    assert_eq!(vm.display.gfx[0x00][0x00], 0);
+    assert_eq!(vm.display.gfx[0x1E][0x00], 0);
+
+    let mut vm = Chip8::new();
+    vm.set_quirks(Quirks {
+        memory_increment: MemoryIncrement::ByX,
+        ..Quirks::chip8()
+    });
+
+    // VF reset by the logical opcodes
+    vm.execute_opcode(0x6F01);
+    vm.execute_opcode(0x8011);
+    assert_eq!(vm.register(FLAG), 0);
+
+    // FX65 increments I by X
+    vm.execute_opcode(0xA300);
+    vm.execute_opcode(0xF365);
+    assert_eq!(vm.index(), 0x303);
+
+    // a single sprite per frame
+    let pc = vm.pc();
+    vm.execute_opcode(0xD011);
+    assert_eq!(vm.pc(), pc + 2);
+    vm.execute_opcode(0xD011);
+    assert_eq!(vm.pc(), pc + 2);
+    vm.update_timers();
+    vm.execute_opcode(0xD011);
+    assert_eq!(vm.pc(), pc + 4);
+}
+
+#[test]
+fn load_rom() {
+    let mut vm = Chip8::new();
+    assert!(vm.load_bytes(&[0x12, 0x34]).is_ok());
+    assert_eq!(vm.memory()[0x200], 0x12);
+    assert_eq!(vm.memory()[0x201], 0x34);
+    assert_eq!(vm.pc(), 0x200);
+
+    match vm.load_bytes(&[]) {
+        Err(LoadError::Empty) => {}
+        _ => panic!("an empty ROM must not be loaded"),
+    }
+    match vm.load_bytes(&[0xAA; MEMORY_SIZE - 0x200 + 1]) {
+        Err(LoadError::TooLarge { size, max }) => {
+            assert_eq!(size, MEMORY_SIZE - 0x200 + 1);
+            assert_eq!(max, MEMORY_SIZE - 0x200);
+        }
+        _ => panic!("a ROM larger than the memory must not be loaded"),
+    }
+    assert!(vm.load_bytes(&[0xAA; MEMORY_SIZE - 0x200]).is_ok());
+    assert_eq!(vm.memory()[MEMORY_SIZE - 1], 0xAA);
+
+    // custom load address
+    let mut vm = Chip8::new();
+    vm.set_load_address(0x600);
+    assert!(vm.load_reader(&[0x56, 0x78][..]).is_ok());
+    assert_eq!(vm.memory()[0x600], 0x56);
+    assert_eq!(vm.pc(), 0x600);
+    vm.execute_opcode(0x1300);
+    vm.reset();
+    assert_eq!(vm.pc(), 0x600);
+    match vm.load_reader(&[0xAA; MEMORY_SIZE][..]) {
+        Err(LoadError::TooLarge { max, .. }) => assert_eq!(max, MEMORY_SIZE - 0x600),
+        _ => panic!("a ROM larger than the memory must not be loaded"),
+    }
+}
+
+#[test]
+fn memory_map() {
+    // the font is at 0x000 by default, and at 0x050 in the modern layout
+    let mut vm = Chip8::new();
+    vm.set_register(0, 0xA);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), 0xA * 5);
+    assert_eq!(vm.memory()[0xA * 5], 0xF0);
+    assert!(vm.set_platform(Platform::Modern).is_ok());
+    vm.set_register(0, 0xA);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), 0x050 + 0xA * 5);
+    assert_eq!(vm.memory()[0x050 + 0xA * 5], 0xF0);
+
+    // ETI-660 : the programs start at 0x600
+    let mut vm = Chip8::new();
+    assert!(vm.set_memory_map(Platform::Eti660.memory_map()).is_ok());
+    assert_eq!(vm.pc(), 0x600);
+    assert!(vm.load_bytes(&[0x13, 0x00]).is_ok());
+    assert_eq!(vm.memory()[0x600], 0x13);
+    vm.execute_opcode(0x1300);
+    vm.reset();
+    assert_eq!(vm.pc(), 0x600);
+
+    // COSMAC VIP : the top of the memory is reserved by the interpreter
+    let mut vm = Chip8::new();
+    assert!(vm.set_memory_map(MemoryMap::vip()).is_ok());
+    assert_eq!(vm.max_program_size(), 3232);
+    assert!(vm.load_bytes(&[0xAA; 3233]).is_err());
+    vm.set_register(0, 0x1);
+    vm.execute_opcode(0xF029);
+    assert_eq!(vm.index(), 5);
+
+    // larger memory, and states of a different memory size are rejected
+    let mut xo = Chip8::new();
+    assert!(xo.set_memory_map(Platform::XoChip.memory_map()).is_ok());
+    assert_eq!(xo.memory().len(), 0x10000);
+    assert!(xo.load_bytes(&[0xAA; MEMORY_SIZE]).is_ok());
+    assert!(vm.load_state(&xo.save_state()).is_err());
+
+    let invalid = MemoryMap {
+        font_address: MEMORY_SIZE - 10,
+        ..MemoryMap::modern()
+    };
+    assert!(vm.set_memory_map(invalid).is_err());
+    assert_eq!(vm.memory_map(), &MemoryMap::vip());
+}
+
+#[test]
+fn hires_platform() {
+    // 1260 ; patch of the interpreter ; at 2C0 : LD I, 2C8 ; DRW V0, V1, 1 ; 0230
+    let mut rom = vec![0x12, 0x60];
+    rom.resize(0xC0, 0xFF);
+    rom.extend_from_slice(&[0xA2, 0xC8, 0xD0, 0x11, 0x02, 0x30, 0x00, 0x00, 0x80]);
+    let mut vm = Chip8::new();
+    assert!(vm.set_platform(Platform::Chip8Hires).is_ok());
+    assert_eq!(vm.display.size(), (64, 64));
+    assert!(vm.load_bytes(&rom).is_ok());
+    vm.emulate_cycle();
+    assert_eq!(vm.pc(), 0x2C0);
+
+    vm.set_register(0, 3);
+    vm.set_register(1, 50);
+    vm.emulate_cycle();
+    vm.emulate_cycle();
+    assert_eq!(vm.display.gfx[50][3], 1);
+    vm.emulate_cycle();
+    assert_eq!(vm.display.gfx[50][3], 0);
+
+    // the states of a different display size are rejected
+    assert!(Chip8::new().load_state(&vm.save_state()).is_err());
+    // the trick only applies at the entry point
+    vm.execute_opcode(0x1260);
+    assert_eq!(vm.pc(), 0x260);
+}
+
+#[test]
+fn schip_platform() {
+    let mut vm = Chip8::new();
+    assert!(vm.set_platform(Platform::SuperChip).is_ok());
+    vm.set_quirks(Quirks::schip());
+    assert_eq!(vm.display.size(), (64, 32));
+    vm.execute_opcode(0x00FF);
+    assert_eq!(vm.display.size(), (128, 64));
+
+    // a 16x16 square, clipped by the right edge
+    for address in 0x300..0x320 {
+        vm.memory_mut()[address] = 0xFF;
+    }
+    vm.execute_opcode(0xA300);
+    vm.execute_opcode(0x6078);
+    vm.execute_opcode(0x6102);
+    vm.execute_opcode(0xD010);
+    assert_eq!(vm.display.gfx[2][120..128], [1; 8]);
+    assert_eq!((vm.display.gfx[2][0], vm.display.gfx[1][127]), (0, 0));
+    assert_eq!((vm.display.gfx[17][127], vm.display.gfx[18][127]), (1, 0));
+    assert_eq!(vm.register(FLAG), 0);
+
+    // scrolled down by 3 rows, left then right by 4 columns
+    vm.execute_opcode(0x00C3);
+    assert_eq!((vm.display.gfx[4][127], vm.display.gfx[5][127]), (0, 1));
+    assert_eq!((vm.display.gfx[20][127], vm.display.gfx[21][127]), (1, 0));
+    vm.execute_opcode(0x00FC);
+    assert_eq!(vm.display.gfx[5][116..125], [1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    vm.execute_opcode(0x00FB);
+    assert_eq!(vm.display.gfx[5][119..128], [0, 1, 1, 1, 1, 1, 1, 1, 1]);
+    vm.execute_opcode(0xD010);
+    assert_eq!(vm.register(FLAG), 1);
+
+    // the user flags survive a reset
+    vm.execute_opcode(0x6A42);
+    vm.execute_opcode(0xFA75);
+    let state = vm.save_state();
+    vm.reset();
+    assert_eq!(vm.display.size(), (64, 32));
+    vm.execute_opcode(0xFA85);
+    assert_eq!((vm.register(0), vm.register(0xA)), (0x78, 0x42));
+
+    // the states of the high resolution mode
+    let mut other = Chip8::new();
+    assert!(other.load_state(&state).is_err());
+    assert!(other.set_platform(Platform::SuperChip).is_ok());
+    assert!(other.load_state(&state).is_ok());
+    assert_eq!(other.display.size(), (128, 64));
+    other.execute_opcode(0x00FE);
+    assert_eq!(other.display.size(), (64, 32));
+
+    // 00FD ends the program
+    assert!(vm.load_bytes(&[0x00, 0xFD]).is_ok());
+    assert!(!vm.emulate_cycle());
+    assert!(vm.emulate_cycle());
+    vm.reset();
+    assert!(!vm.emulate_cycle());
+}
+
+#[test]
+fn chip8x_colors() {
+    let mut vm = Chip8::new();
+    assert!(vm.set_platform(Platform::Chip8X).is_ok());
+    assert_eq!(vm.pc(), 0x300);
+    let colors = vm.display.colors.clone().unwrap();
+    assert_eq!(colors.zones[31][7], 1);
+
+    // zones 2 to 3 of the rows of zones 1 to 2 in yellow
+    vm.set_register(0, 0x12);
+    vm.set_register(1, 0x11);
+    vm.set_register(2, 5);
+    vm.execute_opcode(0xB020);
+    let colors = vm.display.colors.clone().unwrap();
+    assert_eq!(colors.zones[4][2], 5);
+    assert_eq!(colors.zones[11][3], 5);
+    assert_eq!(colors.zones[12][3], 1);
+    assert_eq!(colors.zones[4][1], 1);
+
+    // 3 rows of the first zone in aqua from the row 30, wrapping around
+    vm.set_register(0, 0x00);
+    vm.set_register(1, 30);
+    vm.set_register(2, 6);
+    vm.execute_opcode(0xB023);
+    let colors = vm.display.colors.clone().unwrap();
+    assert_eq!((colors.zones[30][0], colors.zones[0][0]), (6, 6));
+
+    vm.execute_opcode(0x02A0);
+    assert_eq!(vm.display.colors.as_ref().unwrap().background, 1);
+    vm.set_register(3, 0x77);
+    vm.set_register(4, 0x12);
+    vm.execute_opcode(0x5341);
+    assert_eq!(vm.register(3), 0x01);
+
+    // second keypad and I/O port
+    vm.second_keypad.set_key_state(0x1, Pressed);
+    let pc = vm.pc();
+    vm.execute_opcode(0xE3F2);
+    assert_eq!(vm.pc(), pc + 4);
+    vm.set_io_input(0x42);
+    vm.execute_opcode(0xF5FB);
+    vm.execute_opcode(0xF5F8);
+    assert_eq!(vm.io_output(), 0x42);
+
+    // the colours are part of the state
+    let state = vm.save_state();
+    vm.reset();
+    assert_eq!(vm.display.colors.as_ref().unwrap().background, 0);
+    assert!(vm.load_state(&state).is_ok());
+    assert_eq!(vm.display.colors.as_ref().unwrap().zones[30][0], 6);
+}
+
+#[test]
+fn megachip_mode() {
+    let mut vm = Chip8::new();
+    assert!(vm.set_platform(Platform::MegaChip).is_ok());
+    assert_eq!(vm.memory().len(), 0x1000000);
+    // 0011 is ignored by the other platforms
+    vm.execute_opcode(0x0011);
+    assert!(vm.megachip().is_some());
+    assert_eq!(vm.display.size(), (256, 192));
+
+    // I = 0x012345, from the 4 bytes long 0101 2345 instruction
+    vm.memory_mut()[0x202] = 0x23;
+    vm.memory_mut()[0x203] = 0x45;
+    vm.set_pc(0x200);
+    vm.execute_opcode(0x0101);
+    assert_eq!((vm.index(), vm.pc()), (0x012345, 0x204));
+
+    // 2 palette entries, then a 2x2 sprite with a transparent pixel
+    vm.memory_mut()[0x400..0x408]
+        .copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF]);
+    vm.set_index(0x400);
+    vm.execute_opcode(0x0202);
+    vm.execute_opcode(0x0302);
+    vm.execute_opcode(0x0402);
+    vm.memory_mut()[0x500..0x504].copy_from_slice(&[1, 2, 0, 1]);
+    vm.set_index(0x500);
+    vm.set_register(0, 10);
+    vm.set_register(1, 20);
+    vm.execute_opcode(0xD010);
+    assert_eq!(vm.register(FLAG), 0);
+    // the sprites are shown once presented by 00E0
+    assert_eq!(
+        vm.display.color(10, 20, &Palette::default()),
+        Rgb::new(0, 0, 0)
+    );
+    vm.execute_opcode(0x00E0);
+    let palette = Palette::default();
+    assert_eq!(vm.display.color(10, 20, &palette), Rgb::new(0xFF, 0, 0));
+    assert_eq!(vm.display.color(11, 20, &palette), Rgb::new(0, 0, 0xFF));
+    assert_eq!(vm.display.color(10, 21, &palette), Rgb::new(0, 0, 0));
+    assert_eq!(vm.display.gfx[20][10..12], [1, 1]);
+
+    // collisions only with the collision colour, then a half faded screen
+    vm.execute_opcode(0x0902);
+    vm.execute_opcode(0xD010);
+    assert_eq!(vm.register(FLAG), 0);
+    vm.execute_opcode(0xD010);
+    assert_eq!(vm.register(FLAG), 1);
+    vm.execute_opcode(0x0580);
+    vm.execute_opcode(0x00E0);
+    assert_eq!(vm.display.color(11, 20, &palette), Rgb::new(0, 0, 0x80));
+
+    // 50 % blending with the cleared back buffer
+    vm.execute_opcode(0x0802);
+    vm.execute_opcode(0x05FF);
+    vm.execute_opcode(0xD010);
+    vm.execute_opcode(0x00E0);
+    assert_eq!(vm.display.color(11, 20, &palette), Rgb::new(0, 0, 0x7F));
+
+    // a looping sound of 3 samples at 8 kHz
+    vm.memory_mut()[0x600..0x609].copy_from_slice(&[0x1F, 0x40, 0, 0, 3, 0, 0x80, 0xFF, 0x00]);
+    vm.set_index(0x600);
+    vm.execute_opcode(0x0600);
+    let megachip = vm.megachip().unwrap();
+    let sound = megachip.sound.as_ref().unwrap();
+    assert_eq!((sound.rate, sound.looping), (8000, true));
+    assert_eq!(sound.samples, vec![0x80, 0xFF, 0x00]);
+    assert_eq!(megachip.sound_serial, 1);
+    let playing = Machine::digitised_sound(&vm).map(|(sound, serial)| (sound.rate, serial));
+    assert_eq!(playing, Some((8000, 1)));
+
+    // the MEGA-CHIP mode is part of the state
+    let state = vm.save_state();
+    vm.execute_opcode(0x0700);
+    assert!(Machine::digitised_sound(&vm).is_none());
+    vm.execute_opcode(0x0010);
+    assert!(vm.megachip().is_none());
+    assert_eq!(vm.display.size(), (64, 32));
+    assert!(vm.load_state(&state).is_ok());
+    assert!(vm.megachip().unwrap().sound.is_some());
+    assert_eq!(vm.display.color(11, 20, &palette), Rgb::new(0, 0, 0x7F));
+    // on top of SUPER-CHIP : the scrolling moves the screen shown, and the
+    // resolution applies once the mode is off
+    vm.execute_opcode(0x00C4);
+    assert_eq!(vm.display.color(11, 24, &palette), Rgb::new(0, 0, 0x7F));
+    assert_eq!(vm.display.gfx[24][11], 1);
+    vm.execute_opcode(0x00FB);
+    assert_eq!(vm.display.color(15, 24, &palette), Rgb::new(0, 0, 0x7F));
+    vm.set_register(2, 0x42);
+    vm.execute_opcode(0xF275);
+    vm.execute_opcode(0x00FF);
+    assert_eq!(vm.display.size(), (256, 192));
+    vm.execute_opcode(0x0010);
+    assert_eq!(vm.display.size(), (128, 64));
+    vm.set_register(2, 0);
+    vm.execute_opcode(0xF285);
+    assert_eq!(vm.register(2), 0x42);
 }
@@ -0,0 +1,97 @@
+/*
+ * Smoke test of the C API.
+ *
+ * Build and run from the repository root :
+ *   cargo rustc --lib --release --features capi --crate-type cdylib
+ *   cc -Wall -Iinclude tests/capi/test_capi.c -Ltarget/release -lchip8vm -o test_capi
+ *   LD_LIBRARY_PATH=target/release ./test_capi
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "chip8vm.h"
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+/* Draw the "5" font sprite at (5, 10) then wait for a key in V2. */
+static const uint8_t ROM[] = {
+    0x60, 0x05, /* 0x200 : V0 = 5 */
+    0x61, 0x0A, /* 0x202 : V1 = 10 */
+    0xF0, 0x29, /* 0x204 : I = font(V0) */
+    0xD0, 0x15, /* 0x206 : draw 5 lines at (V0, V1) */
+    0xF2, 0x0A, /* 0x208 : V2 = wait for key */
+    0x12, 0x0A, /* 0x20A : jump to 0x20A */
+};
+
+int main(void) {
+    Chip8 *vm = chip8_create();
+    CHECK(vm != NULL);
+    CHECK(chip8_load(NULL, ROM, sizeof(ROM)) == CHIP8_STATUS_NULL_POINTER);
+    CHECK(chip8_load(vm, ROM, sizeof(ROM)) == CHIP8_STATUS_OK);
+
+    static uint8_t huge[4096];
+    CHECK(chip8_load(vm, huge, sizeof(huge)) == CHIP8_STATUS_ROM_TOO_LARGE);
+    CHECK(chip8_load(vm, ROM, sizeof(ROM)) == CHIP8_STATUS_OK);
+
+    CHECK(chip8_run_frame(vm, 10) == CHIP8_STATUS_OK);
+
+    /* framebuffer */
+    uint32_t width = 0, height = 0;
+    CHECK(chip8_framebuffer(vm, NULL, 0, &width, &height) ==
+          CHIP8_STATUS_BUFFER_TOO_SMALL);
+    CHECK(width == 64 && height == 32);
+    uint8_t pixels[64 * 32];
+    CHECK(chip8_framebuffer(vm, pixels, sizeof(pixels), &width, &height) ==
+          CHIP8_STATUS_OK);
+    /* top line of the "5" sprite is 0xF0 */
+    CHECK(pixels[10 * 64 + 5] && pixels[10 * 64 + 8] && !pixels[10 * 64 + 9]);
+
+    /* registers */
+    Chip8Registers regs;
+    CHECK(chip8_get_registers(vm, &regs) == CHIP8_STATUS_OK);
+    CHECK(regs.v[0] == 5 && regs.v[1] == 10 && regs.pc == 0x208);
+
+    /* snapshot before the key press */
+    size_t size = 0;
+    CHECK(chip8_snapshot(vm, NULL, 0, &size) == CHIP8_STATUS_BUFFER_TOO_SMALL);
+    CHECK(size > 4096);
+    static uint8_t snapshot[8192];
+    CHECK(size <= sizeof(snapshot));
+    CHECK(chip8_snapshot(vm, snapshot, sizeof(snapshot), &size) == CHIP8_STATUS_OK);
+
+    /* keypad */
+    CHECK(chip8_set_key(vm, 0x10, 1) == CHIP8_STATUS_INVALID_ARGUMENT);
+    CHECK(chip8_set_key(vm, 0x7, 1) == CHIP8_STATUS_OK);
+    CHECK(chip8_set_key(vm, 0x7, 0) == CHIP8_STATUS_OK);
+    CHECK(chip8_run_frame(vm, 10) == CHIP8_STATUS_OK);
+    CHECK(chip8_get_registers(vm, &regs) == CHIP8_STATUS_OK);
+    CHECK(regs.v[2] == 7 && regs.pc == 0x20A);
+
+    /* restore */
+    CHECK(chip8_restore(vm, snapshot, 4) == CHIP8_STATUS_INVALID_SNAPSHOT);
+    CHECK(chip8_restore(vm, snapshot, size) == CHIP8_STATUS_OK);
+    CHECK(chip8_get_registers(vm, &regs) == CHIP8_STATUS_OK);
+    CHECK(regs.v[2] == 0 && regs.pc == 0x208);
+
+    /* set registers */
+    regs.v[0xA] = 0x42;
+    regs.pc = 0x20A;
+    CHECK(chip8_set_registers(vm, &regs) == CHIP8_STATUS_OK);
+    memset(&regs, 0, sizeof(regs));
+    CHECK(chip8_get_registers(vm, &regs) == CHIP8_STATUS_OK);
+    CHECK(regs.v[0xA] == 0x42 && regs.pc == 0x20A);
+    regs.pc = 0xFFFF;
+    CHECK(chip8_set_registers(vm, &regs) == CHIP8_STATUS_INVALID_ARGUMENT);
+
+    chip8_destroy(vm);
+    chip8_destroy(NULL);
+    printf("C API test passed\n");
+    return 0;
+}
@@ -0,0 +1,230 @@
+/*
+ * Minimal libretro frontend used to test the core without RetroArch : loads
+ * the core and a ROM, runs some frames while holding a RetroPad button,
+ * checks that a save state can be restored and prints the last frame.
+ *
+ * Build and run from the repository root :
+ *   cargo rustc --lib --release --features libretro --crate-type cdylib
+ *   cc -Wall tests/libretro/harness.c -ldl -o harness
+ *   ./harness target/release/libchip8vm.so ROM [FRAMES]
+ */
+#include <dlfcn.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct retro_system_info {
+    const char *library_name;
+    const char *library_version;
+    const char *valid_extensions;
+    bool need_fullpath;
+    bool block_extract;
+};
+
+struct retro_system_av_info {
+    unsigned base_width, base_height, max_width, max_height;
+    float aspect_ratio;
+    double fps, sample_rate;
+};
+
+struct retro_game_info {
+    const char *path;
+    const void *data;
+    size_t size;
+    const char *meta;
+};
+
+struct retro_variable {
+    const char *key;
+    const char *value;
+};
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                \
+            exit(1);                                                       \
+        }                                                                  \
+    } while (0)
+
+static uint32_t frame[64 * 32];
+static unsigned frame_width, frame_height;
+static size_t audio_frames;
+static unsigned variables_count;
+static unsigned frame_count;
+
+static bool environment(unsigned cmd, void *data) {
+    switch (cmd) {
+    case 10: /* SET_PIXEL_FORMAT */
+        return *(unsigned *)data == 1; /* XRGB8888 */
+    case 11: /* SET_INPUT_DESCRIPTORS */
+        return true;
+    case 15: { /* GET_VARIABLE */
+        struct retro_variable *var = data;
+        if (strcmp(var->key, "chip8vm_palette") == 0) {
+            var->value = "green";
+            return true;
+        }
+        return false;
+    }
+    case 16: { /* SET_VARIABLES */
+        const struct retro_variable *vars = data;
+        for (variables_count = 0; vars[variables_count].key; variables_count++)
+            printf("option %s = %s\n", vars[variables_count].key,
+                   vars[variables_count].value);
+        return true;
+    }
+    case 17: /* GET_VARIABLE_UPDATE */
+        *(bool *)data = false;
+        return true;
+    }
+    return false;
+}
+
+static void video_refresh(const void *data, unsigned width, unsigned height,
+                          size_t pitch) {
+    CHECK(width <= 64 && height <= 32 && pitch == width * 4);
+    memcpy(frame, data, pitch * height);
+    frame_width = width;
+    frame_height = height;
+}
+
+static void audio_sample(int16_t left, int16_t right) {
+    (void)left;
+    (void)right;
+}
+
+static size_t audio_sample_batch(const int16_t *data, size_t frames) {
+    (void)data;
+    audio_frames += frames;
+    return frames;
+}
+
+static void input_poll(void) {}
+
+static int16_t input_state(unsigned port, unsigned device, unsigned index,
+                           unsigned id) {
+    (void)index;
+    /* hold A (key 5) from the 10th frame to the 60th */
+    return port == 0 && device == 1 && id == 8 && frame_count >= 10 &&
+           frame_count < 60;
+}
+
+#define LOAD(handle, name)                                                 \
+    name##_t name = (name##_t)dlsym(handle, #name);                        \
+    CHECK(name != NULL)
+
+typedef void (*retro_init_t)(void);
+typedef void (*retro_deinit_t)(void);
+typedef unsigned (*retro_api_version_t)(void);
+typedef void (*retro_get_system_info_t)(struct retro_system_info *);
+typedef void (*retro_get_system_av_info_t)(struct retro_system_av_info *);
+typedef void (*retro_set_environment_t)(bool (*)(unsigned, void *));
+typedef void (*retro_set_video_refresh_t)(void (*)(const void *, unsigned,
+                                                   unsigned, size_t));
+typedef void (*retro_set_audio_sample_t)(void (*)(int16_t, int16_t));
+typedef void (*retro_set_audio_sample_batch_t)(size_t (*)(const int16_t *,
+                                                          size_t));
+typedef void (*retro_set_input_poll_t)(void (*)(void));
+typedef void (*retro_set_input_state_t)(int16_t (*)(unsigned, unsigned,
+                                                    unsigned, unsigned));
+typedef bool (*retro_load_game_t)(const struct retro_game_info *);
+typedef void (*retro_unload_game_t)(void);
+typedef void (*retro_run_t)(void);
+typedef size_t (*retro_serialize_size_t)(void);
+typedef bool (*retro_serialize_t)(void *, size_t);
+typedef bool (*retro_unserialize_t)(const void *, size_t);
+
+int main(int argc, char **argv) {
+    if (argc < 3) {
+        fprintf(stderr, "usage: %s CORE ROM [FRAMES]\n", argv[0]);
+        return 2;
+    }
+    unsigned frames = argc > 3 ? (unsigned)atoi(argv[3]) : 300;
+
+    void *core = dlopen(argv[1], RTLD_NOW);
+    if (!core) {
+        fprintf(stderr, "%s\n", dlerror());
+        return 1;
+    }
+    LOAD(core, retro_init);
+    LOAD(core, retro_deinit);
+    LOAD(core, retro_api_version);
+    LOAD(core, retro_get_system_info);
+    LOAD(core, retro_get_system_av_info);
+    LOAD(core, retro_set_environment);
+    LOAD(core, retro_set_video_refresh);
+    LOAD(core, retro_set_audio_sample);
+    LOAD(core, retro_set_audio_sample_batch);
+    LOAD(core, retro_set_input_poll);
+    LOAD(core, retro_set_input_state);
+    LOAD(core, retro_load_game);
+    LOAD(core, retro_unload_game);
+    LOAD(core, retro_run);
+    LOAD(core, retro_serialize_size);
+    LOAD(core, retro_serialize);
+    LOAD(core, retro_unserialize);
+
+    CHECK(retro_api_version() == 1);
+    struct retro_system_info info;
+    retro_get_system_info(&info);
+    printf("%s %s (%s)\n", info.library_name, info.library_version,
+           info.valid_extensions);
+
+    retro_set_environment(environment);
+    CHECK(variables_count > 0);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample(audio_sample);
+    retro_set_audio_sample_batch(audio_sample_batch);
+    retro_set_input_poll(input_poll);
+    retro_set_input_state(input_state);
+    retro_init();
+
+    FILE *file = fopen(argv[2], "rb");
+    CHECK(file != NULL);
+    static uint8_t rom[4096];
+    size_t size = fread(rom, 1, sizeof(rom), file);
+    fclose(file);
+    struct retro_game_info game = {argv[2], rom, size, NULL};
+    CHECK(retro_load_game(&game));
+
+    struct retro_system_av_info av;
+    retro_get_system_av_info(&av);
+    CHECK(av.base_width == 64 && av.base_height == 32 && av.fps == 60.0);
+
+    for (frame_count = 0; frame_count < frames / 2; frame_count++)
+        retro_run();
+
+    /* save state round trip */
+    size_t state_size = retro_serialize_size();
+    CHECK(state_size > 0);
+    uint8_t *state = malloc(state_size);
+    CHECK(retro_serialize(state, state_size));
+    for (; frame_count < frames; frame_count++)
+        retro_run();
+    CHECK(retro_unserialize(state, state_size));
+    CHECK(!retro_unserialize(state, state_size - 1));
+    uint8_t *restored = malloc(state_size);
+    CHECK(retro_serialize(restored, state_size));
+    CHECK(memcmp(state, restored, state_size) == 0);
+    free(restored);
+    free(state);
+    retro_run();
+
+    CHECK(audio_frames == (size_t)(frames + 1) * 735);
+
+    for (unsigned y = 0; y < frame_height; y++) {
+        for (unsigned x = 0; x < frame_width; x++)
+            putchar(frame[y * frame_width + x] == frame[0] ? ' ' : '#');
+        putchar('\n');
+    }
+
+    retro_unload_game();
+    retro_deinit();
+    dlclose(core);
+    printf("libretro harness passed\n");
+    return 0;
+}